@@ -0,0 +1,45 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use algos_rs::random::xorshift_32;
+use algos_rs::string::{BoyerMoore, Horspool, Kmp, Searcher, TwoWay};
+
+const TEXT_LEN: usize = 1 << 22;
+const RUNS: u32 = 5;
+
+fn measure<S: Searcher<u32>>(name: &str, build: impl Fn() -> S, text: &[u32]) {
+    let mut best = Duration::MAX;
+    let mut found = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let searcher = build();
+        found = black_box(searcher.find_all(black_box(text))).len();
+        best = best.min(start.elapsed());
+    }
+    println!("    {:<12} {:>10.2?} ({} matches)", name, best, found);
+}
+
+fn bench(title: &str, text: &[u32], pattern_lens: &[usize]) {
+    println!("{}", title);
+    let mut rng = xorshift_32(7);
+    for &len in pattern_lens {
+        let start = rng.next().unwrap() as usize % (text.len() - len);
+        let pattern = &text[start..start + len];
+        println!("  pattern length {}", len);
+        measure("kmp", || Kmp::new(pattern), text);
+        measure("boyer-moore", || BoyerMoore::new(pattern), text);
+        measure("horspool", || Horspool::new(pattern), text);
+        measure("two-way", || TwoWay::new(pattern), text);
+    }
+}
+
+/*
+* Compares the exact matchers on random texts: cargo bench --bench string_search
+*/
+fn main() {
+    let mut rng = xorshift_32(2024);
+    let large: Vec<u32> = (0..TEXT_LEN).map(|_| rng.next().unwrap() % 65536).collect();
+    bench("alphabet of 65536 symbols", &large, &[8, 64, 1024, 16384]);
+    let small: Vec<u32> = (0..TEXT_LEN).map(|_| rng.next().unwrap() % 4).collect();
+    bench("alphabet of 4 symbols", &small, &[8, 64, 1024, 16384]);
+}
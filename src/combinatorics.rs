@@ -20,10 +20,10 @@ pub fn next_permutation<T: Ord + Copy>(arr: &mut [T]) -> Option<()> {
 #[cfg(test)]
 #[test]
 fn test_permutation() {
-    let arr = vec![[0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+    let arr = [[0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
     let mut values = vec![0, 1, 2];
     let mut idx = 0;
-    while let Some(_) = next_permutation(&mut values) {
+    while next_permutation(&mut values).is_some() {
         assert_eq!(vec![values[0], values[1], values[2]], arr[idx]);
         idx += 1;
     }
@@ -48,7 +48,7 @@ pub fn next_subset(arr: &mut [bool], current: &mut usize) -> Option<()> {
 
 #[test]
 fn next_subset_test() {
-    let values = vec![
+    let values = [
         [false, false, false],
         [true, false, false],
         [false, true, false],
@@ -62,7 +62,7 @@ fn next_subset_test() {
     let mut set = vec![false; 3];
     let mut current = 0;
     let mut idx = 0;
-    while let Some(_) = next_subset(&mut set, &mut current) {
+    while next_subset(&mut set, &mut current).is_some() {
         assert_eq!(values[idx], set[..]);
         idx += 1;
     }
@@ -1,4 +1,6 @@
-use std::collections::{BinaryHeap, VecDeque};
+use std::collections::VecDeque;
+
+use crate::structures::{IndexedBinaryHeap, IndexedPriorityQueue, Rmq};
 
 pub type Graph = Vec<Vec<usize>>;
 pub type GraphWithWeights = Vec<Vec<(usize, u32)>>;
@@ -350,44 +352,30 @@ fn find_cycle_oriented_test() {
     find_cycle_oriented(&graph, func);
 }
 
-struct NodeDijkstra {
-    node: usize,
-    dist: u32,
-}
-
-impl PartialEq for NodeDijkstra {
-    fn eq(&self, other: &Self) -> bool {
-        self.dist == other.dist
-    }
-}
-impl Eq for NodeDijkstra {}
-impl PartialOrd for NodeDijkstra {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-impl Ord for NodeDijkstra {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.dist.partial_cmp(&self.dist).unwrap()
-    }
+#[allow(unused)]
+pub fn dijkstra<F>(graph: &GraphWithWeights, from: usize, cb: F)
+where
+    F: FnMut(&[Option<u32>], &[Option<usize>]),
+{
+    dijkstra_with_heap::<IndexedBinaryHeap<u32>, F>(graph, from, cb);
 }
 
-#[allow(unused)]
-pub fn dijkstra<F>(graph: &GraphWithWeights, from: usize, mut cb: F)
+/*
+* Dijkstra's algorithm with any indexed priority queue: every vertex is in the queue at most once,
+* its distance is lowered with decrease_key
+*/
+pub fn dijkstra_with_heap<H, F>(graph: &GraphWithWeights, from: usize, mut cb: F)
 where
+    H: IndexedPriorityQueue<u32>,
     F: FnMut(&[Option<u32>], &[Option<usize>]),
 {
     let mut parents = vec![None; graph.len()];
     let mut visited = vec![false; graph.len()];
     let mut distances = vec![None; graph.len()];
-    let mut heap = BinaryHeap::new();
+    let mut heap = H::with_capacity(graph.len());
     distances[from] = Some(Default::default());
-    heap.push(NodeDijkstra {
-        node: from,
-        dist: 0u32,
-    });
-    while !heap.is_empty() {
-        let NodeDijkstra { node, dist } = heap.pop().unwrap();
+    heap.push(from, 0u32);
+    while let Some((node, dist)) = heap.pop() {
         visited[node] = true;
         for (next_node, next_weight) in &graph[node] {
             if !visited[*next_node]
@@ -396,10 +384,9 @@ where
             {
                 parents[*next_node] = Some(node);
                 distances[*next_node] = Some(next_weight + dist);
-                heap.push(NodeDijkstra {
-                    node: *next_node,
-                    dist: distances[*next_node].unwrap(),
-                });
+                if !heap.decrease_key(*next_node, next_weight + dist) {
+                    heap.push(*next_node, next_weight + dist);
+                }
             }
         }
     }
@@ -422,6 +409,79 @@ fn dijkstra_test() {
     graph[1].push((5, 19)); // Add edge 1 -> 5
 
     dijkstra(&graph, 1, func);
+    dijkstra_with_heap::<crate::structures::PairingHeap<u32>, _>(&graph, 1, func);
+    dijkstra_with_heap::<crate::structures::DaryHeap<u32, 4>, _>(&graph, 1, func);
+}
+
+/*
+* Prim's algorithm: minimum spanning forest of an undirected graph (every edge is stored in both directions).
+* The callback receives the parent of every vertex in the forest and the total weight
+*/
+#[allow(unused)]
+pub fn prim<F>(graph: &GraphWithWeights, cb: F)
+where
+    F: FnMut(&[Option<usize>], u64),
+{
+    prim_with_heap::<IndexedBinaryHeap<u32>, F>(graph, cb);
+}
+
+pub fn prim_with_heap<H, F>(graph: &GraphWithWeights, mut cb: F)
+where
+    H: IndexedPriorityQueue<u32>,
+    F: FnMut(&[Option<usize>], u64),
+{
+    let mut parents = vec![None; graph.len()];
+    let mut in_tree = vec![false; graph.len()];
+    let mut heap = H::with_capacity(graph.len());
+    let mut total = 0u64;
+    for start in 0..graph.len() {
+        if in_tree[start] {
+            continue;
+        }
+        heap.push(start, 0);
+        while let Some((node, weight)) = heap.pop() {
+            in_tree[node] = true;
+            total += weight as u64;
+            for (next_node, next_weight) in &graph[node] {
+                if !in_tree[*next_node]
+                    && (heap.decrease_key(*next_node, *next_weight)
+                        || heap.push(*next_node, *next_weight))
+                {
+                    parents[*next_node] = Some(node);
+                }
+            }
+        }
+    }
+    cb(&parents, total);
+}
+
+#[cfg(test)]
+#[test]
+fn prim_test() {
+    let func = |parents: &[Option<usize>], total: u64| {
+        assert_eq!(total, 13);
+        assert_eq!(
+            parents,
+            [None, Some(0), Some(1), Some(1), Some(2), None, Some(5)]
+        );
+    };
+    let mut graph = vec![Vec::new(); 7];
+    for (a, b, weight) in [
+        (0, 1, 4),
+        (0, 2, 8),
+        (1, 2, 2),
+        (1, 3, 5),
+        (2, 3, 6),
+        (2, 4, 1),
+        (3, 4, 9),
+        (5, 6, 1),
+    ] {
+        graph[a].push((b, weight));
+        graph[b].push((a, weight));
+    }
+    prim(&graph, func);
+    prim_with_heap::<crate::structures::PairingHeap<u32>, _>(&graph, func);
+    prim_with_heap::<crate::structures::DaryHeap<u32, 3>, _>(&graph, func);
 }
 
 #[allow(unused)]
@@ -531,3 +591,112 @@ fn topology_sort_test() {
     };
     topological_sort(&graph, func);
 }
+
+/*
+* Lowest common ancestor in a rooted tree.
+* The tree is reduced to a range minimum query over the depths of its Euler tour,
+* preprocessing in O(n), query in O(1).
+*/
+pub struct Lca {
+    euler: Vec<usize>,
+    first: Vec<Option<usize>>,
+    rmq: Rmq<usize>,
+}
+
+impl Lca {
+    /*
+     * graph - the tree (edges may be stored in both directions), root - its root.
+     * Returns None if the root is not a vertex of the graph
+     */
+    pub fn new(graph: &Graph, root: usize) -> Option<Self> {
+        if root >= graph.len() {
+            return None;
+        }
+        let mut euler = vec![root];
+        let mut depths = vec![0];
+        let mut first = vec![None; graph.len()];
+        first[root] = Some(0);
+        // the Euler tour with an explicit stack of (vertex, index of its next edge), so deep trees don't overflow the call stack
+        let mut stack = vec![(root, 0)];
+        while let Some(&(node, edge)) = stack.last() {
+            if let Some(&next) = graph[node].get(edge) {
+                stack.last_mut().unwrap().1 += 1;
+                if first[next].is_none() {
+                    first[next] = Some(euler.len());
+                    euler.push(next);
+                    depths.push(stack.len());
+                    stack.push((next, 0));
+                }
+            } else {
+                stack.pop();
+                if let Some(&(parent, _)) = stack.last() {
+                    euler.push(parent);
+                    depths.push(stack.len() - 1);
+                }
+            }
+        }
+        Some(Lca {
+            euler,
+            first,
+            rmq: Rmq::from_slice(&depths),
+        })
+    }
+
+    /*
+     * returns the lowest common ancestor of two vertices, or None if one of them isn't reachable from the root
+     */
+    pub fn lca(&self, first: usize, second: usize) -> Option<usize> {
+        let first = (*self.first.get(first)?)?;
+        let second = (*self.first.get(second)?)?;
+        let (l, r) = (first.min(second), first.max(second));
+        self.rmq.query(l, r + 1).map(|idx| self.euler[idx])
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn lca_test() {
+    let mut graph = vec![Vec::new(); 10];
+    for (from, to) in [
+        (0, 1),
+        (0, 2),
+        (1, 3),
+        (1, 4),
+        (4, 5),
+        (2, 6),
+        (6, 7),
+        (6, 8),
+    ] {
+        graph[from].push(to); // Add edge from -> to
+        graph[to].push(from); // Add edge to -> from
+    }
+    let lca = Lca::new(&graph, 0).unwrap();
+    assert_eq!(lca.lca(3, 5), Some(1));
+    assert_eq!(lca.lca(5, 4), Some(4));
+    assert_eq!(lca.lca(7, 8), Some(6));
+    assert_eq!(lca.lca(5, 8), Some(0));
+    assert_eq!(lca.lca(2, 2), Some(2));
+    assert_eq!(lca.lca(9, 1), None);
+    assert_eq!(lca.lca(10, 1), None);
+
+    let lca = Lca::new(&graph, 6).unwrap();
+    assert_eq!(lca.lca(0, 7), Some(6));
+    assert_eq!(lca.lca(3, 5), Some(1));
+
+    // a long path would overflow the call stack with a recursive traversal
+    let n = 300_000;
+    let mut path = vec![Vec::new(); n];
+    for idx in 1..n {
+        path[idx - 1].push(idx);
+        path[idx].push(idx - 1);
+    }
+    let lca = Lca::new(&path, 0).unwrap();
+    assert_eq!(lca.lca(n - 1, n / 2), Some(n / 2));
+    assert_eq!(lca.lca(1, n - 1), Some(1));
+
+    assert!(Lca::new(&vec![], 0).is_none());
+    assert!(Lca::new(&path, n).is_none());
+    let single = Lca::new(&vec![vec![]], 0).unwrap();
+    assert_eq!(single.lca(0, 0), Some(0));
+    assert_eq!(single.lca(0, 1), None);
+}
@@ -27,7 +27,7 @@ pub fn pow(value: u64, n: u64) -> u64 {
     }
     let mut res = 1;
     while n > 0 {
-        if n % 2 != 0 {
+        if !n.is_multiple_of(2) {
             res *= value;
             n -= 1;
         } else {
@@ -53,7 +53,7 @@ pub fn pow_mod(mut value: u64, mut n: u64, m: u64) -> u64 {
     }
     let mut res = 1;
     while n > 0 {
-        if n % 2 != 0 {
+        if !n.is_multiple_of(2) {
             res = (res % m * value % m) % m;
             n -= 1;
         } else {
@@ -84,7 +84,7 @@ pub fn fibonacci(n: u64) -> u64 {
     let mut d = 0;
     let mut rd = 1;
     while n > 0 {
-        if n % 2 != 0 {
+        if !n.is_multiple_of(2) {
             tc = rc;
             rc = rc * a + rd * c;
             rd = tc * b + rd * d;
@@ -1,10 +1,34 @@
+/*
+* Xorshift pseudo-random number generator (Marsaglia), period 2^32 - 1.
+* A zero seed would produce only zeros, so it is replaced by a fixed non-zero value
+*/
+#[derive(Clone, Debug)]
+pub struct XorShift32 {
+    value: u32,
+}
+
+impl Iterator for XorShift32 {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        self.value ^= self.value << 13;
+        self.value ^= self.value >> 17;
+        self.value ^= self.value << 5;
+        Some(self.value)
+    }
+}
+
 #[allow(unused)]
-fn xorshift_32(seed: u32) -> impl Iterator<Item = u32> {
-    let mut value = seed;
-    std::iter::repeat_with(move || {
-        value ^= value << 13;
-        value ^= value >> 17;
-        value ^= value << 5;
-        value
-    })
+pub fn xorshift_32(seed: u32) -> XorShift32 {
+    XorShift32 {
+        value: if seed == 0 { 0x9e37_79b9 } else { seed },
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn xorshift_32_test() {
+    let values: Vec<u32> = xorshift_32(1).take(3).collect();
+    assert_eq!(values, vec![270369, 67634689, 2647435461]);
+    assert!(xorshift_32(0).take(100).all(|value| value != 0));
 }
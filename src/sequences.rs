@@ -1,24 +1,153 @@
-/*
-* Search for the nearest next smaller element for each element of the array
-* asymptotics of O(n)
-* nse is the next smallest element
- */
-
 use std::num::TryFromIntError;
 
+use crate::structures::Fenwick;
+
+/*
+* Search for the nearest next element satisfying the relation for each element of the array:
+* for every idx the smallest j > idx with rel(&arr[j], &arr[idx]) is found.
+* rel must be a transitive comparison (<, >, <=, >= or the same relation under another order)
+* asymptotic of O(n)
+*/
 #[allow(unused)]
-pub fn search_nearest_nse_for_each_element(arr: &[u32]) -> Vec<Option<usize>> {
-    let mut st = Vec::new();
+pub fn search_nearest_next_by<T, F>(arr: &[T], rel: F) -> Vec<Option<usize>>
+where
+    F: Fn(&T, &T) -> bool,
+{
+    let mut st: Vec<usize> = Vec::new();
     let mut next = vec![None; arr.len()];
     for idx in 0..arr.len() {
-        while !st.is_empty() && arr[idx] < arr[*st.last().unwrap()] {
-            next[st.pop().unwrap()] = Some(idx);
+        while let Some(&top) = st.last() {
+            if !rel(&arr[idx], &arr[top]) {
+                break;
+            }
+            next[top] = Some(idx);
+            st.pop();
         }
         st.push(idx);
     }
     next
 }
 
+/*
+* Search for the nearest previous element satisfying the relation for each element of the array:
+* for every idx the largest j < idx with rel(&arr[j], &arr[idx]) is found.
+* rel must be a transitive comparison (<, >, <=, >= or the same relation under another order)
+* asymptotic of O(n)
+*/
+#[allow(unused)]
+pub fn search_nearest_prev_by<T, F>(arr: &[T], rel: F) -> Vec<Option<usize>>
+where
+    F: Fn(&T, &T) -> bool,
+{
+    let mut st: Vec<usize> = Vec::new();
+    let mut prev = vec![None; arr.len()];
+    for idx in 0..arr.len() {
+        while let Some(&top) = st.last() {
+            if rel(&arr[top], &arr[idx]) {
+                break;
+            }
+            st.pop();
+        }
+        prev[idx] = st.last().copied();
+        st.push(idx);
+    }
+    prev
+}
+
+#[allow(unused)]
+pub fn search_nearest_next_smaller<T: Ord>(arr: &[T]) -> Vec<Option<usize>> {
+    search_nearest_next_by(arr, |a, b| a < b)
+}
+
+#[allow(unused)]
+pub fn search_nearest_next_greater<T: Ord>(arr: &[T]) -> Vec<Option<usize>> {
+    search_nearest_next_by(arr, |a, b| a > b)
+}
+
+#[allow(unused)]
+pub fn search_nearest_next_smaller_or_equal<T: Ord>(arr: &[T]) -> Vec<Option<usize>> {
+    search_nearest_next_by(arr, |a, b| a <= b)
+}
+
+#[allow(unused)]
+pub fn search_nearest_next_greater_or_equal<T: Ord>(arr: &[T]) -> Vec<Option<usize>> {
+    search_nearest_next_by(arr, |a, b| a >= b)
+}
+
+#[allow(unused)]
+pub fn search_nearest_prev_smaller<T: Ord>(arr: &[T]) -> Vec<Option<usize>> {
+    search_nearest_prev_by(arr, |a, b| a < b)
+}
+
+#[allow(unused)]
+pub fn search_nearest_prev_greater<T: Ord>(arr: &[T]) -> Vec<Option<usize>> {
+    search_nearest_prev_by(arr, |a, b| a > b)
+}
+
+#[allow(unused)]
+pub fn search_nearest_prev_smaller_or_equal<T: Ord>(arr: &[T]) -> Vec<Option<usize>> {
+    search_nearest_prev_by(arr, |a, b| a <= b)
+}
+
+#[allow(unused)]
+pub fn search_nearest_prev_greater_or_equal<T: Ord>(arr: &[T]) -> Vec<Option<usize>> {
+    search_nearest_prev_by(arr, |a, b| a >= b)
+}
+
+#[cfg(test)]
+#[test]
+fn search_nearest_by_test() {
+    let array = [3, 1, 3, 2, 3, 5, 1];
+    assert_eq!(
+        search_nearest_next_smaller(&array),
+        vec![Some(1), None, Some(3), Some(6), Some(6), Some(6), None]
+    );
+    assert_eq!(
+        search_nearest_next_smaller_or_equal(&array),
+        vec![Some(1), Some(6), Some(3), Some(6), Some(6), Some(6), None]
+    );
+    assert_eq!(
+        search_nearest_next_greater(&array),
+        vec![Some(5), Some(2), Some(5), Some(4), Some(5), None, None]
+    );
+    assert_eq!(
+        search_nearest_next_greater_or_equal(&array),
+        vec![Some(2), Some(2), Some(4), Some(4), Some(5), None, None]
+    );
+    assert_eq!(
+        search_nearest_prev_smaller(&array),
+        vec![None, None, Some(1), Some(1), Some(3), Some(4), None]
+    );
+    assert_eq!(
+        search_nearest_prev_smaller_or_equal(&array),
+        vec![None, None, Some(1), Some(1), Some(3), Some(4), Some(1)]
+    );
+    assert_eq!(
+        search_nearest_prev_greater(&array),
+        vec![None, Some(0), None, Some(2), None, None, Some(5)]
+    );
+    assert_eq!(
+        search_nearest_prev_greater_or_equal(&array),
+        vec![None, Some(0), Some(0), Some(2), Some(2), None, Some(5)]
+    );
+    // a custom order: the nearest next word that is shorter
+    let words = ["tree", "a", "forest", "leaf", "oak"];
+    assert_eq!(
+        search_nearest_next_by(&words, |a, b| a.len() < b.len()),
+        vec![Some(1), None, Some(3), Some(4), None]
+    );
+}
+
+/*
+* Search for the nearest next smaller element for each element of the array
+* asymptotics of O(n)
+* nse is the next smallest element
+ */
+#[allow(unused)]
+pub fn search_nearest_nse_for_each_element(arr: &[u32]) -> Vec<Option<usize>> {
+    search_nearest_next_smaller(arr)
+}
+
 #[cfg(test)]
 #[test]
 fn search_nearest_nse_for_each_element_test() {
@@ -49,20 +178,10 @@ fn search_nearest_nse_for_each_element_test() {
 * pse is the previous smaller element
 */
 #[allow(unused)]
-fn search_nearest_pse_for_each_element(arr: &[u32]) -> Vec<Option<usize>> {
-    let mut st: Vec<Option<usize>> = Vec::new();
-    let mut prev = vec![None; arr.len()];
-    for idx in 0..arr.len() {
-        while !st.is_empty() && arr[idx] <= arr[(st.last().unwrap()).unwrap()] {
-            st.pop();
-        }
-        if !st.is_empty() {
-            prev[idx] = *st.last().unwrap();
-        }
-        st.push(Some(idx));
-    }
-    prev
+pub fn search_nearest_pse_for_each_element(arr: &[u32]) -> Vec<Option<usize>> {
+    search_nearest_prev_smaller(arr)
 }
+
 #[cfg(test)]
 #[test]
 fn search_nearest_pse_for_each_element_test() {
@@ -87,6 +206,126 @@ fn search_nearest_pse_for_each_element_test() {
     );
 }
 
+/*
+* Cartesian tree (min-heap ordered, in-order traversal gives the original array).
+* Among equal elements the leftmost one is the ancestor.
+* The parent of an element is the larger one of its previous smaller-or-equal and next smaller elements,
+* asymptotic of O(n)
+*/
+#[derive(Debug, PartialEq)]
+pub struct CartesianTree {
+    pub root: Option<usize>,
+    pub parents: Vec<Option<usize>>,
+    pub left: Vec<Option<usize>>,
+    pub right: Vec<Option<usize>>,
+}
+
+#[allow(unused)]
+pub fn cartesian_tree<T: Ord>(arr: &[T]) -> CartesianTree {
+    let prev = search_nearest_prev_smaller_or_equal(arr);
+    let next = search_nearest_next_smaller(arr);
+    let mut tree = CartesianTree {
+        root: None,
+        parents: vec![None; arr.len()],
+        left: vec![None; arr.len()],
+        right: vec![None; arr.len()],
+    };
+    for idx in 0..arr.len() {
+        let parent = match (prev[idx], next[idx]) {
+            (Some(l), Some(r)) if arr[l] > arr[r] => Some(l),
+            (_, Some(r)) => Some(r),
+            (l, None) => l,
+        };
+        tree.parents[idx] = parent;
+        match parent {
+            None => tree.root = Some(idx),
+            Some(parent) if parent > idx => tree.left[parent] = Some(idx),
+            Some(parent) => tree.right[parent] = Some(idx),
+        }
+    }
+    tree
+}
+
+#[cfg(test)]
+#[test]
+fn cartesian_tree_test() {
+    // value(index)
+    //         1(3)
+    //        /    \
+    //     3(1)    2(4)
+    //     /  \
+    //  5(0)  4(2)
+    let tree = cartesian_tree(&[5, 3, 4, 1, 2]);
+    assert_eq!(tree.root, Some(3));
+    assert_eq!(tree.parents, vec![Some(1), Some(3), Some(1), None, Some(3)]);
+    assert_eq!(tree.left, vec![None, Some(0), None, Some(1), None]);
+    assert_eq!(tree.right, vec![None, Some(2), None, Some(4), None]);
+
+    let tree = cartesian_tree(&[1, 3, 1]);
+    assert_eq!(tree.root, Some(0));
+    assert_eq!(tree.parents, vec![None, Some(2), Some(0)]);
+    assert_eq!(tree.left[2], Some(1));
+    assert_eq!(tree.right[0], Some(2));
+
+    assert_eq!(cartesian_tree::<u32>(&[]).root, None);
+}
+
+/*
+* Largest rectangle in a histogram. Every bar is extended to the left and to the right
+* up to the nearest smaller bars
+* asymptotic of O(n)
+*/
+#[allow(unused)]
+pub fn largest_rectangle_in_histogram(heights: &[u64]) -> u64 {
+    let prev = search_nearest_prev_smaller(heights);
+    let next = search_nearest_next_smaller(heights);
+    let mut res = 0;
+    for idx in 0..heights.len() {
+        let left = prev[idx].map_or(0, |l| l + 1);
+        let right = next[idx].unwrap_or(heights.len());
+        res = std::cmp::max(res, heights[idx] * (right - left) as u64);
+    }
+    res
+}
+
+#[cfg(test)]
+#[test]
+fn largest_rectangle_in_histogram_test() {
+    assert_eq!(largest_rectangle_in_histogram(&[2, 1, 5, 6, 2, 3]), 10);
+    assert_eq!(largest_rectangle_in_histogram(&[2, 4]), 4);
+    assert_eq!(largest_rectangle_in_histogram(&[3, 3, 3]), 9);
+    assert_eq!(largest_rectangle_in_histogram(&[]), 0);
+}
+
+/*
+* Sum of minimums over all subarrays. Every element is the minimum of the subarrays that start after
+* its previous smaller element and end before its next smaller-or-equal element
+* (equal minimums are counted once, for the rightmost of them)
+* asymptotic of O(n)
+*/
+#[allow(unused)]
+pub fn sum_of_subarray_minimums(arr: &[i64]) -> i64 {
+    let prev = search_nearest_prev_smaller(arr);
+    let next = search_nearest_next_smaller_or_equal(arr);
+    let mut res = 0;
+    for idx in 0..arr.len() {
+        let left = (idx - prev[idx].map_or(0, |l| l + 1) + 1) as i64;
+        let right = (next[idx].unwrap_or(arr.len()) - idx) as i64;
+        res += arr[idx] * left * right;
+    }
+    res
+}
+
+#[cfg(test)]
+#[test]
+fn sum_of_subarray_minimums_test() {
+    assert_eq!(sum_of_subarray_minimums(&[3, 1, 2, 4]), 17);
+    assert_eq!(sum_of_subarray_minimums(&[11, 81, 94, 43, 3]), 444);
+    assert_eq!(sum_of_subarray_minimums(&[2, 2, 2]), 12);
+    assert_eq!(sum_of_subarray_minimums(&[-1, 5]), 3);
+    assert_eq!(sum_of_subarray_minimums(&[]), 0);
+}
+
 // Search insert position of K in a sorted array
 #[allow(unused)]
 pub fn search_insert_pos(arr: &[i32], k: i32) -> Result<usize, TryFromIntError> {
@@ -172,3 +411,34 @@ fn lcs_test() {
     let b = [10, 10, 1, 2, 3, 10];
     assert_eq!(lcs(&a, &b), vec![1, 2, 3]);
 }
+
+/*
+* Count inversions (pairs i < j with arr[i] > arr[j]) with a Fenwick tree over the compressed values
+* asymptotic of O(n log n)
+*/
+#[allow(unused)]
+pub fn count_inversions<T: Ord>(arr: &[T]) -> u64 {
+    let mut sorted: Vec<&T> = arr.iter().collect();
+    sorted.sort();
+    sorted.dedup();
+    let mut fenwick = Fenwick::<u64>::new(sorted.len());
+    let mut res = 0;
+    for (idx, value) in arr.iter().enumerate() {
+        let rank = sorted.binary_search(&value).unwrap();
+        // elements seen so far that are not greater than value
+        res += idx as u64 - fenwick.prefix_sum(rank + 1);
+        fenwick.add(rank, 1);
+    }
+    res
+}
+
+#[cfg(test)]
+#[test]
+fn count_inversions_test() {
+    assert_eq!(count_inversions(&[2, 4, 1, 3, 5]), 3);
+    assert_eq!(count_inversions::<i32>(&[]), 0);
+    assert_eq!(count_inversions(&[1, 2, 3, 4]), 0);
+    assert_eq!(count_inversions(&[4, 3, 2, 1]), 6);
+    assert_eq!(count_inversions(&[3, 3, 1, 3]), 2);
+    assert_eq!(count_inversions(&["b", "a", "c", "a"]), 3);
+}
@@ -1,18 +1,31 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::Hash;
+
+use rand::Rng;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::structures::Rmq;
 
 #[allow(unused)]
 pub fn prefix_function(src: &str) -> Vec<usize> {
+    prefix_function_of(src.as_bytes())
+}
+
+/*
+* prefix function of a sequence of any comparable elements
+*/
+pub fn prefix_function_of<T: Eq>(src: &[T]) -> Vec<usize> {
     if src.is_empty() {
         return vec![];
     }
     let mut prefix = vec![0; src.len()];
-    let bytes = src.as_bytes();
     for i in 1..src.len() {
         let mut j = prefix[i - 1];
-        while j > 0 && bytes[i] != bytes[j] {
+        while j > 0 && src[i] != src[j] {
             j = prefix[j - 1];
         }
-        if bytes[i] == bytes[j] {
+        if src[i] == src[j] {
             j += 1;
         }
         prefix[i] = j;
@@ -31,15 +44,21 @@ fn test_prefix_function() {
 
 #[allow(unused)]
 pub fn z_function(src: &str) -> Vec<usize> {
+    z_function_of(src.as_bytes())
+}
+
+/*
+* z function of a sequence of any comparable elements
+*/
+pub fn z_function_of<T: Eq>(src: &[T]) -> Vec<usize> {
     let mut z = vec![0; src.len()];
-    let bytes = src.as_bytes();
     let mut l = 0usize;
     let mut r = 0usize;
     for i in 1..src.len() {
         if i <= r {
             z[i] = std::cmp::min(r - i + 1, z[i - l]);
         }
-        while i + z[i] < src.len() && bytes[z[i]] == bytes[i + z[i]] {
+        while i + z[i] < src.len() && src[z[i]] == src[i + z[i]] {
             z[i] += 1;
         }
         if i + z[i] - 1 > r {
@@ -87,60 +106,288 @@ fn test_hash() {
     assert_eq!(hash("abcd"), hash("abcd"));
 }
 
-#[allow(unused)]
-pub fn rabin_karp(t: &str, p: &str) -> Vec<usize> {
-    if t.is_empty() || p.is_empty() {
+/*
+* Modulus of the polynomial hash. Dual hashes modulo 1e9+7 and 998244353 at once and packs both values into one u64
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashModulus {
+    Mersenne61,
+    Dual,
+}
+
+const HASH_MERSENNE_61: u64 = (1 << 61) - 1;
+const HASH_DUAL: (u64, u64) = (1_000_000_007, 998_244_353);
+
+/*
+* Parameters of the polynomial hash h(s) = s[0] * base^(n-1) + ... + s[n-1] over the chosen modulus
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RollingHasher {
+    modulus: HashModulus,
+    base: u64,
+}
+
+impl RollingHasher {
+    /*
+     * returns None if the base is not in 2..modulus (the smaller modulus for the dual mode)
+     */
+    pub fn new(modulus: HashModulus, base: u64) -> Option<Self> {
+        if base < 2 || base >= Self::max_base(modulus) {
+            return None;
+        }
+        Some(RollingHasher { modulus, base })
+    }
+
+    /*
+     * base chosen at random, so nobody can prepare colliding inputs in advance
+     */
+    pub fn random(modulus: HashModulus) -> Self {
+        let base = rand::rng().random_range(256..Self::max_base(modulus));
+        RollingHasher { modulus, base }
+    }
+
+    fn max_base(modulus: HashModulus) -> u64 {
+        match modulus {
+            HashModulus::Mersenne61 => HASH_MERSENNE_61,
+            HashModulus::Dual => HASH_DUAL.1,
+        }
+    }
+
+    pub fn modulus(&self) -> HashModulus {
+        self.modulus
+    }
+
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    pub fn hash<T: Copy + Into<u64>>(&self, s: &[T]) -> u64 {
+        s.iter().fold(0, |hash, symbol| self.push(hash, *symbol))
+    }
+
+    /*
+     * prefix hashes of the sequence for O(1) substring hashes
+     */
+    pub fn prefixes<T: Copy + Into<u64>>(&self, s: &[T]) -> RollingHash {
+        RollingHash::new(*self, s)
+    }
+
+    fn push<T: Into<u64>>(&self, hash: u64, symbol: T) -> u64 {
+        self.add(
+            self.mul(hash, self.reduce(self.base)),
+            self.reduce(symbol.into()),
+        )
+    }
+
+    fn pow(&self, exp: usize) -> u64 {
+        let (mut res, mut base, mut exp) = (self.reduce(1), self.reduce(self.base), exp);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                res = self.mul(res, base);
+            }
+            base = self.mul(base, base);
+            exp >>= 1;
+        }
+        res
+    }
+
+    fn reduce(&self, value: u64) -> u64 {
+        match self.modulus {
+            HashModulus::Mersenne61 => value % HASH_MERSENNE_61,
+            HashModulus::Dual => ((value % HASH_DUAL.0) << 32) | (value % HASH_DUAL.1),
+        }
+    }
+
+    fn add(&self, a: u64, b: u64) -> u64 {
+        self.combine(a, b, |a, b, modulus| (a + b) % modulus)
+    }
+
+    fn sub(&self, a: u64, b: u64) -> u64 {
+        self.combine(a, b, |a, b, modulus| (a + modulus - b) % modulus)
+    }
+
+    fn mul(&self, a: u64, b: u64) -> u64 {
+        self.combine(a, b, |a, b, modulus| {
+            ((a as u128 * b as u128) % modulus as u128) as u64
+        })
+    }
+
+    fn combine<F: Fn(u64, u64, u64) -> u64>(&self, a: u64, b: u64, op: F) -> u64 {
+        match self.modulus {
+            HashModulus::Mersenne61 => op(a, b, HASH_MERSENNE_61),
+            HashModulus::Dual => {
+                let low = u32::MAX as u64;
+                (op(a >> 32, b >> 32, HASH_DUAL.0) << 32) | op(a & low, b & low, HASH_DUAL.1)
+            }
+        }
+    }
+}
+
+/*
+* Prefix hashes of a sequence: the hash of any substring in O(1) after O(n) preprocessing
+*/
+pub struct RollingHash {
+    hasher: RollingHasher,
+    prefix: Vec<u64>,
+    powers: Vec<u64>,
+}
+
+impl RollingHash {
+    pub fn new<T: Copy + Into<u64>>(hasher: RollingHasher, s: &[T]) -> Self {
+        let mut prefix = vec![0; s.len() + 1];
+        let mut powers = vec![hasher.reduce(1); s.len() + 1];
+        for (i, symbol) in s.iter().enumerate() {
+            prefix[i + 1] = hasher.push(prefix[i], *symbol);
+            powers[i + 1] = hasher.mul(powers[i], hasher.reduce(hasher.base));
+        }
+        RollingHash {
+            hasher,
+            prefix,
+            powers,
+        }
+    }
+
+    pub fn hasher(&self) -> &RollingHasher {
+        &self.hasher
+    }
+
+    pub fn len(&self) -> usize {
+        self.prefix.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /*
+     * hash of s[range], equal to hasher.hash(&s[range]). None if the range is out of bounds
+     */
+    pub fn substring(&self, range: std::ops::Range<usize>) -> Option<u64> {
+        if range.start > range.end || range.end > self.len() {
+            return None;
+        }
+        let shifted = self
+            .hasher
+            .mul(self.prefix[range.start], self.powers[range.len()]);
+        Some(self.hasher.sub(self.prefix[range.end], shifted))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn rolling_hash_test() {
+    assert!(RollingHasher::new(HashModulus::Mersenne61, 1).is_none());
+    assert!(RollingHasher::new(HashModulus::Dual, HASH_DUAL.1).is_none());
+    let mut rng = crate::random::xorshift_32(46);
+    for modulus in [HashModulus::Mersenne61, HashModulus::Dual] {
+        let hasher = RollingHasher::random(modulus);
+        assert_eq!(hasher.modulus(), modulus);
+        let s: Vec<u8> = (0..300)
+            .map(|_| b"ab"[(rng.next().unwrap() % 2) as usize])
+            .collect();
+        let hash = hasher.prefixes(&s);
+        assert_eq!(hash.len(), s.len());
+        assert_eq!(hash.substring(0..s.len()), Some(hasher.hash(&s)));
+        assert_eq!(hash.substring(5..301), None);
+        for _ in 0..500 {
+            let len = (rng.next().unwrap() % 6) as usize;
+            let first = (rng.next().unwrap() as usize) % (s.len() - len);
+            let second = (rng.next().unwrap() as usize) % (s.len() - len);
+            let (a, b) = (first..first + len, second..second + len);
+            assert_eq!(hash.substring(a.clone()), Some(hasher.hash(&s[a.clone()])));
+            assert_eq!(
+                hash.substring(a.clone()) == hash.substring(b.clone()),
+                s[a] == s[b]
+            );
+        }
+        let chars: Vec<char> = "строка строка".chars().collect();
+        let hash = RollingHash::new(hasher, &chars);
+        assert_eq!(hash.substring(0..6), hash.substring(7..13));
+    }
+    let hasher = RollingHasher::new(HashModulus::Dual, 131).unwrap();
+    assert_eq!(
+        hasher.hash(b"ab"),
+        ((97 * 131 + 98) << 32) | (97 * 131 + 98)
+    );
+}
+
+/*
+* positions of all occurrences of p in t by comparing rolling hashes of the windows of t.
+* With verify every hash match is compared symbol by symbol, so no false positives are reported
+*/
+pub fn rabin_karp_of<T: Copy + Into<u64> + Eq>(
+    t: &[T],
+    p: &[T],
+    hasher: &RollingHasher,
+    verify: bool,
+) -> Vec<usize> {
+    if p.is_empty() || t.len() < p.len() {
         return vec![];
     }
-    let bytes = t.as_bytes();
+    let leading = hasher.pow(p.len() - 1);
+    let hash_p = hasher.hash(p);
+    let mut hash_t = hasher.hash(&t[..p.len()]);
     let mut res = vec![];
-    let hash_p = hash2(p);
-    let mut hash_t = hash2(&t[0..p.len()]);
-    for i in 0..t.len() - p.len() {
-        if hash_p == hash_t {
+    for i in 0..=t.len() - p.len() {
+        if i > 0 {
+            let leaving = hasher.mul(hasher.reduce(t[i - 1].into()), leading);
+            hash_t = hasher.push(hasher.sub(hash_t, leaving), t[i + p.len() - 1]);
+        }
+        if hash_t == hash_p && (!verify || t[i..i + p.len()] == *p) {
             res.push(i);
         }
-        hash_t = hash_t
-            .wrapping_mul(31u64)
-            .wrapping_add(bytes[i + p.len()] as u64)
-            .wrapping_sub((bytes[i] as u64).wrapping_mul(31u64.wrapping_pow(p.len() as u32)));
-    }
-    if hash_p == hash_t {
-        res.push(t.len() - p.len());
     }
     res
 }
 
+/*
+* verified Rabin–Karp over bytes with a random base modulo 2^61-1
+*/
+#[allow(unused)]
+pub fn rabin_karp(t: &str, p: &str) -> Vec<usize> {
+    rabin_karp_of(
+        t.as_bytes(),
+        p.as_bytes(),
+        &RollingHasher::random(HashModulus::Mersenne61),
+        true,
+    )
+}
+
 #[cfg(test)]
 #[test]
 fn test_rabin_karp() {
     assert_eq!(rabin_karp("abcxabcxyabcxyz", "abc"), vec![0, 4, 9]);
     assert_eq!(rabin_karp("ababaac", "bab"), vec![1]);
     assert_eq!(rabin_karp("ababcxabdabcxabcxabcde", "abcxabcde"), vec![13]);
+    assert_eq!(rabin_karp("ab", "abc"), vec![]);
+    assert_eq!(rabin_karp("aaaa", "aa"), vec![0, 1, 2]);
+
+    // with the base 2 the windows [1, 0] and [0, 2] have the same hash
+    let weak = RollingHasher::new(HashModulus::Dual, 2).unwrap();
+    let (t, p) = ([1u8, 0, 0, 2], [0u8, 2]);
+    assert_eq!(rabin_karp_of(&t, &p, &weak, false), vec![0, 2]);
+    assert_eq!(rabin_karp_of(&t, &p, &weak, true), vec![2]);
+    let mut rng = crate::random::xorshift_32(146);
+    for _ in 0..100 {
+        let t: Vec<u8> = (0..200).map(|_| (rng.next().unwrap() % 3) as u8).collect();
+        let p: Vec<u8> = (0..1 + rng.next().unwrap() % 4)
+            .map(|_| (rng.next().unwrap() % 3) as u8)
+            .collect();
+        let hasher = RollingHasher::random(HashModulus::Dual);
+        assert_eq!(rabin_karp_of(&t, &p, &hasher, true), kmp_of(&t, &p));
+    }
 }
 
 #[allow(unused)]
 pub fn kmp(t: &str, p: &str) -> Vec<usize> {
-    if t.is_empty() || p.is_empty() {
-        return vec![];
-    }
-    let mut res = vec![];
-    let prefix = prefix_function(p);
-    let bytes = p.as_bytes();
-    let mut idx = 0;
-    for (i, value) in t.as_bytes().iter().enumerate() {
-        while idx > 0 && bytes[idx] != *value {
-            idx = prefix[idx - 1];
-        }
-        if bytes[idx] == *value {
-            idx += 1;
-        }
-        if idx == p.len() {
-            res.push(i + 1 - idx);
-            idx = prefix[idx - 1];
-        }
-    }
-    res
+    kmp_of(t.as_bytes(), p.as_bytes())
+}
+
+/*
+* positions of all occurrences of the pattern p in the sequence t
+*/
+pub fn kmp_of<T: Eq>(t: &[T], p: &[T]) -> Vec<usize> {
+    Kmp::new(p).find_all(t)
 }
 
 #[cfg(test)]
@@ -153,303 +400,4174 @@ fn test_kmp() {
     assert_eq!(kmp("abcdabcd", "abc"), vec![0, 4]);
 }
 
-#[allow(unused)]
-pub fn compress(s: &str) -> (&str, usize) {
-    let k = s.len() - prefix_function(s)[s.len() - 1];
-    match s.len() % k {
-        0 => (&s[..k], s.len() / k),
-        _ => (s, 1),
+/*
+* Exact pattern matcher preprocessed once for the pattern and reused for many texts
+*/
+pub trait Searcher<T> {
+    fn pattern(&self) -> &[T];
+
+    /*
+     * calls f with the positions of the occurrences starting at or after start in increasing order
+     * until f returns false. The empty pattern has no occurrences
+     */
+    fn search(&self, text: &[T], start: usize, f: &mut dyn FnMut(usize) -> bool);
+
+    fn find_at(&self, text: &[T], start: usize) -> Option<usize> {
+        let mut res = None;
+        self.search(text, start, &mut |pos| {
+            res = Some(pos);
+            false
+        });
+        res
+    }
+
+    fn find(&self, text: &[T]) -> Option<usize> {
+        self.find_at(text, 0)
+    }
+
+    /*
+     * all occurrences, overlapping ones included
+     */
+    fn find_all(&self, text: &[T]) -> Vec<usize> {
+        let mut res = vec![];
+        self.search(text, 0, &mut |pos| {
+            res.push(pos);
+            true
+        });
+        res
     }
 }
 
-#[cfg(test)]
-#[test]
-fn test() {
-    assert_eq!(compress("abcabcabc"), ("abc", 3));
-    assert_eq!(compress("abcd"), ("abcd", 1));
+/*
+* Knuth–Morris–Pratt: O(n + m), reads every symbol of the text once
+*/
+pub struct Kmp<'a, T> {
+    pattern: &'a [T],
+    prefix: Vec<usize>,
 }
 
-#[allow(unused)]
-pub fn levenshtein_distance(
-    first: &str,
-    second: &str,
-    delete_cost: u32,
-    insert_cost: u32,
-    replace_cost: u32,
-) -> u32 {
-    let first = first.as_bytes();
-    let second = second.as_bytes();
-    let mut dist = vec![0; first.len() + 1];
-    for j in 1..(first.len() + 1) {
-        dist[j] = dist[j - 1] + insert_cost;
-    }
-    for i in 1..second.len() + 1 {
-        let mut dist_temp = vec![0; first.len() + 1];
-        dist_temp[0] = dist[0] + delete_cost;
-        for j in 1..(first.len() + 1) {
-            if second[i - 1] != first[j - 1] {
-                dist_temp[j] = std::cmp::min(
-                    std::cmp::min(dist[j] + delete_cost, dist[j - 1] + insert_cost),
-                    dist_temp[j - 1] + replace_cost,
-                );
-            } else {
-                dist_temp[j] = dist[j - 1];
-            }
+impl<'a, T: Eq> Kmp<'a, T> {
+    pub fn new(pattern: &'a [T]) -> Self {
+        Kmp {
+            pattern,
+            prefix: prefix_function_of(pattern),
         }
-        dist = dist_temp;
     }
-    dist[first.len()]
 }
 
-#[cfg(test)]
-#[test]
-fn test_levenshtein_distance() {
-    assert_eq!(
-        levenshtein_distance("POLYNOMIAL", "EXPONENTIAL", 1, 1, 1),
-        6
-    );
-    assert_eq!(levenshtein_distance("abcdasdasd", "cddabcd", 1, 1, 1), 6);
-    assert_eq!(levenshtein_distance("", "", 1, 1, 1), 0);
-    assert_eq!(levenshtein_distance("aaa", "aaa", 1, 1, 1), 0);
-    assert_eq!(levenshtein_distance("", "aaa", 1, 1, 1), 3);
-}
+impl<T: Eq> Searcher<T> for Kmp<'_, T> {
+    fn pattern(&self) -> &[T] {
+        self.pattern
+    }
 
-#[allow(unused)]
-#[derive(Clone)]
-struct VertexAhoCorasick {
-    children: BTreeMap<i32, i32>,
-    link: i32,
-    good_link: i32,
-    pat_num: i32,
-    pch: i32,
-    parent: i32,
+    fn search(&self, text: &[T], start: usize, f: &mut dyn FnMut(usize) -> bool) {
+        let p = self.pattern;
+        if p.is_empty() {
+            return;
+        }
+        let mut idx = 0;
+        for (i, value) in text.iter().enumerate().skip(start) {
+            while idx > 0 && p[idx] != *value {
+                idx = self.prefix[idx - 1];
+            }
+            if p[idx] == *value {
+                idx += 1;
+            }
+            if idx == p.len() {
+                if !f(i + 1 - idx) {
+                    return;
+                }
+                idx = self.prefix[idx - 1];
+            }
+        }
+    }
 }
 
-#[allow(unused)]
-struct TrieAhoCorasick {
-    arr: Vec<VertexAhoCorasick>,
-    sz: i32,
+/*
+* Boyer–Moore with the bad character and the strong good suffix rules. Compares the pattern from its end,
+* so long patterns skip most of the text. O(nm) in the worst case, sublinear on average
+*/
+pub struct BoyerMoore<'a, T> {
+    pattern: &'a [T],
+    last: HashMap<&'a T, usize>,
+    // good_suffix[j] is the shift after the suffix pattern[j..] matched and pattern[j - 1] didn't
+    good_suffix: Vec<usize>,
 }
 
-#[allow(unused)]
-impl TrieAhoCorasick {
-    fn new() -> TrieAhoCorasick {
-        TrieAhoCorasick {
-            arr: vec![
-                VertexAhoCorasick {
-                    children: BTreeMap::new(),
-                    link: -1,
-                    good_link: -1,
-                    pat_num: -1,
-                    pch: -1,
-                    parent: -1
-                };
-                1
-            ],
-            sz: 1,
+impl<'a, T: Eq + Hash> BoyerMoore<'a, T> {
+    pub fn new(pattern: &'a [T]) -> Self {
+        let m = pattern.len();
+        let last = pattern
+            .iter()
+            .enumerate()
+            .map(|(idx, symbol)| (symbol, idx))
+            .collect();
+        // border[i] is the start of the widest border of pattern[i..]
+        let mut good_suffix = vec![0; m + 1];
+        let mut border = vec![0; m + 1];
+        let (mut i, mut j) = (m, m + 1);
+        border[i] = j;
+        while i > 0 {
+            while j <= m && pattern[i - 1] != pattern[j - 1] {
+                if good_suffix[j] == 0 {
+                    good_suffix[j] = j - i;
+                }
+                j = border[j];
+            }
+            i -= 1;
+            j -= 1;
+            border[i] = j;
         }
-    }
-    fn insert(&mut self, s: &str, num: i32) {
-        let mut v = 0;
-        for ch in s.as_bytes() {
-            let idx = *ch as i32;
-            #[allow(clippy::map_entry)]
-            if !self.arr[v].children.contains_key(&idx) {
-                self.arr.push(VertexAhoCorasick {
-                    children: BTreeMap::new(),
-                    link: 0,
-                    good_link: -1,
-                    pat_num: -1,
-                    pch: idx,
-                    parent: v as i32,
-                });
-                self.arr[v].children.insert(idx, self.sz);
-                self.sz += 1;
+        j = border[0];
+        for (i, shift) in good_suffix.iter_mut().enumerate() {
+            if *shift == 0 {
+                *shift = j;
             }
-            v = *self.arr[v].children.get(&idx).unwrap() as usize;
+            if i == j {
+                j = border[j];
+            }
+        }
+        BoyerMoore {
+            pattern,
+            last,
+            good_suffix,
         }
-        self.arr[v].pat_num = num;
     }
 }
 
-#[allow(unused)]
-pub fn aho_corasick(dict: &[&str], t: &str) -> BTreeMap<i32, Vec<usize>> {
-    let mut res: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
-    let mut trie = TrieAhoCorasick::new();
-    for (idx, s) in dict.iter().enumerate() {
-        trie.insert(s, idx as i32);
+impl<T: Eq + Hash> Searcher<T> for BoyerMoore<'_, T> {
+    fn pattern(&self) -> &[T] {
+        self.pattern
     }
-    let mut q = VecDeque::new();
-    q.push_back(0);
-    while !q.is_empty() {
-        let curr = q.pop_front().unwrap();
 
-        for value in trie.arr[curr as usize].children.values() {
-            q.push_back(*value);
-        }
-        if curr == 0 {
-            continue;
-        }
-        let parent = trie.arr[curr as usize].parent;
-        let mut next_link = trie.arr[parent as usize].link;
-        let pch = trie.arr[curr as usize].pch;
-        while next_link >= 0 && !trie.arr[next_link as usize].children.contains_key(&pch) {
-            next_link = trie.arr[next_link as usize].link;
+    fn search(&self, text: &[T], start: usize, f: &mut dyn FnMut(usize) -> bool) {
+        let (p, m) = (self.pattern, self.pattern.len());
+        if m == 0 {
+            return;
         }
-        if next_link >= 0 {
-            let link = *trie.arr[next_link as usize].children.get(&pch).unwrap();
-            let good_link = if trie.arr[link as usize].pat_num != -1 {
-                link
+        let mut pos = start;
+        while pos + m <= text.len() {
+            let mut j = m;
+            while j > 0 && p[j - 1] == text[pos + j - 1] {
+                j -= 1;
+            }
+            if j == 0 {
+                if !f(pos) {
+                    return;
+                }
+                pos += self.good_suffix[0];
             } else {
-                trie.arr[link as usize].good_link
-            };
-            let r = &mut trie.arr[curr as usize];
-            r.link = link;
-            r.good_link = good_link;
+                let bad_character = match self.last.get(&text[pos + j - 1]) {
+                    Some(last) => (j - 1).saturating_sub(*last),
+                    None => j,
+                };
+                pos += self.good_suffix[j].max(bad_character);
+            }
         }
     }
-    let mut v = 0i32;
-    for (i, ch) in t.as_bytes().iter().enumerate() {
-        let idx = *ch as i32;
-        while v >= 0 && !trie.arr[v as usize].children.contains_key(&idx) {
-            v = trie.arr[v as usize].link;
+}
+
+/*
+* Boyer–Moore–Horspool: the shift depends only on the text symbol under the last position of the pattern
+*/
+pub struct Horspool<'a, T> {
+    pattern: &'a [T],
+    shift: HashMap<&'a T, usize>,
+}
+
+impl<'a, T: Eq + Hash> Horspool<'a, T> {
+    pub fn new(pattern: &'a [T]) -> Self {
+        let m = pattern.len();
+        let shift = pattern
+            .iter()
+            .enumerate()
+            .take(m.saturating_sub(1))
+            .map(|(idx, symbol)| (symbol, m - 1 - idx))
+            .collect();
+        Horspool { pattern, shift }
+    }
+}
+
+impl<T: Eq + Hash> Searcher<T> for Horspool<'_, T> {
+    fn pattern(&self) -> &[T] {
+        self.pattern
+    }
+
+    fn search(&self, text: &[T], start: usize, f: &mut dyn FnMut(usize) -> bool) {
+        let (p, m) = (self.pattern, self.pattern.len());
+        if m == 0 {
+            return;
         }
-        if v == -1 {
-            v = 0;
-        } else {
-            v = *trie.arr[v as usize].children.get(&idx).unwrap();
+        let mut pos = start;
+        while pos + m <= text.len() {
+            let last = &text[pos + m - 1];
+            if *last == p[m - 1] && text[pos..pos + m - 1] == p[..m - 1] && !f(pos) {
+                return;
+            }
+            pos += self.shift.get(last).copied().unwrap_or(m);
         }
-        if trie.arr[v as usize].pat_num != -1 {
-            let value = res.entry(trie.arr[v as usize].pat_num).or_default();
-            (*value).push(i + 1 - dict[trie.arr[v as usize].pat_num as usize].len());
+    }
+}
+
+/*
+* Crochemore–Perrin two-way algorithm: O(n + m) time and O(1) extra memory. The pattern is split at
+* a critical position, the right part is matched left to right and the left part right to left
+*/
+pub struct TwoWay<'a, T> {
+    pattern: &'a [T],
+    // the pattern is split into pattern[..critical] and pattern[critical..]
+    critical: usize,
+    period: usize,
+    // the pattern is periodic with the period, so the matched prefix can be remembered
+    periodic: bool,
+}
+
+impl<'a, T: Ord> TwoWay<'a, T> {
+    pub fn new(pattern: &'a [T]) -> Self {
+        let (first, first_period) = Self::maximal_suffix(pattern, false);
+        let (second, second_period) = Self::maximal_suffix(pattern, true);
+        let (critical, period) = if first >= second {
+            (first, first_period)
+        } else {
+            (second, second_period)
+        };
+        let m = pattern.len();
+        let periodic =
+            critical + period <= m && pattern[..critical] == pattern[period..period + critical];
+        let period = if periodic {
+            period
+        } else {
+            critical.max(m - critical) + 1
+        };
+        TwoWay {
+            pattern,
+            critical,
+            period,
+            periodic,
         }
-        let mut good_link = trie.arr[v as usize].good_link;
-        while good_link > 0 {
-            let value = res.entry(trie.arr[good_link as usize].pat_num).or_default();
-            (*value).push(i + 1 - dict[trie.arr[good_link as usize].pat_num as usize].len());
-            good_link = trie.arr[good_link as usize].good_link;
+    }
+
+    /*
+     * start and period of the maximal suffix for the order or the reversed order
+     */
+    fn maximal_suffix(pattern: &[T], reversed: bool) -> (usize, usize) {
+        let (mut suffix, mut j, mut k, mut period) = (0, 1, 0, 1);
+        while j + k < pattern.len() {
+            let (a, b) = (&pattern[j + k], &pattern[suffix + k]);
+            let order = if reversed { b.cmp(a) } else { a.cmp(b) };
+            match order {
+                std::cmp::Ordering::Less => {
+                    j += k + 1;
+                    k = 0;
+                    period = j - suffix;
+                }
+                std::cmp::Ordering::Equal => {
+                    if k + 1 == period {
+                        j += period;
+                        k = 0;
+                    } else {
+                        k += 1;
+                    }
+                }
+                std::cmp::Ordering::Greater => {
+                    suffix = j;
+                    j += 1;
+                    k = 0;
+                    period = 1;
+                }
+            }
         }
+        (suffix, period)
     }
-    res
 }
 
-#[test]
-fn aho_corasick_test() {
-    let mut dict = ["aba", "abb", "bbca"];
-    let text = "abaabbbbca";
-    let mut res = aho_corasick(&dict, text);
-
-    let mut map = BTreeMap::new();
-    map.insert(0, vec![0]);
-    map.insert(1, vec![3]);
-    map.insert(2, vec![6]);
-    assert_eq!(map, res);
+impl<T: Ord> Searcher<T> for TwoWay<'_, T> {
+    fn pattern(&self) -> &[T] {
+        self.pattern
+    }
 
-    let text = "abaabbbbcaaba";
-    res = aho_corasick(&dict, text);
+    fn search(&self, text: &[T], start: usize, f: &mut dyn FnMut(usize) -> bool) {
+        let (p, m, critical) = (self.pattern, self.pattern.len(), self.critical);
+        if m == 0 {
+            return;
+        }
+        let mut pos = start;
+        // length of the prefix of the pattern known to match at pos
+        let mut memory = 0;
+        while pos + m <= text.len() {
+            let mut i = critical.max(memory);
+            while i < m && p[i] == text[pos + i] {
+                i += 1;
+            }
+            if i < m {
+                pos += i + 1 - critical;
+                memory = 0;
+                continue;
+            }
+            let mut i = critical;
+            while i > memory && p[i - 1] == text[pos + i - 1] {
+                i -= 1;
+            }
+            if i <= memory && !f(pos) {
+                return;
+            }
+            pos += self.period;
+            if self.periodic {
+                memory = m - self.period;
+            }
+        }
+    }
+}
 
-    map = BTreeMap::new();
-    map.insert(0, vec![0, 10]);
-    map.insert(1, vec![3]);
-    map.insert(2, vec![6]);
-    assert_eq!(map, res);
+#[allow(unused)]
+pub fn boyer_moore(t: &str, p: &str) -> Vec<usize> {
+    BoyerMoore::new(p.as_bytes()).find_all(t.as_bytes())
+}
 
-    let text = "abaabbbbcaba";
-    res = aho_corasick(&dict, text);
+#[allow(unused)]
+pub fn horspool(t: &str, p: &str) -> Vec<usize> {
+    Horspool::new(p.as_bytes()).find_all(t.as_bytes())
+}
 
-    map = BTreeMap::new();
-    map.insert(0, vec![0, 9]);
-    map.insert(1, vec![3]);
-    map.insert(2, vec![6]);
-    assert_eq!(map, res);
+#[allow(unused)]
+pub fn two_way(t: &str, p: &str) -> Vec<usize> {
+    TwoWay::new(p.as_bytes()).find_all(t.as_bytes())
+}
 
-    dict = ["abba", "bb", "cc"];
-    let text = "abba";
-    res = aho_corasick(&dict, text);
+#[cfg(test)]
+#[test]
+fn test_searchers() {
+    for search in [boyer_moore, horspool, two_way] {
+        assert_eq!(search("ababcxabdabcxabcxabcde", "abcxabcde"), vec![13]);
+        assert_eq!(search("a", "ab"), vec![]);
+        assert_eq!(search("", ""), vec![]);
+        assert_eq!(search("aaaaa", "aa"), vec![0, 1, 2, 3]);
+        assert_eq!(search("abcdabcd", "abc"), vec![0, 4]);
+    }
+    let text: Vec<u32> = vec![7, 1000, 5, 7, 1000, 5, 9];
+    let pattern = [7, 1000, 5];
+    assert_eq!(BoyerMoore::new(&pattern).find_at(&text, 1), Some(3));
+    assert_eq!(TwoWay::new(&pattern).find_at(&text, 4), None);
 
-    map = BTreeMap::new();
-    map.insert(0, vec![0]);
-    map.insert(1, vec![1]);
-    assert_eq!(map, res);
+    let mut rng = crate::random::xorshift_32(49);
+    for _ in 0..500 {
+        let alphabet = 1 + rng.next().unwrap() % 3;
+        let text: Vec<u32> = (0..rng.next().unwrap() % 60)
+            .map(|_| rng.next().unwrap() % alphabet)
+            .collect();
+        let pattern: Vec<u32> = (0..1 + rng.next().unwrap() % 8)
+            .map(|_| rng.next().unwrap() % alphabet)
+            .collect();
+        let expected: Vec<usize> = (0..(text.len() + 1).saturating_sub(pattern.len()))
+            .filter(|pos| text[*pos..].starts_with(&pattern))
+            .collect();
+        let searchers: [&dyn Searcher<u32>; 4] = [
+            &Kmp::new(&pattern),
+            &BoyerMoore::new(&pattern),
+            &Horspool::new(&pattern),
+            &TwoWay::new(&pattern),
+        ];
+        for searcher in searchers {
+            assert_eq!(searcher.pattern(), &pattern[..]);
+            assert_eq!(searcher.find_all(&text), expected);
+            assert_eq!(searcher.find(&text), expected.first().copied());
+            let start = (rng.next().unwrap() as usize) % (text.len() + 1);
+            assert_eq!(
+                searcher.find_at(&text, start),
+                expected.iter().copied().find(|pos| *pos >= start)
+            );
+        }
+    }
+}
 
-    dict = ["aba", "baba", "cc"];
-    let text = "ababababa";
-    res = aho_corasick(&dict, text);
+/*
+* Bitap (shift-and) matcher: the state is a bit vector of the pattern prefixes matching at the current
+* text position. Supports wildcard pattern symbols, and with the Wu–Manber extension up to k mismatches
+* or k edits in O(n * (k + 1) * m / 64)
+*/
+pub struct Bitap<T> {
+    len: usize,
+    // masks[symbol] has the bit i set if pattern[i] is the symbol or the wildcard
+    masks: HashMap<T, Vec<u64>>,
+    wildcards: Vec<u64>,
+}
 
-    map = BTreeMap::new();
-    map.insert(0, vec![0, 2, 4, 6]);
-    map.insert(1, vec![1, 3, 5]);
-    assert_eq!(map, res);
+impl<T: Eq + Hash + Clone> Bitap<T> {
+    pub fn new(pattern: &[T]) -> Self {
+        Self::build(pattern, None)
+    }
 
-    let dict = ["aba", "baba", "cc"];
-    let text = "ababababa";
-    let res = aho_corasick(&dict, text);
+    /*
+     * pattern where the wildcard symbol matches any symbol of the text
+     */
+    pub fn with_wildcard(pattern: &[T], wildcard: &T) -> Self {
+        Self::build(pattern, Some(wildcard))
+    }
 
-    let mut map = BTreeMap::new();
+    fn build(pattern: &[T], wildcard: Option<&T>) -> Self {
+        let words = pattern.len().div_ceil(64);
+        let mut wildcards = vec![0; words];
+        let mut masks: HashMap<T, Vec<u64>> = HashMap::new();
+        for (idx, symbol) in pattern.iter().enumerate() {
+            let mask = if Some(symbol) == wildcard {
+                &mut wildcards
+            } else {
+                masks
+                    .entry(symbol.clone())
+                    .or_insert_with(|| vec![0; words])
+            };
+            mask[idx / 64] |= 1 << (idx % 64);
+        }
+        for mask in masks.values_mut() {
+            for (word, wildcard) in mask.iter_mut().zip(wildcards.iter()) {
+                *word |= wildcard;
+            }
+        }
+        Bitap {
+            len: pattern.len(),
+            masks,
+            wildcards,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /*
+     * starts of the exact occurrences (wildcards aside)
+     */
+    pub fn find_all(&self, text: &[T]) -> Vec<usize> {
+        self.find_with_mismatches(text, 0)
+            .into_iter()
+            .map(|(start, _)| start)
+            .collect()
+    }
+
+    /*
+     * starts of the occurrences with at most k mismatched symbols and the number of mismatches
+     */
+    pub fn find_with_mismatches(&self, text: &[T], k: usize) -> Vec<(usize, usize)> {
+        let mut res = vec![];
+        self.run(text, k, false, |end, errors| {
+            res.push((end - self.len, errors))
+        });
+        res
+    }
+
+    /*
+     * ends (exclusive) of the substrings within the edit distance k from the pattern, with the least distance.
+     * The starts may vary, the ends are reported once in increasing order
+     */
+    pub fn find_with_edits(&self, text: &[T], k: usize) -> Vec<(usize, usize)> {
+        let mut res = vec![];
+        self.run(text, k, true, |end, errors| res.push((end, errors)));
+        res
+    }
+
+    fn run<F: FnMut(usize, usize)>(&self, text: &[T], k: usize, edits: bool, mut report: F) {
+        if self.len == 0 {
+            return;
+        }
+        let words = self.wildcards.len();
+        let (last_word, last_bit) = ((self.len - 1) / 64, 1 << ((self.len - 1) % 64));
+        // state[d] is the set of the pattern prefixes ending here with at most d errors.
+        // With edits the first d symbols of the pattern can be deleted before the text starts
+        let mut state: Vec<Vec<u64>> = (0..=k)
+            .map(|d| {
+                let mut bits = vec![0; words];
+                if edits {
+                    for idx in 0..d.min(self.len) {
+                        bits[idx / 64] |= 1 << (idx % 64);
+                    }
+                }
+                bits
+            })
+            .collect();
+        let mut next = state.clone();
+        if let Some(d) = (0..=k).find(|d| state[*d][last_word] & last_bit != 0) {
+            report(0, d);
+        }
+        for (pos, symbol) in text.iter().enumerate() {
+            let mask = self.masks.get(symbol).unwrap_or(&self.wildcards);
+            for d in 0..=k {
+                let (done, rest) = next.split_at_mut(d);
+                let current = &mut rest[0];
+                // the carries of the shifts by one position (with 1 shifted in) of the vectors used below
+                let (mut carry, mut carry_previous, mut carry_next) = (1, 1, 1);
+                for word in 0..words {
+                    let shifted = bitap_shift(state[d][word], &mut carry);
+                    let mut bits = shifted & mask[word];
+                    if d > 0 {
+                        let previous = state[d - 1][word];
+                        // substitution
+                        bits |= bitap_shift(previous, &mut carry_previous);
+                        if edits {
+                            // insertion of the text symbol and deletion of the pattern symbol
+                            bits |= previous | bitap_shift(done[d - 1][word], &mut carry_next);
+                        }
+                    }
+                    current[word] = bits;
+                }
+            }
+            std::mem::swap(&mut state, &mut next);
+            if let Some(d) = (0..=k).find(|d| state[*d][last_word] & last_bit != 0) {
+                report(pos + 1, d);
+            }
+        }
+    }
+}
+
+// shifts the word left by one bit, shifting in the carry and leaving the bit shifted out in it
+fn bitap_shift(word: u64, carry: &mut u64) -> u64 {
+    let res = (word << 1) | *carry;
+    *carry = word >> 63;
+    res
+}
+
+/*
+* occurrences of the pattern where '?' matches any character
+*/
+pub fn wildcard_search(t: &str, p: &str) -> Vec<TextPosition> {
+    let (text, positions) = split_chars(t);
+    let pattern: Vec<char> = p.chars().collect();
+    Bitap::with_wildcard(&pattern, &'?')
+        .find_all(&text)
+        .into_iter()
+        .map(|idx| positions[idx])
+        .collect()
+}
+
+/*
+* Landau–Vishkin k-differences in O((n + m) log (n + m) + nk): for every diagonal the furthest pattern
+* position reachable with e edits is extended by the longest common extension of the pattern and the text,
+* answered in O(1) by the suffix array of pattern + separator + text.
+* Returns the ends (exclusive) of the substrings within the edit distance k from the pattern, with the least distance
+*/
+pub fn landau_vishkin_of<T: Ord>(t: &[T], p: &[T], k: usize) -> Vec<(usize, usize)> {
+    let (n, m) = (t.len(), p.len());
+    if m == 0 {
+        return vec![];
+    }
+    let mut values: Vec<&T> = p.iter().chain(t.iter()).collect();
+    values.sort();
+    values.dedup();
+    let symbol_rank = |value: &T| values.binary_search(&value).unwrap() + 1;
+    // 0 is the separator
+    let mut s: Vec<usize> = p.iter().map(symbol_rank).collect();
+    s.push(0);
+    s.extend(t.iter().map(symbol_rank));
+    let sa = suffix_array_int(&s, values.len());
+    let lcp = lcp_array(&s, &sa);
+    let mut rank = vec![0; s.len()];
+    for (idx, suffix) in sa.iter().enumerate() {
+        rank[*suffix] = idx;
+    }
+    let rmq = Rmq::from_slice(&lcp);
+    // longest common prefix of p[row..] and t[pos..]
+    let extension = |row: usize, pos: usize| {
+        if row == m || pos == n {
+            return 0;
+        }
+        let (first, second) = (rank[row], rank[m + 1 + pos]);
+        *rmq.min(first.min(second), first.max(second)).unwrap()
+    };
+
+    // diagonal d (the text position minus the pattern position) is stored at d + k + 1,
+    // with one more diagonal at each side to avoid bound checks
+    let diagonals = n + k + 3;
+    let offset = k as isize + 1;
+    let mut best = vec![usize::MAX; n + 1];
+    let mut previous: Vec<Option<usize>> = vec![None; diagonals];
+    for e in 0..=k {
+        let mut current: Vec<Option<usize>> = vec![None; diagonals];
+        for d in -(e.min(m) as isize)..=n as isize {
+            let idx = (d + offset) as usize;
+            // the diagonals before the text start need -d deletions to enter the text
+            let base = if d >= 0 {
+                (e == 0).then_some(0)
+            } else {
+                (e as isize == -d).then_some(e)
+            };
+            // substitution, insertion of the text symbol and deletion of the pattern symbol after e - 1 edits
+            let start = [
+                base,
+                previous[idx].map(|row| row + 1),
+                previous[idx - 1],
+                previous[idx + 1].map(|row| row + 1),
+            ]
+            .into_iter()
+            .flatten()
+            .max();
+            let Some(row) = start else { continue };
+            let row = row.min(m).min((n as isize - d) as usize);
+            let pos = (row as isize + d) as usize;
+            let row = row + extension(row, pos);
+            current[idx] = Some(row);
+            if row == m {
+                let end = (m as isize + d) as usize;
+                best[end] = best[end].min(e);
+            }
+        }
+        previous = current;
+    }
+    best.into_iter()
+        .enumerate()
+        .filter(|(_, e)| *e != usize::MAX)
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn test_approximate_search() {
+    let positions = wildcard_search("мама мыла раму", "?а");
+    let chars: Vec<usize> = positions.iter().map(|position| position.char).collect();
+    assert_eq!(chars, [0, 2, 7, 10]);
+    assert_eq!(positions[1].byte, 4);
+    let bitap = Bitap::new(b"abcd".as_slice());
+    assert_eq!(
+        bitap.find_with_mismatches(b"abed xbcd abcd", 1),
+        [(0, 1), (5, 1), (10, 0)]
+    );
+    assert_eq!(bitap.find_with_edits(b"acd", 1), [(3, 1)]);
+    assert_eq!(landau_vishkin_of(b"acd", b"abcd", 1), [(3, 1)]);
+    assert!(Bitap::new(&[] as &[u8]).find_all(b"abc").is_empty());
+
+    let mut rng = crate::random::xorshift_32(50);
+    for _ in 0..300 {
+        let word = |rng: &mut crate::random::XorShift32, max_len: u32, min_len: u32| -> Vec<u8> {
+            let len = min_len + rng.next().unwrap() % max_len;
+            (0..len)
+                .map(|_| b"abc?"[(rng.next().unwrap() % 4) as usize])
+                .collect()
+        };
+        // long patterns span several words of the bit vectors
+        let (text, pattern) = if rng.next().unwrap().is_multiple_of(4) {
+            let text: Vec<u8> = word(&mut rng, 300, 0)
+                .into_iter()
+                .filter(|ch| *ch != b'?')
+                .collect();
+            let start = rng.next().unwrap() as usize % (text.len() + 1);
+            let len = (70 + rng.next().unwrap() % 80) as usize;
+            let pattern = text[start..(start + len).min(text.len())].to_vec();
+            (
+                text,
+                if pattern.is_empty() {
+                    vec![b'a']
+                } else {
+                    pattern
+                },
+            )
+        } else {
+            let text: Vec<u8> = word(&mut rng, 40, 0)
+                .into_iter()
+                .filter(|ch| *ch != b'?')
+                .collect();
+            (text, word(&mut rng, 7, 1))
+        };
+        let k = (rng.next().unwrap() % 4) as usize;
+        let plain: Vec<u8> = pattern.iter().copied().filter(|ch| *ch != b'?').collect();
+        let wildcard = Bitap::with_wildcard(&pattern, &b'?');
+        let windows = |len: usize| 0..(text.len() + 1).saturating_sub(len);
+        let expected: Vec<usize> = windows(pattern.len())
+            .filter(|start| {
+                (0..pattern.len()).all(|i| pattern[i] == b'?' || pattern[i] == text[start + i])
+            })
+            .collect();
+        assert_eq!(wildcard.find_all(&text), expected);
+        let bitap = Bitap::new(&plain);
+        if plain.is_empty() {
+            assert!(bitap.find_with_mismatches(&text, k).is_empty());
+            continue;
+        }
+        let expected: Vec<(usize, usize)> = windows(plain.len())
+            .map(|start| {
+                (
+                    start,
+                    (0..plain.len())
+                        .filter(|i| plain[*i] != text[start + i])
+                        .count(),
+                )
+            })
+            .filter(|(_, mismatches)| *mismatches <= k)
+            .collect();
+        assert_eq!(bitap.find_with_mismatches(&text, k), expected);
+        let edits = bitap.find_with_edits(&text, k);
+        assert_eq!(landau_vishkin_of(&text, &plain, k), edits);
+        if plain.len() > 20 {
+            continue;
+        }
+        let expected: Vec<(usize, usize)> = (0..=text.len())
+            .map(|end| {
+                let distance = (0..=end)
+                    .map(|start| {
+                        levenshtein_distance_of(&plain, &text[start..end], 1, 1, 1) as usize
+                    })
+                    .min()
+                    .unwrap();
+                (end, distance)
+            })
+            .filter(|(_, distance)| *distance <= k)
+            .collect();
+        assert_eq!(edits, expected);
+    }
+}
+
+#[allow(unused)]
+pub fn compress(s: &str) -> (&str, usize) {
+    let k = s.len() - prefix_function(s)[s.len() - 1];
+    match s.len() % k {
+        0 => (&s[..k], s.len() / k),
+        _ => (s, 1),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    assert_eq!(compress("abcabcabc"), ("abc", 3));
+    assert_eq!(compress("abcd"), ("abcd", 1));
+}
+
+#[allow(unused)]
+pub fn levenshtein_distance(
+    first: &str,
+    second: &str,
+    delete_cost: u32,
+    insert_cost: u32,
+    replace_cost: u32,
+) -> u32 {
+    levenshtein_distance_of(
+        first.as_bytes(),
+        second.as_bytes(),
+        delete_cost,
+        insert_cost,
+        replace_cost,
+    )
+}
+
+/*
+* Levenshtein distance between sequences of any comparable elements: the cheapest cost of turning second into first,
+* where delete_cost removes a symbol of second, insert_cost adds a symbol of first and replace_cost replaces one
+*/
+pub fn levenshtein_distance_of<T: Eq>(
+    first: &[T],
+    second: &[T],
+    delete_cost: u32,
+    insert_cost: u32,
+    replace_cost: u32,
+) -> u32 {
+    let costs = (delete_cost, insert_cost, replace_cost);
+    edit_distance_last_row(second.iter(), first.iter(), costs)[first.len()]
+}
+
+#[cfg(test)]
+#[test]
+fn test_levenshtein_distance() {
+    assert_eq!(
+        levenshtein_distance("POLYNOMIAL", "EXPONENTIAL", 1, 1, 1),
+        6
+    );
+    assert_eq!(levenshtein_distance("abcdasdasd", "cddabcd", 1, 1, 1), 6);
+    assert_eq!(levenshtein_distance("", "", 1, 1, 1), 0);
+    assert_eq!(levenshtein_distance("aaa", "aaa", 1, 1, 1), 0);
+    assert_eq!(levenshtein_distance("", "aaa", 1, 1, 1), 3);
+    // second is turned into first
+    assert_eq!(levenshtein_distance("", "aaa", 1, 2, 1), 3);
+    assert_eq!(levenshtein_distance("abc", "", 2, 1, 1), 3);
+    assert_eq!(levenshtein_distance("abc", "abd", 1, 1, 5), 2);
+    assert_eq!(levenshtein_distance("abc", "abd", 5, 5, 1), 1);
+    assert_eq!(levenshtein_distance("ab", "a", 9, 1, 9), 1);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditOp {
+    Keep,
+    Delete,
+    Insert,
+    Replace,
+}
+
+/*
+* One step of an edit script turning second into first (the direction of levenshtein_distance).
+* first and second are the positions in the sequences: Keep and Replace use first[first] and second[second],
+* Delete removes second[second], Insert adds first[first] before second[second]
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub op: EditOp,
+    pub first: usize,
+    pub second: usize,
+}
+
+/*
+* returns the cheapest cost of turning second into first, equal to levenshtein_distance_of(first, second, ..),
+* and the edit script. O(nm) time and memory
+*/
+pub fn edit_script<T: Eq>(
+    first: &[T],
+    second: &[T],
+    delete_cost: u32,
+    insert_cost: u32,
+    replace_cost: u32,
+) -> (u32, Vec<Edit>) {
+    let costs = (delete_cost, insert_cost, replace_cost);
+    let (cost, script) = forward_edit_script(second, first, costs);
+    (cost, swap_sides(script))
+}
+
+// the edit script turning first into second, the positions in the edits are swapped
+fn forward_edit_script<T: Eq>(
+    first: &[T],
+    second: &[T],
+    costs: (u32, u32, u32),
+) -> (u32, Vec<Edit>) {
+    let (delete_cost, insert_cost, replace_cost) = costs;
+    let (n, m) = (first.len(), second.len());
+    let mut dist = vec![vec![0u32; m + 1]; n + 1];
+    for i in 0..=n {
+        for j in 0..=m {
+            dist[i][j] = match (i, j) {
+                (0, 0) => 0,
+                (i, 0) => dist[i - 1][0] + delete_cost,
+                (0, j) => dist[0][j - 1] + insert_cost,
+                (i, j) => {
+                    let diagonal = if first[i - 1] == second[j - 1] {
+                        0
+                    } else {
+                        replace_cost
+                    };
+                    std::cmp::min(
+                        dist[i - 1][j - 1] + diagonal,
+                        std::cmp::min(dist[i - 1][j] + delete_cost, dist[i][j - 1] + insert_cost),
+                    )
+                }
+            };
+        }
+    }
+    let mut script = vec![];
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 {
+            let equal = first[i - 1] == second[j - 1];
+            let diagonal = if equal { 0 } else { replace_cost };
+            if dist[i][j] == dist[i - 1][j - 1] + diagonal {
+                let op = if equal { EditOp::Keep } else { EditOp::Replace };
+                script.push(Edit {
+                    op,
+                    first: i - 1,
+                    second: j - 1,
+                });
+                i -= 1;
+                j -= 1;
+                continue;
+            }
+        }
+        if i > 0 && dist[i][j] == dist[i - 1][j] + delete_cost {
+            script.push(Edit {
+                op: EditOp::Delete,
+                first: i - 1,
+                second: j,
+            });
+            i -= 1;
+        } else {
+            script.push(Edit {
+                op: EditOp::Insert,
+                first: i,
+                second: j - 1,
+            });
+            j -= 1;
+        }
+    }
+    script.reverse();
+    (dist[n][m], script)
+}
+
+fn swap_sides(script: Vec<Edit>) -> Vec<Edit> {
+    script
+        .into_iter()
+        .map(|edit| Edit {
+            op: edit.op,
+            first: edit.second,
+            second: edit.first,
+        })
+        .collect()
+}
+
+/*
+* Hirschberg's algorithm: the same result as edit_script (turning second into first) in O(nm) time
+* but O(n + m) memory. One sequence is split in halves, the split point of the other one is found from the last rows
+* of the forward and the backward distance tables, and both halves are solved recursively
+*/
+pub fn hirschberg<T: Eq>(
+    first: &[T],
+    second: &[T],
+    delete_cost: u32,
+    insert_cost: u32,
+    replace_cost: u32,
+) -> (u32, Vec<Edit>) {
+    let costs = (delete_cost, insert_cost, replace_cost);
+    let mut script = vec![];
+    hirschberg_rec(second, first, 0, 0, costs, &mut script);
+    let script = swap_sides(script);
+    let cost = script
+        .iter()
+        .map(|edit| match edit.op {
+            EditOp::Keep => 0,
+            EditOp::Delete => delete_cost,
+            EditOp::Insert => insert_cost,
+            EditOp::Replace => replace_cost,
+        })
+        .sum();
+    (cost, script)
+}
+
+// appends the edit script turning first into second, the positions in the edits are swapped
+fn hirschberg_rec<T: Eq>(
+    first: &[T],
+    second: &[T],
+    first_offset: usize,
+    second_offset: usize,
+    costs: (u32, u32, u32),
+    script: &mut Vec<Edit>,
+) {
+    if first.len() <= 1 || second.len() <= 1 {
+        let (_, part) = forward_edit_script(first, second, costs);
+        script.extend(part.into_iter().map(|edit| Edit {
+            op: edit.op,
+            first: edit.first + first_offset,
+            second: edit.second + second_offset,
+        }));
+        return;
+    }
+    let mid = first.len() / 2;
+    let forward = edit_distance_last_row(first[..mid].iter(), second.iter(), costs);
+    let backward = edit_distance_last_row(first[mid..].iter().rev(), second.iter().rev(), costs);
+    let split = (0..=second.len())
+        .min_by_key(|j| forward[*j] + backward[second.len() - j])
+        .unwrap();
+    hirschberg_rec(
+        &first[..mid],
+        &second[..split],
+        first_offset,
+        second_offset,
+        costs,
+        script,
+    );
+    hirschberg_rec(
+        &first[mid..],
+        &second[split..],
+        first_offset + mid,
+        second_offset + split,
+        costs,
+        script,
+    );
+}
+
+// the costs of turning the whole first into every prefix of second
+fn edit_distance_last_row<'a, T: Eq + 'a, F, S>(
+    first: F,
+    second: S,
+    costs: (u32, u32, u32),
+) -> Vec<u32>
+where
+    F: Iterator<Item = &'a T>,
+    S: Iterator<Item = &'a T> + Clone,
+{
+    let (delete_cost, insert_cost, replace_cost) = costs;
+    let mut row: Vec<u32> = std::iter::once(0)
+        .chain(second.clone().scan(0, |acc, _| {
+            *acc += insert_cost;
+            Some(*acc)
+        }))
+        .collect();
+    for a in first {
+        let mut next = vec![row[0] + delete_cost];
+        for (j, b) in second.clone().enumerate() {
+            let diagonal = row[j] + if a == b { 0 } else { replace_cost };
+            next.push(std::cmp::min(
+                diagonal,
+                std::cmp::min(row[j + 1] + delete_cost, next[j] + insert_cost),
+            ));
+        }
+        row = next;
+    }
+    row
+}
+
+/*
+* Levenshtein distance with unit costs limited by k: only the diagonal band of width 2k + 1 is computed
+* and the computation stops as soon as the whole band exceeds k. Returns None if the distance is greater than k.
+* O(min(n, m) * k)
+*/
+pub fn levenshtein_distance_bounded<T: Eq>(first: &[T], second: &[T], k: usize) -> Option<usize> {
+    // the distance is symmetric with unit costs, so the rows go over the shorter sequence
+    let (first, second) = if first.len() <= second.len() {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    let (n, m) = (first.len(), second.len());
+    if m - n > k {
+        return None;
+    }
+    let inf = k + 1;
+    let width = 2 * k + 1;
+    // band[t] is the cell (i, i + t - k) of the current row
+    let mut band: Vec<usize> = (0..width)
+        .map(|t| match t.checked_sub(k) {
+            Some(j) if j <= m => j,
+            _ => inf,
+        })
+        .collect();
+    let mut next = vec![inf; width];
+    for i in 1..=n {
+        for t in 0..width {
+            next[t] = match (i + t).checked_sub(k) {
+                Some(j) if j > m => inf,
+                Some(0) => i,
+                Some(j) => {
+                    let diagonal = band[t] + usize::from(first[i - 1] != second[j - 1]);
+                    let up = band.get(t + 1).map_or(inf, |value| value + 1);
+                    let left = t.checked_sub(1).map_or(inf, |left| next[left] + 1);
+                    diagonal.min(up).min(left).min(inf)
+                }
+                None => inf,
+            };
+        }
+        if next.iter().all(|value| *value > k) {
+            return None;
+        }
+        std::mem::swap(&mut band, &mut next);
+    }
+    Some(band[m - n + k]).filter(|value| *value <= k)
+}
+
+/*
+* Optimal string alignment distance: Levenshtein distance with transpositions of adjacent elements,
+* but no substring can be edited more than once (so "ca" -> "abc" costs 3)
+*/
+pub fn osa_distance<T: Eq>(first: &[T], second: &[T]) -> usize {
+    let (n, m) = (first.len(), second.len());
+    let mut dist = vec![vec![0; m + 1]; n + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    dist[0] = (0..=m).collect();
+    for i in 1..=n {
+        for j in 1..=m {
+            let cost = usize::from(first[i - 1] != second[j - 1]);
+            dist[i][j] = std::cmp::min(
+                dist[i - 1][j - 1] + cost,
+                std::cmp::min(dist[i - 1][j], dist[i][j - 1]) + 1,
+            );
+            if i > 1 && j > 1 && first[i - 1] == second[j - 2] && first[i - 2] == second[j - 1] {
+                dist[i][j] = std::cmp::min(dist[i][j], dist[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    dist[n][m]
+}
+
+/*
+* Damerau–Levenshtein distance: insertions, deletions, replacements and transpositions of adjacent elements,
+* transposed elements may be edited again (so "ca" -> "abc" costs 2). Lowrance–Wagner algorithm, O(nm)
+*/
+pub fn damerau_levenshtein_distance<T: Eq + Hash>(first: &[T], second: &[T]) -> usize {
+    let (n, m) = (first.len(), second.len());
+    let max = n + m;
+    // the table is shifted by one to keep the row and the column with the maximum distance
+    let mut dist = vec![vec![0; m + 2]; n + 2];
+    dist[0][0] = max;
+    for i in 0..=n {
+        dist[i + 1][0] = max;
+        dist[i + 1][1] = i;
+    }
+    for j in 0..=m {
+        dist[0][j + 1] = max;
+        dist[1][j + 1] = j;
+    }
+    // the last row where every element occurs in first
+    let mut last_row: HashMap<&T, usize> = HashMap::new();
+    for i in 1..=n {
+        // the last column in this row where the elements were equal
+        let mut last_column = 0;
+        for j in 1..=m {
+            let k = last_row.get(&second[j - 1]).copied().unwrap_or(0);
+            let l = last_column;
+            let cost = if first[i - 1] == second[j - 1] {
+                last_column = j;
+                0
+            } else {
+                1
+            };
+            dist[i + 1][j + 1] = std::cmp::min(
+                std::cmp::min(dist[i][j] + cost, dist[i + 1][j] + 1),
+                std::cmp::min(
+                    dist[i][j + 1] + 1,
+                    dist[k][l] + (i - k - 1) + 1 + (j - l - 1),
+                ),
+            );
+        }
+        last_row.insert(&first[i - 1], i);
+    }
+    dist[n + 1][m + 1]
+}
+
+#[cfg(test)]
+fn apply_edit_script<T: Clone>(first: &[T], second: &[T], script: &[Edit]) -> Vec<T> {
+    let mut res = vec![];
+    let mut pos = 0;
+    for edit in script {
+        assert_eq!(edit.second, pos);
+        match edit.op {
+            EditOp::Keep | EditOp::Replace => {
+                res.push(first[edit.first].clone());
+                pos += 1;
+            }
+            EditOp::Delete => pos += 1,
+            EditOp::Insert => res.push(first[edit.first].clone()),
+        }
+    }
+    assert_eq!(pos, second.len());
+    res
+}
+
+#[cfg(test)]
+#[test]
+fn edit_distance_family_test() {
+    let (cost, script) = edit_script(b"sitting", b"kitten", 1, 1, 1);
+    assert_eq!(cost, 3);
+    let ops: Vec<EditOp> = script.iter().map(|edit| edit.op).collect();
+    use EditOp::*;
+    assert_eq!(ops, vec![Replace, Keep, Keep, Keep, Replace, Keep, Insert]);
+    assert_eq!(
+        apply_edit_script(b"sitting", b"kitten", &script),
+        b"sitting"
+    );
+    assert_eq!(edit_script(b"", b"ab", 1, 1, 1).0, 2);
+    // second is turned into first like in levenshtein_distance: three insertions
+    assert_eq!(edit_script(b"abc", b"", 2, 1, 1).0, 3);
+    assert_eq!(hirschberg(b"abc", b"", 2, 1, 1).0, 3);
+    assert_eq!(edit_script(b"", b"abc", 2, 1, 1).0, 6);
+    assert_eq!(levenshtein_distance("abc", "", 2, 1, 1), 3);
+
+    assert_eq!(osa_distance(b"ca", b"abc"), 3);
+    assert_eq!(damerau_levenshtein_distance(b"ca", b"abc"), 2);
+    assert_eq!(osa_distance(b"abcdef", b"abdcef"), 1);
+    assert_eq!(damerau_levenshtein_distance(b"abcdef", b"abdcef"), 1);
+    assert_eq!(damerau_levenshtein_distance(b"", b"abc"), 3);
+    assert_eq!(
+        levenshtein_distance_bounded(b"kitten", b"sitting", 3),
+        Some(3)
+    );
+    assert_eq!(levenshtein_distance_bounded(b"kitten", b"sitting", 2), None);
+    assert_eq!(levenshtein_distance_bounded(b"abc", b"abcdef", 2), None);
+    // only the band is computed, so long sequences with a small bound are cheap
+    let long: Vec<u32> = (0..200_000).collect();
+    let mut edited = long.clone();
+    edited[100_000] = 0;
+    edited.remove(150_000);
+    assert_eq!(levenshtein_distance_bounded(&long, &edited, 2), Some(2));
+    assert_eq!(levenshtein_distance_bounded(&edited, &long, 1), None);
+
+    let mut rng = crate::random::xorshift_32(43);
+    for _ in 0..200 {
+        let mut word = |len: u32| -> Vec<u8> {
+            let len = rng.next().unwrap() % len;
+            (0..len)
+                .map(|_| b'a' + (rng.next().unwrap() % 3) as u8)
+                .collect()
+        };
+        let (first, second) = (word(12), word(12));
+        let (cost, script) = edit_script(&first, &second, 2, 3, 4);
+        // the edit script adds up to the distance with the same arguments
+        assert_eq!(cost, levenshtein_distance_of(&first, &second, 2, 3, 4));
+        assert_eq!(apply_edit_script(&first, &second, &script), first);
+        let (linear_cost, linear_script) = hirschberg(&first, &second, 2, 3, 4);
+        assert_eq!(linear_cost, cost);
+        assert_eq!(apply_edit_script(&first, &second, &linear_script), first);
+
+        let distance = levenshtein_distance_of(&first, &second, 1, 1, 1) as usize;
+        for k in 0..6 {
+            let expected = Some(distance).filter(|distance| *distance <= k);
+            assert_eq!(levenshtein_distance_bounded(&first, &second, k), expected);
+        }
+        let osa = osa_distance(&first, &second);
+        let damerau = damerau_levenshtein_distance(&first, &second);
+        assert!(damerau <= osa && osa <= distance);
+        assert_eq!(osa_distance(&second, &first), osa);
+        assert_eq!(damerau_levenshtein_distance(&second, &first), damerau);
+    }
+}
+
+/*
+* Score of aligning two symbols, positive for similar symbols
+*/
+pub trait SubstitutionMatrix<T> {
+    fn score(&self, first: &T, second: &T) -> i32;
+}
+
+impl<T, F: Fn(&T, &T) -> i32> SubstitutionMatrix<T> for F {
+    fn score(&self, first: &T, second: &T) -> i32 {
+        self(first, second)
+    }
+}
+
+/*
+* The same score for all equal symbols and for all different ones (usual for DNA)
+*/
+#[derive(Clone, Copy, Debug)]
+pub struct MatchMismatch {
+    pub match_score: i32,
+    pub mismatch_score: i32,
+}
+
+impl<T: Eq> SubstitutionMatrix<T> for MatchMismatch {
+    fn score(&self, first: &T, second: &T) -> i32 {
+        if first == second {
+            self.match_score
+        } else {
+            self.mismatch_score
+        }
+    }
+}
+
+/*
+* Substitution matrix given by a table over an alphabet (like BLOSUM62 for proteins).
+* The symbols outside the alphabet get the unknown score
+*/
+pub struct ScoreMatrix<T> {
+    index: HashMap<T, usize>,
+    scores: Vec<Vec<i32>>,
+    unknown: i32,
+}
+
+impl<T: Eq + Hash + Clone> ScoreMatrix<T> {
+    /*
+     * returns None if the table is not square of the size of the alphabet
+     */
+    pub fn new(alphabet: &[T], scores: &[Vec<i32>], unknown: i32) -> Option<Self> {
+        if scores.len() != alphabet.len() || scores.iter().any(|row| row.len() != alphabet.len()) {
+            return None;
+        }
+        Some(ScoreMatrix {
+            index: alphabet
+                .iter()
+                .enumerate()
+                .map(|(idx, symbol)| (symbol.clone(), idx))
+                .collect(),
+            scores: scores.to_vec(),
+            unknown,
+        })
+    }
+}
+
+impl<T: Eq + Hash> SubstitutionMatrix<T> for ScoreMatrix<T> {
+    fn score(&self, first: &T, second: &T) -> i32 {
+        match (self.index.get(first), self.index.get(second)) {
+            (Some(first), Some(second)) => self.scores[*first][*second],
+            _ => self.unknown,
+        }
+    }
+}
+
+/*
+* Affine gap penalty: a gap of length l costs open + extend * (l - 1). open == extend gives the linear penalty
+*/
+#[derive(Clone, Copy, Debug)]
+pub struct GapPenalty {
+    pub open: i32,
+    pub extend: i32,
+}
+
+impl GapPenalty {
+    pub fn linear(penalty: i32) -> Self {
+        GapPenalty {
+            open: penalty,
+            extend: penalty,
+        }
+    }
+
+    pub fn affine(open: i32, extend: i32) -> Self {
+        GapPenalty { open, extend }
+    }
+}
+
+/*
+* Result of an alignment: the aligned symbols of both sequences column by column (None is a gap)
+* and the aligned parts of the sequences (the whole sequences for the global alignment)
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alignment<'a, T> {
+    pub score: i32,
+    pub first: Vec<Option<&'a T>>,
+    pub second: Vec<Option<&'a T>>,
+    pub first_range: std::ops::Range<usize>,
+    pub second_range: std::ops::Range<usize>,
+}
+
+const ALIGNMENT_NONE: i64 = i64::MIN / 4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum AlignmentState {
+    // the symbols are aligned with each other
+    Pair,
+    // the symbol of first is aligned with a gap
+    GapInSecond,
+    // the symbol of second is aligned with a gap
+    GapInFirst,
+}
+
+/*
+* Needleman–Wunsch global alignment with affine gaps (Gotoh's three matrices). O(nm) time and memory
+*/
+pub fn needleman_wunsch<'a, T, S: SubstitutionMatrix<T>>(
+    first: &'a [T],
+    second: &'a [T],
+    matrix: &S,
+    gap: GapPenalty,
+) -> Alignment<'a, T> {
+    gotoh(first, second, matrix, gap, false)
+}
+
+/*
+* Smith–Waterman local alignment with affine gaps: the pair of substrings with the best global alignment score.
+* The empty alignment with the score 0 is returned if no pair of symbols scores positively
+*/
+pub fn smith_waterman<'a, T, S: SubstitutionMatrix<T>>(
+    first: &'a [T],
+    second: &'a [T],
+    matrix: &S,
+    gap: GapPenalty,
+) -> Alignment<'a, T> {
+    gotoh(first, second, matrix, gap, true)
+}
+
+fn gotoh<'a, T, S: SubstitutionMatrix<T>>(
+    first: &'a [T],
+    second: &'a [T],
+    matrix: &S,
+    gap: GapPenalty,
+    local: bool,
+) -> Alignment<'a, T> {
+    let (n, m) = (first.len(), second.len());
+    let (open, extend) = (gap.open as i64, gap.extend as i64);
+    let score = |i: usize, j: usize| matrix.score(&first[i - 1], &second[j - 1]) as i64;
+    // the best scores of the alignments of the prefixes ending in every state
+    let mut pair = vec![vec![ALIGNMENT_NONE; m + 1]; n + 1];
+    let mut gap_second = vec![vec![ALIGNMENT_NONE; m + 1]; n + 1];
+    let mut gap_first = vec![vec![ALIGNMENT_NONE; m + 1]; n + 1];
+    pair[0][0] = 0;
+    if !local {
+        for (i, row) in gap_second.iter_mut().enumerate().skip(1) {
+            row[0] = -open - extend * (i as i64 - 1);
+        }
+        for (j, cell) in gap_first[0].iter_mut().enumerate().skip(1) {
+            *cell = -open - extend * (j as i64 - 1);
+        }
+    }
+    let mut best = (0, 0, 0);
+    for i in 1..=n {
+        for j in 1..=m {
+            let previous = pair[i - 1][j - 1]
+                .max(gap_second[i - 1][j - 1])
+                .max(gap_first[i - 1][j - 1]);
+            pair[i][j] = score(i, j) + if local { previous.max(0) } else { previous };
+            gap_second[i][j] = (pair[i - 1][j] - open)
+                .max(gap_second[i - 1][j] - extend)
+                .max(gap_first[i - 1][j] - open);
+            gap_first[i][j] = (pair[i][j - 1] - open)
+                .max(gap_first[i][j - 1] - extend)
+                .max(gap_second[i][j - 1] - open);
+            if local && pair[i][j] > best.0 {
+                best = (pair[i][j], i, j);
+            }
+        }
+    }
+    let (score_value, mut i, mut j, mut state) = if local {
+        (best.0, best.1, best.2, AlignmentState::Pair)
+    } else {
+        let candidates = [
+            (pair[n][m], AlignmentState::Pair),
+            (gap_second[n][m], AlignmentState::GapInSecond),
+            (gap_first[n][m], AlignmentState::GapInFirst),
+        ];
+        let (value, state) = candidates
+            .into_iter()
+            .max_by_key(|(value, _)| *value)
+            .unwrap();
+        (value, n, m, state)
+    };
+    let (end_first, end_second) = (i, j);
+    let mut first_aligned = vec![];
+    let mut second_aligned = vec![];
+    // the local alignment is traced back until the cell where it starts from scratch
+    while (local && score_value > 0) || (!local && (i > 0 || j > 0)) {
+        match state {
+            AlignmentState::Pair => {
+                let previous = pair[i][j] - score(i, j);
+                first_aligned.push(Some(&first[i - 1]));
+                second_aligned.push(Some(&second[j - 1]));
+                i -= 1;
+                j -= 1;
+                if local && previous == 0 {
+                    break;
+                }
+                state = if previous == pair[i][j] {
+                    AlignmentState::Pair
+                } else if previous == gap_second[i][j] {
+                    AlignmentState::GapInSecond
+                } else {
+                    AlignmentState::GapInFirst
+                };
+            }
+            AlignmentState::GapInSecond => {
+                let current = gap_second[i][j];
+                first_aligned.push(Some(&first[i - 1]));
+                second_aligned.push(None);
+                i -= 1;
+                state = if current == gap_second[i][j] - extend {
+                    AlignmentState::GapInSecond
+                } else if current == pair[i][j] - open {
+                    AlignmentState::Pair
+                } else {
+                    AlignmentState::GapInFirst
+                };
+            }
+            AlignmentState::GapInFirst => {
+                let current = gap_first[i][j];
+                first_aligned.push(None);
+                second_aligned.push(Some(&second[j - 1]));
+                j -= 1;
+                state = if current == gap_first[i][j] - extend {
+                    AlignmentState::GapInFirst
+                } else if current == pair[i][j] - open {
+                    AlignmentState::Pair
+                } else {
+                    AlignmentState::GapInSecond
+                };
+            }
+        }
+    }
+    first_aligned.reverse();
+    second_aligned.reverse();
+    Alignment {
+        score: score_value as i32,
+        first: first_aligned,
+        second: second_aligned,
+        first_range: i..end_first,
+        second_range: j..end_second,
+    }
+}
+
+#[cfg(test)]
+fn alignment_to_string(aligned: &[Option<&u8>]) -> String {
+    aligned
+        .iter()
+        .map(|symbol| symbol.map_or('-', |symbol| *symbol as char))
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn sequence_alignment_test() {
+    let dna = MatchMismatch {
+        match_score: 1,
+        mismatch_score: -1,
+    };
+    let global = needleman_wunsch(b"GATTACA", b"GCATGCU", &dna, GapPenalty::linear(1));
+    assert_eq!(global.score, 0);
+    assert_eq!(global.first.len(), global.second.len());
+    assert_eq!(global.first_range, 0..7);
+
+    let local = smith_waterman(
+        b"TGTTACGG",
+        b"GGTTGACTA",
+        &MatchMismatch {
+            match_score: 3,
+            mismatch_score: -3,
+        },
+        GapPenalty::linear(2),
+    );
+    assert_eq!(local.score, 13);
+    assert_eq!(alignment_to_string(&local.first), "GTT-AC");
+    assert_eq!(alignment_to_string(&local.second), "GTTGAC");
+    assert_eq!((local.first_range, local.second_range), (1..6, 1..7));
+    let empty = smith_waterman(b"AAA", b"TTT", &dna, GapPenalty::linear(1));
+    assert_eq!((empty.score, empty.first.len()), (0, 0));
+
+    // one long gap is cheaper than several short ones with the affine penalty
+    let matrix = |a: &u8, b: &u8| if a == b { 2 } else { -3 };
+    let affine = needleman_wunsch(b"AAAGGGTTT", b"AAATTT", &matrix, GapPenalty::affine(5, 1));
+    assert_eq!(affine.score, 12 - 7);
+    assert_eq!(alignment_to_string(&affine.second), "AAA---TTT");
+
+    let proteins = ScoreMatrix::new(
+        &['A', 'R', 'N'],
+        &[vec![4, -1, -2], vec![-1, 5, 0], vec![-2, 0, 6]],
+        -4,
+    )
+    .unwrap();
+    let first = ['A', 'R', 'N', 'N'];
+    let second = ['A', 'N', 'N'];
+    let alignment = needleman_wunsch(&first, &second, &proteins, GapPenalty::affine(10, 1));
+    // A-A, R-gap, N-N, N-N
+    assert_eq!(alignment.score, 6);
+    assert_eq!(proteins.score(&'X', &'A'), -4);
+    assert!(ScoreMatrix::new(&['A'], &[vec![1, 2]], 0).is_none());
+
+    // the scores agree with the aligned columns and with the edit distance
+    let mut rng = crate::random::xorshift_32(47);
+    for _ in 0..200 {
+        let mut word = |len: u32| -> Vec<u8> {
+            let len = rng.next().unwrap() % len;
+            (0..len)
+                .map(|_| b"ACGT"[(rng.next().unwrap() % 4) as usize])
+                .collect()
+        };
+        let (first, second) = (word(15), word(15));
+        let unit = MatchMismatch {
+            match_score: 0,
+            mismatch_score: -1,
+        };
+        let global = needleman_wunsch(&first, &second, &unit, GapPenalty::linear(1));
+        assert_eq!(
+            -global.score as u32,
+            levenshtein_distance_of(&first, &second, 1, 1, 1)
+        );
+        for (gap, local) in [
+            (GapPenalty::affine(4, 1), false),
+            (GapPenalty::affine(3, 2), true),
+        ] {
+            let alignment = if local {
+                smith_waterman(&first, &second, &matrix, gap)
+            } else {
+                needleman_wunsch(&first, &second, &matrix, gap)
+            };
+            let mut score = 0;
+            let mut gap_state = (false, false);
+            for (a, b) in alignment.first.iter().zip(alignment.second.iter()) {
+                match (a, b) {
+                    (Some(a), Some(b)) => {
+                        score += matrix(a, b);
+                        gap_state = (false, false);
+                    }
+                    (Some(_), None) => {
+                        score -= if gap_state.0 { gap.extend } else { gap.open };
+                        gap_state = (true, false);
+                    }
+                    (None, Some(_)) => {
+                        score -= if gap_state.1 { gap.extend } else { gap.open };
+                        gap_state = (false, true);
+                    }
+                    (None, None) => unreachable!(),
+                }
+            }
+            assert_eq!(score, alignment.score);
+            let aligned_first: Vec<u8> = alignment
+                .first
+                .iter()
+                .flatten()
+                .map(|symbol| **symbol)
+                .collect();
+            assert_eq!(aligned_first, first[alignment.first_range.clone()]);
+            let aligned_second: Vec<u8> = alignment
+                .second
+                .iter()
+                .flatten()
+                .map(|symbol| **symbol)
+                .collect();
+            assert_eq!(aligned_second, second[alignment.second_range.clone()]);
+        }
+    }
+}
+
+/*
+* Manacher's algorithm in O(n). Returns (odd, even): s[i+1-odd[i]..i+odd[i]] is the maximal palindrome
+* centered at i and s[i-even[i]..i+even[i]] is the maximal one centered between i-1 and i
+*/
+pub fn manacher_of<T: Eq>(s: &[T]) -> (Vec<usize>, Vec<usize>) {
+    let n = s.len();
+    let mut odd = vec![0; n];
+    let (mut l, mut r) = (0, 0);
+    for i in 0..n {
+        let mut k = if i < r {
+            odd[l + r - 1 - i].min(r - i)
+        } else {
+            1
+        };
+        while i + k < n && i >= k && s[i + k] == s[i - k] {
+            k += 1;
+        }
+        odd[i] = k;
+        if i + k > r {
+            (l, r) = (i + 1 - k, i + k);
+        }
+    }
+    let mut even = vec![0; n];
+    let (mut l, mut r) = (0, 0);
+    for i in 0..n {
+        let mut k = if i < r { even[l + r - i].min(r - i) } else { 0 };
+        while i + k < n && i > k && s[i + k] == s[i - k - 1] {
+            k += 1;
+        }
+        even[i] = k;
+        if i + k > r {
+            (l, r) = (i - k, i + k);
+        }
+    }
+    (odd, even)
+}
+
+/*
+* the maximal palindrome for every center, in order of centers (empty ones are skipped)
+*/
+pub fn maximal_palindromes_of<T: Eq>(s: &[T]) -> Vec<std::ops::Range<usize>> {
+    let (odd, even) = manacher_of(s);
+    let mut res = vec![];
+    for i in 0..s.len() {
+        if even[i] > 0 {
+            res.push(i - even[i]..i + even[i]);
+        }
+        res.push(i + 1 - odd[i]..i + odd[i]);
+    }
+    res
+}
+
+/*
+* the leftmost longest palindromic substring
+*/
+pub fn longest_palindrome_of<T: Eq>(s: &[T]) -> std::ops::Range<usize> {
+    maximal_palindromes_of(s)
+        .into_iter()
+        .fold(0..0, |best, range| {
+            if range.len() > best.len() {
+                range
+            } else {
+                best
+            }
+        })
+}
+
+/*
+* longest palindromic substring of the text by characters
+*/
+pub fn longest_palindromic_substring(s: &str) -> &str {
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    let symbols: Vec<char> = chars.iter().map(|(_, ch)| *ch).collect();
+    let range = longest_palindrome_of(&symbols);
+    let byte = |idx: usize| chars.get(idx).map_or(s.len(), |(byte, _)| *byte);
+    &s[byte(range.start)..byte(range.end)]
+}
+
+/*
+* number of palindromic substrings counted by positions
+*/
+pub fn palindromic_substrings_count_of<T: Eq>(s: &[T]) -> usize {
+    let (odd, even) = manacher_of(s);
+    odd.iter().sum::<usize>() + even.iter().sum::<usize>()
+}
+
+#[cfg(test)]
+#[test]
+fn manacher_test() {
+    assert_eq!(
+        manacher_of(b"abaab"),
+        (vec![1, 2, 1, 1, 1], vec![0, 0, 0, 2, 0])
+    );
+    assert_eq!(longest_palindromic_substring("babad"), "bab");
+    assert_eq!(longest_palindromic_substring("cbbd"), "bb");
+    assert_eq!(longest_palindromic_substring("шалаш и"), "шалаш");
+    assert_eq!(longest_palindromic_substring(""), "");
+    assert_eq!(palindromic_substrings_count_of(b"aaa"), 6);
+
+    let mut rng = crate::random::xorshift_32(47);
+    for _ in 0..100 {
+        let len = (rng.next().unwrap() % 30) as usize;
+        let s: Vec<u8> = (0..len)
+            .map(|_| b"ab"[(rng.next().unwrap() % 2) as usize])
+            .collect();
+        let is_palindrome = |l: usize, r: usize| s[l..r].iter().eq(s[l..r].iter().rev());
+        let mut count = 0;
+        let mut longest = 0;
+        for l in 0..len {
+            for r in l + 1..=len {
+                if is_palindrome(l, r) {
+                    count += 1;
+                    longest = longest.max(r - l);
+                }
+            }
+        }
+        assert_eq!(palindromic_substrings_count_of(&s), count);
+        assert_eq!(longest_palindrome_of(&s).len(), longest);
+        for range in maximal_palindromes_of(&s) {
+            assert!(is_palindrome(range.start, range.end));
+            assert!(range.start == 0 || range.end == len || s[range.start - 1] != s[range.end]);
+        }
+    }
+}
+
+/*
+* Palindromic tree (eertree): a node for each distinct palindromic substring, built online symbol by symbol.
+* Node 0 is the imaginary root of length -1, node 1 is the empty palindrome
+*/
+pub struct PalindromicTree<T> {
+    s: Vec<T>,
+    len: Vec<isize>,
+    link: Vec<usize>,
+    next: Vec<HashMap<T, usize>>,
+    // end of the first occurrence
+    end: Vec<usize>,
+    // how many times the node is the longest palindromic suffix of a prefix
+    suffix_count: Vec<usize>,
+    // difference between the lengths of the node and its suffix link, and the link to the first node with another difference
+    diff: Vec<usize>,
+    series_link: Vec<usize>,
+    last: usize,
+}
+
+impl<T: Eq + Hash + Clone> PalindromicTree<T> {
+    pub fn new() -> Self {
+        PalindromicTree {
+            s: vec![],
+            len: vec![-1, 0],
+            link: vec![0, 0],
+            next: vec![HashMap::new(), HashMap::new()],
+            end: vec![0, 0],
+            suffix_count: vec![0, 0],
+            diff: vec![0, 0],
+            series_link: vec![0, 0],
+            last: 1,
+        }
+    }
+
+    pub fn build(s: &[T]) -> Self {
+        let mut tree = Self::new();
+        for symbol in s {
+            tree.push(symbol.clone());
+        }
+        tree
+    }
+
+    fn suffix_extendable(&self, mut node: usize) -> usize {
+        let i = self.s.len() - 1;
+        loop {
+            let len = self.len[node];
+            if len == -1 || i as isize - len > 0 && self.s[i - 1 - len as usize] == self.s[i] {
+                return node;
+            }
+            node = self.link[node];
+        }
+    }
+
+    /*
+     * appends the symbol, returns true if a new distinct palindrome appeared
+     */
+    pub fn push(&mut self, symbol: T) -> bool {
+        self.s.push(symbol.clone());
+        let parent = self.suffix_extendable(self.last);
+        if let Some(&node) = self.next[parent].get(&symbol) {
+            self.last = node;
+            self.suffix_count[node] += 1;
+            return false;
+        }
+        let len = self.len[parent] + 2;
+        let link = if len == 1 {
+            1
+        } else {
+            let link_parent = self.suffix_extendable(self.link[parent]);
+            self.next[link_parent][&symbol]
+        };
+        let node = self.len.len();
+        let diff = (len - self.len[link]) as usize;
+        self.len.push(len);
+        self.link.push(link);
+        self.next.push(HashMap::new());
+        self.end.push(self.s.len());
+        self.suffix_count.push(1);
+        self.diff.push(diff);
+        self.series_link.push(if diff == self.diff[link] {
+            self.series_link[link]
+        } else {
+            link
+        });
+        self.next[parent].insert(symbol, node);
+        self.last = node;
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.s.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.s.is_empty()
+    }
+
+    /*
+     * number of distinct non-empty palindromic substrings
+     */
+    pub fn distinct_count(&self) -> usize {
+        self.len.len() - 2
+    }
+
+    /*
+     * the longest palindromic suffix of the current string
+     */
+    pub fn longest_suffix_palindrome(&self) -> std::ops::Range<usize> {
+        self.s.len() - self.len[self.last] as usize..self.s.len()
+    }
+
+    /*
+     * first occurrence of every distinct palindrome with the number of its occurrences, in order of appearance
+     */
+    pub fn palindromes(&self) -> Vec<(std::ops::Range<usize>, usize)> {
+        let mut count = self.suffix_count.clone();
+        for node in (2..self.len.len()).rev() {
+            count[self.link[node]] += count[node];
+        }
+        (2..self.len.len())
+            .map(|node| {
+                (
+                    self.end[node] - self.len[node] as usize..self.end[node],
+                    count[node],
+                )
+            })
+            .collect()
+    }
+}
+
+impl<T: Eq + Hash + Clone> Default for PalindromicTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/*
+* splitting of the sequence into the minimum number of palindromes in O(n log n) using the series links of eertree
+*/
+pub fn palindromic_factorization_of<T: Eq + Hash + Clone>(s: &[T]) -> Vec<std::ops::Range<usize>> {
+    let mut tree = PalindromicTree::new();
+    // dp[i] is (the number of palindromes, start of the last one) for the prefix of length i
+    let mut dp = vec![(0, 0); s.len() + 1];
+    let mut series = vec![(usize::MAX, 0); 2];
+    for (idx, symbol) in s.iter().enumerate() {
+        let i = idx + 1;
+        tree.push(symbol.clone());
+        series.resize(tree.len.len(), (usize::MAX, 0));
+        dp[i] = (usize::MAX, 0);
+        let mut node = tree.last;
+        while tree.len[node] > 0 {
+            let start = i - (tree.len[tree.series_link[node]] as usize + tree.diff[node]);
+            series[node] = (dp[start].0, start);
+            if tree.diff[node] == tree.diff[tree.link[node]] {
+                series[node] = series[node].min(series[tree.link[node]]);
+            }
+            if series[node].0 + 1 < dp[i].0 {
+                dp[i] = (series[node].0 + 1, series[node].1);
+            }
+            node = tree.series_link[node];
+        }
+    }
+    let mut res = vec![];
+    let mut end = s.len();
+    while end > 0 {
+        res.push(dp[end].1..end);
+        end = dp[end].1;
+    }
+    res.reverse();
+    res
+}
+
+#[cfg(test)]
+#[test]
+fn palindromic_tree_test() {
+    let tree = PalindromicTree::build(b"eertree");
+    assert_eq!(tree.distinct_count(), 7);
+    assert_eq!(tree.longest_suffix_palindrome(), 0..7);
+    let palindromes: Vec<&[u8]> = tree
+        .palindromes()
+        .into_iter()
+        .map(|(range, _)| &b"eertree"[range])
+        .collect();
+    assert_eq!(
+        palindromes,
+        [&b"e"[..], b"ee", b"r", b"t", b"rtr", b"ertre", b"eertree"]
+    );
+    assert_eq!(palindromic_factorization_of(b"abacdc"), [0..3, 3..6]);
+    assert!(palindromic_factorization_of::<u8>(&[]).is_empty());
+
+    let mut rng = crate::random::xorshift_32(147);
+    for _ in 0..100 {
+        let len = (rng.next().unwrap() % 25) as usize;
+        let s: Vec<u8> = (0..len)
+            .map(|_| b"abc"[(rng.next().unwrap() % 3) as usize])
+            .collect();
+        let is_palindrome =
+            |range: std::ops::Range<usize>| s[range.clone()].iter().eq(s[range].iter().rev());
+        let mut expected: HashMap<&[u8], usize> = HashMap::new();
+        for l in 0..len {
+            for r in l + 1..=len {
+                if is_palindrome(l..r) {
+                    *expected.entry(&s[l..r]).or_default() += 1;
+                }
+            }
+        }
+        let tree = PalindromicTree::build(&s);
+        assert_eq!(tree.distinct_count(), expected.len());
+        for (range, count) in tree.palindromes() {
+            assert_eq!(expected[&s[range]], count);
+        }
+        let mut min_split = vec![usize::MAX; len + 1];
+        min_split[0] = 0;
+        for r in 1..=len {
+            for l in 0..r {
+                if is_palindrome(l..r) {
+                    min_split[r] = min_split[r].min(min_split[l] + 1);
+                }
+            }
+        }
+        let factorization = palindromic_factorization_of(&s);
+        assert_eq!(factorization.len(), min_split[len]);
+        assert!(factorization
+            .iter()
+            .all(|range| is_palindrome(range.clone())));
+        assert!(factorization
+            .windows(2)
+            .all(|pair| pair[0].end == pair[1].start));
+    }
+}
+
+struct TrieNode<V> {
+    children: BTreeMap<u8, usize>,
+    value: Option<V>,
+    // the number of keys in the subtree
+    count: usize,
+}
+
+/*
+* Prefix tree over byte strings mapping keys to values.
+* Every node stores the number of keys below it, so counting the keys with a prefix takes O(|prefix|).
+* The nodes live in an array, the nodes freed by remove are reused.
+*/
+pub struct Trie<V> {
+    nodes: Vec<TrieNode<V>>,
+    free: Vec<usize>,
+}
+
+impl<V> Trie<V> {
+    pub fn new() -> Self {
+        Trie {
+            nodes: vec![TrieNode {
+                children: BTreeMap::new(),
+                value: None,
+                count: 0,
+            }],
+            free: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes[0].count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /*
+     * inserts the key, returns the previous value if the key was already present
+     */
+    pub fn insert<K: AsRef<[u8]>>(&mut self, key: K, value: V) -> Option<V> {
+        let key = key.as_ref();
+        let mut path = vec![0];
+        let mut node = 0;
+        for ch in key {
+            node = match self.nodes[node].children.get(ch) {
+                Some(next) => *next,
+                None => {
+                    let next = self.make_node();
+                    self.nodes[node].children.insert(*ch, next);
+                    next
+                }
+            };
+            path.push(node);
+        }
+        let old = self.nodes[node].value.replace(value);
+        if old.is_none() {
+            for node in path {
+                self.nodes[node].count += 1;
+            }
+        }
+        old
+    }
+
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<V> {
+        let key = key.as_ref();
+        let mut path = vec![0];
+        for ch in key {
+            path.push(*self.nodes[*path.last().unwrap()].children.get(ch)?);
+        }
+        let old = self.nodes[*path.last().unwrap()].value.take()?;
+        for node in path.iter() {
+            self.nodes[*node].count -= 1;
+        }
+        // the nodes without keys are detached, their children were detached before
+        for idx in (1..path.len()).rev() {
+            if self.nodes[path[idx]].count > 0 {
+                break;
+            }
+            self.nodes[path[idx - 1]].children.remove(&key[idx - 1]);
+            self.nodes[path[idx]].children.clear();
+            self.free.push(path[idx]);
+        }
+        Some(old)
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&V> {
+        self.nodes[self.find(key.as_ref())?].value.as_ref()
+    }
+
+    pub fn get_mut<K: AsRef<[u8]>>(&mut self, key: K) -> Option<&mut V> {
+        let node = self.find(key.as_ref())?;
+        self.nodes[node].value.as_mut()
+    }
+
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /*
+     * returns the number of keys starting with the prefix
+     */
+    pub fn count_prefix<K: AsRef<[u8]>>(&self, prefix: K) -> usize {
+        self.find(prefix.as_ref())
+            .map_or(0, |node| self.nodes[node].count)
+    }
+
+    /*
+     * returns the length and the value of the longest key that is a prefix of the specified one
+     */
+    pub fn longest_prefix<K: AsRef<[u8]>>(&self, key: K) -> Option<(usize, &V)> {
+        let mut node = 0;
+        let mut res = self.nodes[0].value.as_ref().map(|value| (0, value));
+        for (idx, ch) in key.as_ref().iter().enumerate() {
+            match self.nodes[node].children.get(ch) {
+                Some(next) => node = *next,
+                None => break,
+            }
+            if let Some(value) = self.nodes[node].value.as_ref() {
+                res = Some((idx + 1, value));
+            }
+        }
+        res
+    }
+
+    /*
+     * iterates over the keys starting with the prefix in the lexicographic order
+     */
+    pub fn iter_prefix<K: AsRef<[u8]>>(&self, prefix: K) -> impl Iterator<Item = (Vec<u8>, &V)> {
+        let prefix = prefix.as_ref();
+        let mut stack = vec![];
+        if let Some(node) = self.find(prefix) {
+            stack.push((node, prefix.to_vec()));
+        }
+        std::iter::from_fn(move || {
+            while let Some((node, key)) = stack.pop() {
+                for (ch, child) in self.nodes[node].children.iter().rev() {
+                    let mut next = key.clone();
+                    next.push(*ch);
+                    stack.push((*child, next));
+                }
+                if let Some(value) = self.nodes[node].value.as_ref() {
+                    return Some((key, value));
+                }
+            }
+            None
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Vec<u8>, &V)> {
+        self.iter_prefix(b"")
+    }
+
+    fn find(&self, key: &[u8]) -> Option<usize> {
+        let mut node = 0;
+        for ch in key {
+            node = *self.nodes[node].children.get(ch)?;
+        }
+        Some(node)
+    }
+
+    fn make_node(&mut self) -> usize {
+        match self.free.pop() {
+            Some(node) => node,
+            None => {
+                self.nodes.push(TrieNode {
+                    children: BTreeMap::new(),
+                    value: None,
+                    count: 0,
+                });
+                self.nodes.len() - 1
+            }
+        }
+    }
+}
+
+impl<V> Default for Trie<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn trie_test() {
+    let mut trie = Trie::new();
+    for (idx, word) in ["car", "cart", "carbon", "cat", "dog", ""]
+        .iter()
+        .enumerate()
+    {
+        assert_eq!(trie.insert(word, idx), None);
+    }
+    assert_eq!(trie.insert("cat", 10), Some(3));
+    assert_eq!(trie.len(), 6);
+    assert_eq!(trie.count_prefix("car"), 3);
+    assert_eq!(trie.count_prefix("ca"), 4);
+    assert_eq!(trie.count_prefix("x"), 0);
+    assert_eq!(trie.get("cat"), Some(&10));
+    assert_eq!(trie.get("ca"), None);
+    let words: Vec<Vec<u8>> = trie.iter_prefix("car").map(|(key, _)| key).collect();
+    assert_eq!(
+        words,
+        [b"car".to_vec(), b"carbon".to_vec(), b"cart".to_vec()]
+    );
+    assert_eq!(trie.longest_prefix("cartoon"), Some((4, &1)));
+    assert_eq!(trie.longest_prefix("cab"), Some((0, &5)));
+
+    assert_eq!(trie.remove("carbon"), Some(2));
+    assert_eq!(trie.remove("carbon"), None);
+    assert_eq!(trie.remove("ca"), None);
+    assert_eq!(trie.count_prefix("carb"), 0);
+    *trie.get_mut("dog").unwrap() += 100;
+    assert_eq!(trie.get("dog"), Some(&104));
+    trie.insert("carbide", 7);
+    assert_eq!(trie.iter().count(), 6);
+    assert!(trie.contains_key("carbide"));
+}
+
+struct RadixNode<V> {
+    // the bytes of the edge from the parent
+    label: Vec<u8>,
+    value: Option<V>,
+    count: usize,
+    children: BTreeMap<u8, RadixNode<V>>,
+}
+
+impl<V> RadixNode<V> {
+    fn new(label: Vec<u8>) -> Self {
+        RadixNode {
+            label,
+            value: None,
+            count: 0,
+            children: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        let old = match key.first() {
+            None => self.value.replace(value),
+            Some(ch) => match self.children.get(ch) {
+                None => {
+                    let mut leaf = RadixNode::new(key.to_vec());
+                    leaf.value = Some(value);
+                    leaf.count = 1;
+                    self.children.insert(*ch, leaf);
+                    None
+                }
+                Some(child) => {
+                    let common = child
+                        .label
+                        .iter()
+                        .zip(key.iter())
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    if common < child.label.len() {
+                        // the edge is split at the end of the common part
+                        let mut child = self.children.remove(ch).unwrap();
+                        let mut middle = RadixNode::new(child.label[..common].to_vec());
+                        middle.count = child.count;
+                        child.label.drain(..common);
+                        middle.children.insert(child.label[0], child);
+                        self.children.insert(*ch, middle);
+                    }
+                    self.children
+                        .get_mut(ch)
+                        .unwrap()
+                        .insert(&key[common..], value)
+                }
+            },
+        };
+        if old.is_none() {
+            self.count += 1;
+        }
+        old
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let old = match key.first() {
+            None => self.value.take(),
+            Some(ch) => {
+                let child = self.children.get_mut(ch)?;
+                if !key.starts_with(&child.label) {
+                    return None;
+                }
+                let old = child.remove(&key[child.label.len()..]);
+                if child.value.is_none() && child.children.len() <= 1 {
+                    // the node without a value and with one child is merged with the child
+                    let mut child = self.children.remove(ch).unwrap();
+                    if let Some((_, mut grandchild)) = child.children.pop_first() {
+                        child.label.append(&mut grandchild.label);
+                        grandchild.label = child.label;
+                        self.children.insert(*ch, grandchild);
+                    }
+                }
+                old
+            }
+        };
+        if old.is_some() {
+            self.count -= 1;
+        }
+        old
+    }
+}
+
+/*
+* Radix tree (compressed prefix tree): the chains of nodes with one child are merged into one edge
+* labeled with a byte string, so the tree has at most 2 * len() nodes.
+* The interface is the same as the one of Trie.
+*/
+pub struct RadixTree<V> {
+    root: RadixNode<V>,
+}
+
+impl<V> RadixTree<V> {
+    pub fn new() -> Self {
+        RadixTree {
+            root: RadixNode::new(vec![]),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /*
+     * inserts the key, returns the previous value if the key was already present
+     */
+    pub fn insert<K: AsRef<[u8]>>(&mut self, key: K, value: V) -> Option<V> {
+        self.root.insert(key.as_ref(), value)
+    }
+
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<V> {
+        self.root.remove(key.as_ref())
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&V> {
+        let mut node = &self.root;
+        let mut key = key.as_ref();
+        while let Some(ch) = key.first() {
+            node = node.children.get(ch)?;
+            key = key.strip_prefix(node.label.as_slice())?;
+        }
+        node.value.as_ref()
+    }
+
+    pub fn get_mut<K: AsRef<[u8]>>(&mut self, key: K) -> Option<&mut V> {
+        let mut node = &mut self.root;
+        let mut key = key.as_ref();
+        while let Some(ch) = key.first() {
+            node = node.children.get_mut(ch)?;
+            key = key.strip_prefix(node.label.as_slice())?;
+        }
+        node.value.as_mut()
+    }
+
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /*
+     * returns the number of keys starting with the prefix
+     */
+    pub fn count_prefix<K: AsRef<[u8]>>(&self, prefix: K) -> usize {
+        self.find_prefix(prefix.as_ref())
+            .map_or(0, |(node, _)| node.count)
+    }
+
+    /*
+     * returns the length and the value of the longest key that is a prefix of the specified one
+     */
+    pub fn longest_prefix<K: AsRef<[u8]>>(&self, key: K) -> Option<(usize, &V)> {
+        let key = key.as_ref();
+        let mut node = &self.root;
+        let mut depth = 0;
+        let mut res = node.value.as_ref().map(|value| (0, value));
+        while let Some(child) = key.get(depth).and_then(|ch| node.children.get(ch)) {
+            if !key[depth..].starts_with(&child.label) {
+                break;
+            }
+            node = child;
+            depth += child.label.len();
+            if let Some(value) = node.value.as_ref() {
+                res = Some((depth, value));
+            }
+        }
+        res
+    }
+
+    /*
+     * iterates over the keys starting with the prefix in the lexicographic order
+     */
+    pub fn iter_prefix<K: AsRef<[u8]>>(&self, prefix: K) -> impl Iterator<Item = (Vec<u8>, &V)> {
+        let mut stack = vec![];
+        if let Some(start) = self.find_prefix(prefix.as_ref()) {
+            stack.push(start);
+        }
+        std::iter::from_fn(move || {
+            while let Some((node, key)) = stack.pop() {
+                for child in node.children.values().rev() {
+                    let mut next = key.clone();
+                    next.extend_from_slice(&child.label);
+                    stack.push((child, next));
+                }
+                if let Some(value) = node.value.as_ref() {
+                    return Some((key, value));
+                }
+            }
+            None
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Vec<u8>, &V)> {
+        self.iter_prefix(b"")
+    }
+
+    // returns the highest node whose keys start with the prefix, and the full key of the node
+    fn find_prefix(&self, prefix: &[u8]) -> Option<(&RadixNode<V>, Vec<u8>)> {
+        let mut node = &self.root;
+        let mut depth = 0;
+        while depth < prefix.len() {
+            let child = node.children.get(&prefix[depth])?;
+            let rest = &prefix[depth..];
+            if !rest.starts_with(&child.label) && !child.label.starts_with(rest) {
+                return None;
+            }
+            node = child;
+            depth += child.label.len();
+        }
+        let mut key = prefix.to_vec();
+        // the prefix may end inside the last edge
+        key.extend_from_slice(&node.label[node.label.len() - (depth - prefix.len())..]);
+        Some((node, key))
+    }
+}
+
+impl<V> Default for RadixTree<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn radix_tree_test() {
+    // routing table: the longest matching prefix wins
+    let mut routes = RadixTree::new();
+    routes.insert("/", "root");
+    routes.insert("/api", "api");
+    routes.insert("/api/users", "users");
+    routes.insert("/apix", "other");
+    assert_eq!(routes.longest_prefix("/api/users/42"), Some((10, &"users")));
+    assert_eq!(routes.longest_prefix("/api/orders"), Some((4, &"api")));
+    assert_eq!(routes.longest_prefix("/static"), Some((1, &"root")));
+    assert_eq!(routes.longest_prefix("static"), None);
+    assert_eq!(routes.count_prefix("/ap"), 3);
+    assert_eq!(routes.count_prefix("/api/"), 1);
+    let keys: Vec<Vec<u8>> = routes.iter_prefix("/ap").map(|(key, _)| key).collect();
+    assert_eq!(
+        keys,
+        [b"/api".to_vec(), b"/api/users".to_vec(), b"/apix".to_vec()]
+    );
+    *routes.get_mut("/apix").unwrap() = "x";
+    assert_eq!(routes.get("/apix"), Some(&"x"));
+    assert_eq!(routes.get("/ap"), None);
+
+    // random operations compared with Trie and BTreeMap
+    let mut radix = RadixTree::new();
+    let mut trie = Trie::new();
+    let mut expected = BTreeMap::new();
+    let mut rng = crate::random::xorshift_32(21);
+    for step in 0..3000u32 {
+        let len = rng.next().unwrap() % 6;
+        let key: Vec<u8> = (0..len)
+            .map(|_| b'a' + (rng.next().unwrap() % 3) as u8)
+            .collect();
+        if rng.next().unwrap().is_multiple_of(3) {
+            let old = expected.remove(&key);
+            assert_eq!(radix.remove(&key), old);
+            assert_eq!(trie.remove(&key), old);
+        } else {
+            let old = expected.insert(key.clone(), step);
+            assert_eq!(radix.insert(&key, step), old);
+            assert_eq!(trie.insert(&key, step), old);
+        }
+        let prefix = &key[..key.len() / 2];
+        let count = expected
+            .keys()
+            .filter(|other| other.starts_with(prefix))
+            .count();
+        assert_eq!(radix.count_prefix(prefix), count);
+        assert_eq!(trie.count_prefix(prefix), count);
+        assert_eq!(radix.longest_prefix(&key), trie.longest_prefix(&key));
+    }
+    assert_eq!(radix.len(), expected.len());
+    assert!(radix
+        .iter()
+        .map(|(key, value)| (key, *value))
+        .eq(expected.clone().into_iter()));
+    assert!(trie
+        .iter()
+        .map(|(key, value)| (key, *value))
+        .eq(expected.into_iter()));
+}
+
+/*
+* How the non-overlapping search chooses between the matches.
+* Standard - the match that ends first (the longest pattern among the ones ending there),
+* LeftmostFirst - the match that starts first, among them the pattern given first,
+* LeftmostLongest - the match that starts first, among them the longest one.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchKind {
+    Standard,
+    LeftmostFirst,
+    LeftmostLongest,
+}
+
+/*
+* The pattern with the number pattern occurs in the text at [start, end)
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub pattern: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+struct AhoCorasickNode {
+    // sorted by the byte
+    children: Vec<(u8, usize)>,
+    fail: usize,
+    // the patterns equal to the string of the node
+    output: Vec<usize>,
+    // the nearest node on the chain of suffix links with a non-empty output
+    dict_link: Option<usize>,
+    depth: usize,
+    // the leftmost automaton over the same trie, used by the leftmost match kinds:
+    // the nodes reachable only through a match of a pattern with a higher priority are cut off,
+    // a match state has no suffix link (None stands for the dead state),
+    // the output is the best pattern ending in the node or, if there is none, the one copied from the suffix link
+    leftmost_alive: bool,
+    leftmost_fail: Option<usize>,
+    leftmost_output: Option<usize>,
+}
+
+/*
+* Aho–Corasick automaton: a trie of the patterns with suffix links. It is built once in O(total length of the patterns)
+* and then finds all occurrences of all patterns in a text in O(|text| + number of matches).
+* Empty patterns never match.
+*/
+pub struct AhoCorasick {
+    nodes: Vec<AhoCorasickNode>,
+    pattern_lens: Vec<usize>,
+    match_kind: MatchKind,
+    ascii_case_insensitive: bool,
+}
+
+impl AhoCorasick {
+    pub fn new<P: AsRef<[u8]>>(patterns: &[P]) -> Self {
+        Self::with_options(patterns, MatchKind::Standard, false)
+    }
+
+    pub fn with_options<P: AsRef<[u8]>>(
+        patterns: &[P],
+        match_kind: MatchKind,
+        ascii_case_insensitive: bool,
+    ) -> Self {
+        let mut automaton = AhoCorasick {
+            nodes: vec![AhoCorasickNode {
+                children: vec![],
+                fail: 0,
+                output: vec![],
+                dict_link: None,
+                depth: 0,
+                leftmost_alive: true,
+                leftmost_fail: Some(0),
+                leftmost_output: None,
+            }],
+            pattern_lens: patterns
+                .iter()
+                .map(|pattern| pattern.as_ref().len())
+                .collect(),
+            match_kind,
+            ascii_case_insensitive,
+        };
+        for (idx, pattern) in patterns.iter().enumerate() {
+            if !pattern.as_ref().is_empty() {
+                automaton.insert(pattern.as_ref(), idx);
+            }
+        }
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+        while let Some(node) = queue.pop_front() {
+            for idx in 0..automaton.nodes[node].children.len() {
+                let (ch, child) = automaton.nodes[node].children[idx];
+                let fail = if node == 0 {
+                    0
+                } else {
+                    automaton.next_state(automaton.nodes[node].fail, ch)
+                };
+                automaton.nodes[child].fail = fail;
+                automaton.nodes[child].dict_link = if automaton.nodes[fail].output.is_empty() {
+                    automaton.nodes[fail].dict_link
+                } else {
+                    Some(fail)
+                };
+                if match_kind != MatchKind::Standard && automaton.nodes[child].leftmost_alive {
+                    automaton.fill_leftmost(node, child, ch);
+                }
+                queue.push_back(child);
+            }
+        }
+        automaton
+    }
+
+    pub fn patterns_len(&self) -> usize {
+        self.pattern_lens.len()
+    }
+
+    pub fn match_kind(&self) -> MatchKind {
+        self.match_kind
+    }
+
+    pub fn is_match<T: AsRef<[u8]> + ?Sized>(&self, text: &T) -> bool {
+        let mut state = 0;
+        for ch in text.as_ref() {
+            state = self.next_state(state, self.normalize(*ch));
+            if !self.nodes[state].output.is_empty() || self.nodes[state].dict_link.is_some() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /*
+     * returns the first match according to the match kind
+     */
+    pub fn find<T: AsRef<[u8]> + ?Sized>(&self, text: &T) -> Option<Match> {
+        self.find_at(text.as_ref(), 0)
+    }
+
+    /*
+     * iterates over the non-overlapping matches chosen according to the match kind
+     */
+    pub fn find_iter<'a, 'b, T: AsRef<[u8]> + ?Sized>(&'a self, text: &'b T) -> FindIter<'a, 'b> {
+        FindIter {
+            automaton: self,
+            text: text.as_ref(),
+            pos: 0,
+        }
+    }
+
+    /*
+     * iterates over all occurrences of all patterns ordered by their ends
+     * (the longer patterns first among the ones ending at the same position)
+     */
+    pub fn find_overlapping_iter<'a, 'b, T: AsRef<[u8]> + ?Sized>(
+        &'a self,
+        text: &'b T,
+    ) -> FindOverlappingIter<'a, 'b> {
+        FindOverlappingIter {
+            automaton: self,
+            text: text.as_ref(),
+            pos: 0,
+            state: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /*
+     * starts a search over a text given in chunks, see StreamSearcher
+     */
+    pub fn stream(&self) -> StreamSearcher<'_> {
+        StreamSearcher {
+            automaton: self,
+            state: 0,
+            offset: 0,
+        }
+    }
+
+    fn insert(&mut self, pattern: &[u8], idx: usize) {
+        let mut node = 0;
+        // leftmost-first never reports a pattern passing through a match of an earlier one
+        let mut cut = false;
+        for ch in pattern {
+            let ch = self.normalize(*ch);
+            cut |= self.match_kind == MatchKind::LeftmostFirst
+                && self.nodes[node].leftmost_output.is_some();
+            node = match self.child(node, ch) {
+                Some(next) => next,
+                None => {
+                    let next = self.nodes.len();
+                    let depth = self.nodes[node].depth + 1;
+                    self.nodes.push(AhoCorasickNode {
+                        children: vec![],
+                        fail: 0,
+                        output: vec![],
+                        dict_link: None,
+                        depth,
+                        leftmost_alive: false,
+                        leftmost_fail: None,
+                        leftmost_output: None,
+                    });
+                    let children = &mut self.nodes[node].children;
+                    let pos = children.partition_point(|(other, _)| *other < ch);
+                    children.insert(pos, (ch, next));
+                    next
+                }
+            };
+            self.nodes[node].leftmost_alive |= !cut;
+        }
+        self.nodes[node].output.push(idx);
+        if !cut && self.nodes[node].leftmost_output.is_none() {
+            self.nodes[node].leftmost_output = Some(idx);
+        }
+    }
+
+    fn fill_leftmost(&mut self, parent: usize, child: usize, ch: u8) {
+        if self.nodes[child].leftmost_output.is_some() {
+            // the match can only be extended, every match starting later loses to it
+            self.nodes[child].leftmost_fail = None;
+            return;
+        }
+        let fail = if parent == 0 {
+            Some(0)
+        } else {
+            self.nodes[parent]
+                .leftmost_fail
+                .and_then(|fail| self.leftmost_next_state(fail, ch))
+        };
+        self.nodes[child].leftmost_fail = fail;
+        self.nodes[child].leftmost_output = fail.and_then(|fail| self.nodes[fail].leftmost_output);
+    }
+
+    fn normalize(&self, ch: u8) -> u8 {
+        if self.ascii_case_insensitive {
+            ch.to_ascii_lowercase()
+        } else {
+            ch
+        }
+    }
+
+    fn child(&self, node: usize, ch: u8) -> Option<usize> {
+        let children = &self.nodes[node].children;
+        children
+            .binary_search_by_key(&ch, |(other, _)| *other)
+            .ok()
+            .map(|idx| children[idx].1)
+    }
+
+    fn next_state(&self, mut state: usize, ch: u8) -> usize {
+        loop {
+            if let Some(next) = self.child(state, ch) {
+                return next;
+            }
+            if state == 0 {
+                return 0;
+            }
+            state = self.nodes[state].fail;
+        }
+    }
+
+    // calls cb for every pattern ending in the state, the longest ones first
+    fn for_each_output<F: FnMut(Match)>(&self, state: usize, end: usize, mut cb: F) {
+        let mut node = Some(state);
+        while let Some(current) = node {
+            for pattern in self.nodes[current].output.iter() {
+                cb(Match {
+                    pattern: *pattern,
+                    start: end - self.pattern_lens[*pattern],
+                    end,
+                });
+            }
+            node = self.nodes[current].dict_link;
+        }
+    }
+
+    fn leftmost_next_state(&self, mut state: usize, ch: u8) -> Option<usize> {
+        loop {
+            if let Some(next) = self
+                .child(state, ch)
+                .filter(|next| self.nodes[*next].leftmost_alive)
+            {
+                return Some(next);
+            }
+            if state == 0 {
+                return Some(0);
+            }
+            state = self.nodes[state].leftmost_fail?;
+        }
+    }
+
+    fn find_at(&self, text: &[u8], from: usize) -> Option<Match> {
+        if self.match_kind != MatchKind::Standard {
+            return self.find_leftmost_at(text, from);
+        }
+        let mut state = 0;
+        for (idx, ch) in text.iter().enumerate().skip(from) {
+            state = self.next_state(state, self.normalize(*ch));
+            let mut best = None;
+            // the first output is the longest pattern with the smallest number
+            self.for_each_output(state, idx + 1, |found| {
+                best = best.or(Some(found));
+            });
+            if best.is_some() {
+                return best;
+            }
+        }
+        None
+    }
+
+    // scans the leftmost automaton until the dead state: the bytes after the found match are read
+    // only while a better match starting at the same position is still possible
+    fn find_leftmost_at(&self, text: &[u8], from: usize) -> Option<Match> {
+        let mut state = 0;
+        let mut best = None;
+        for (idx, ch) in text.iter().enumerate().skip(from) {
+            state = match self.leftmost_next_state(state, self.normalize(*ch)) {
+                Some(next) => next,
+                None => return best,
+            };
+            if let Some(pattern) = self.nodes[state].leftmost_output {
+                best = Some(Match {
+                    pattern,
+                    start: idx + 1 - self.pattern_lens[pattern],
+                    end: idx + 1,
+                });
+            }
+        }
+        best
+    }
+}
+
+pub struct FindIter<'a, 'b> {
+    automaton: &'a AhoCorasick,
+    text: &'b [u8],
+    pos: usize,
+}
+
+impl Iterator for FindIter<'_, '_> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        let found = self.automaton.find_at(self.text, self.pos)?;
+        self.pos = found.end;
+        Some(found)
+    }
+}
+
+pub struct FindOverlappingIter<'a, 'b> {
+    automaton: &'a AhoCorasick,
+    text: &'b [u8],
+    pos: usize,
+    state: usize,
+    pending: VecDeque<Match>,
+}
+
+impl Iterator for FindOverlappingIter<'_, '_> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        while self.pending.is_empty() && self.pos < self.text.len() {
+            let ch = self.automaton.normalize(self.text[self.pos]);
+            self.state = self.automaton.next_state(self.state, ch);
+            self.pos += 1;
+            let pending = &mut self.pending;
+            self.automaton
+                .for_each_output(self.state, self.pos, |found| pending.push_back(found));
+        }
+        self.pending.pop_front()
+    }
+}
+
+/*
+* Search over a text that comes in chunks (for example read from a file), a match may span several chunks.
+* All occurrences are reported like in find_overlapping_iter, the positions are counted from the start of the whole text.
+*/
+pub struct StreamSearcher<'a> {
+    automaton: &'a AhoCorasick,
+    state: usize,
+    offset: usize,
+}
+
+impl StreamSearcher<'_> {
+    /*
+     * processes the next chunk and calls cb for every match ending in it
+     */
+    pub fn feed<F: FnMut(Match)>(&mut self, chunk: &[u8], mut cb: F) {
+        for ch in chunk {
+            self.state = self
+                .automaton
+                .next_state(self.state, self.automaton.normalize(*ch));
+            self.offset += 1;
+            self.automaton
+                .for_each_output(self.state, self.offset, &mut cb);
+        }
+    }
+
+    /*
+     * the number of bytes processed so far
+     */
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+#[allow(unused)]
+pub fn aho_corasick(dict: &[&str], t: &str) -> BTreeMap<i32, Vec<usize>> {
+    let mut res: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    let automaton = AhoCorasick::new(dict);
+    for found in automaton.find_overlapping_iter(t) {
+        res.entry(found.pattern as i32)
+            .or_default()
+            .push(found.start);
+    }
+    res
+}
+
+#[cfg(test)]
+#[test]
+fn aho_corasick_automaton_test() {
+    let spans = |matches: Vec<Match>| -> Vec<(usize, usize, usize)> {
+        matches
+            .into_iter()
+            .map(|found| (found.pattern, found.start, found.end))
+            .collect()
+    };
+    let patterns = ["abcd", "b", "bcd", "abc"];
+    let text = "xabcdx";
+    let standard = AhoCorasick::new(&patterns);
+    assert_eq!(spans(standard.find_iter(text).collect()), vec![(1, 2, 3)]);
+    let first = AhoCorasick::with_options(&patterns, MatchKind::LeftmostFirst, false);
+    assert_eq!(spans(first.find_iter(text).collect()), vec![(0, 1, 5)]);
+    let patterns = ["abc", "b", "abcd", "bcd"];
+    let first = AhoCorasick::with_options(&patterns, MatchKind::LeftmostFirst, false);
+    assert_eq!(spans(first.find_iter(text).collect()), vec![(0, 1, 4)]);
+    let longest = AhoCorasick::with_options(&patterns, MatchKind::LeftmostLongest, false);
+    assert_eq!(spans(longest.find_iter(text).collect()), vec![(2, 1, 5)]);
+    assert_eq!(
+        spans(longest.find_overlapping_iter(text).collect()),
+        vec![(1, 2, 3), (0, 1, 4), (2, 1, 5), (3, 2, 5)]
+    );
+
+    let patterns = ["Samwise", "Sam", "wise"];
+    let longest = AhoCorasick::with_options(&patterns, MatchKind::LeftmostLongest, true);
+    assert_eq!(
+        spans(longest.find_iter("sam and SAMWISE, wise sam").collect()),
+        vec![(1, 0, 3), (0, 8, 15), (2, 17, 21), (1, 22, 25)]
+    );
+    assert!(longest.is_match("SAM"));
+    assert!(!AhoCorasick::new(&patterns).is_match("SAM"));
+
+    // a pattern after an earlier one that is its prefix never matches with leftmost-first,
+    // a match starting later is found through the suffix links
+    let first = AhoCorasick::with_options(&["ab", "abc"], MatchKind::LeftmostFirst, false);
+    assert_eq!(
+        spans(first.find_iter("abcabc").collect()),
+        vec![(0, 0, 2), (0, 3, 5)]
+    );
+    let first = AhoCorasick::with_options(&["abc", "ab"], MatchKind::LeftmostFirst, false);
+    assert_eq!(
+        spans(first.find_iter("abcab").collect()),
+        vec![(0, 0, 3), (1, 3, 5)]
+    );
+    let first = AhoCorasick::with_options(&["abcd", "bc", "ce"], MatchKind::LeftmostFirst, false);
+    assert_eq!(
+        spans(first.find_iter("abce abcd").collect()),
+        vec![(1, 1, 3), (0, 5, 9)]
+    );
+
+    // the scan stops right after the match instead of following the longer pattern
+    let long = "a".repeat(5000) + "b";
+    let first = AhoCorasick::with_options(&["a", long.as_str()], MatchKind::LeftmostFirst, false);
+    let text = "a".repeat(200_000);
+    assert!(first.find_iter(&text).enumerate().all(|(idx, found)| (
+        found.pattern,
+        found.start,
+        found.end
+    ) == (0, idx, idx + 1)));
+    assert_eq!(first.find_iter(&text).count(), text.len());
+    assert_eq!(
+        AhoCorasick::new(&["", "x"]).find("ax"),
+        Some(Match {
+            pattern: 1,
+            start: 1,
+            end: 2
+        })
+    );
+
+    // the streaming search gives the same matches as the search over the whole text
+    let patterns = ["error", "err", "timeout", "or t"];
+    let automaton = AhoCorasick::new(&patterns);
+    let text = "connection error timeout; error: timeout";
+    let mut streamed = vec![];
+    let mut stream = automaton.stream();
+    for chunk in text.as_bytes().chunks(3) {
+        stream.feed(chunk, |found| streamed.push(found));
+    }
+    assert_eq!(stream.offset(), text.len());
+    assert_eq!(
+        streamed,
+        automaton.find_overlapping_iter(text).collect::<Vec<_>>()
+    );
+    assert_eq!(streamed.len(), 7);
+
+    // random texts compared with the naive search
+    let mut rng = crate::random::xorshift_32(17);
+    let mut word = |len: u32| -> String {
+        (0..len)
+            .map(|_| (b'a' + (rng.next().unwrap() % 3) as u8) as char)
+            .collect()
+    };
+    let patterns: Vec<String> = (1..30).map(|idx| word(1 + idx % 5)).collect();
+    let text = word(2000);
+    let automaton = AhoCorasick::new(&patterns);
+    let mut expected = vec![];
+    for end in 1..=text.len() {
+        let mut ending: Vec<(usize, usize, usize)> = patterns
+            .iter()
+            .enumerate()
+            .filter(|(_, pattern)| text[..end].ends_with(pattern.as_str()))
+            .map(|(idx, pattern)| (idx, end - pattern.len(), end))
+            .collect();
+        ending.sort_by_key(|(idx, start, _)| (*start, *idx));
+        expected.extend(ending);
+    }
+    assert_eq!(
+        spans(automaton.find_overlapping_iter(&text).collect()),
+        expected
+    );
+    for kind in [MatchKind::LeftmostFirst, MatchKind::LeftmostLongest] {
+        let automaton = AhoCorasick::with_options(&patterns, kind, false);
+        let mut pos = 0;
+        let mut naive = vec![];
+        while pos < text.len() {
+            let found = (pos..text.len()).find_map(|start| {
+                let candidates = patterns
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, pattern)| text[start..].starts_with(pattern.as_str()));
+                match kind {
+                    MatchKind::LeftmostFirst => candidates.min_by_key(|(idx, _)| *idx),
+                    _ => candidates
+                        .max_by_key(|(idx, pattern)| (pattern.len(), std::cmp::Reverse(*idx))),
+                }
+                .map(|(idx, pattern)| (idx, start, start + pattern.len()))
+            });
+            match found {
+                Some(found) => {
+                    naive.push(found);
+                    pos = found.2;
+                }
+                None => break,
+            }
+        }
+        assert_eq!(spans(automaton.find_iter(&text).collect()), naive);
+    }
+}
+
+#[test]
+fn aho_corasick_test() {
+    let mut dict = ["aba", "abb", "bbca"];
+    let text = "abaabbbbca";
+    let mut res = aho_corasick(&dict, text);
+
+    let mut map = BTreeMap::new();
+    map.insert(0, vec![0]);
+    map.insert(1, vec![3]);
+    map.insert(2, vec![6]);
+    assert_eq!(map, res);
+
+    let text = "abaabbbbcaaba";
+    res = aho_corasick(&dict, text);
+
+    map = BTreeMap::new();
+    map.insert(0, vec![0, 10]);
+    map.insert(1, vec![3]);
+    map.insert(2, vec![6]);
+    assert_eq!(map, res);
+
+    let text = "abaabbbbcaba";
+    res = aho_corasick(&dict, text);
+
+    map = BTreeMap::new();
+    map.insert(0, vec![0, 9]);
+    map.insert(1, vec![3]);
+    map.insert(2, vec![6]);
+    assert_eq!(map, res);
+
+    dict = ["abba", "bb", "cc"];
+    let text = "abba";
+    res = aho_corasick(&dict, text);
+
+    map = BTreeMap::new();
+    map.insert(0, vec![0]);
+    map.insert(1, vec![1]);
+    assert_eq!(map, res);
+
+    dict = ["aba", "baba", "cc"];
+    let text = "ababababa";
+    res = aho_corasick(&dict, text);
+
+    map = BTreeMap::new();
+    map.insert(0, vec![0, 2, 4, 6]);
+    map.insert(1, vec![1, 3, 5]);
+    assert_eq!(map, res);
+
+    let dict = ["aba", "baba", "cc"];
+    let text = "ababababa";
+    let res = aho_corasick(&dict, text);
+
+    let mut map = BTreeMap::new();
     map.insert(0, vec![0, 2, 4, 6]);
     map.insert(1, vec![1, 3, 5]);
     assert_eq!(map, res);
 }
 
-#[allow(unused)]
-pub fn diferent_substrings_generator(s: &str) -> Vec<&str> {
-    let mut seq = vec![];
-    for i in (0..s.len()).rev() {
-        let pr = z_function(&s[i..s.len()]);
-        let res = s.len() - i - pr.iter().max_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
-        for j in 0..res {
-            seq.push(&s[i..s.len() - j]);
+#[allow(unused)]
+pub fn diferent_substrings_generator(s: &str) -> Vec<&str> {
+    let mut seq = vec![];
+    for i in (0..s.len()).rev() {
+        let pr = z_function(&s[i..s.len()]);
+        let res = s.len() - i - pr.iter().max_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
+        for j in 0..res {
+            seq.push(&s[i..s.len() - j]);
+        }
+    }
+    seq
+}
+
+#[allow(unused)]
+pub fn distinct_substrings_count(s: &str) -> usize {
+    SuffixArray::new(s.as_bytes()).distinct_substrings_count()
+}
+
+#[cfg(test)]
+#[test]
+fn test_distinct_substrings() {
+    assert_eq!(diferent_substrings_generator("a"), vec!["a"]);
+    assert_eq!(
+        diferent_substrings_generator("aaaa"),
+        vec!["a", "aa", "aaa", "aaaa"]
+    );
+    assert_eq!(diferent_substrings_generator(""), Vec::<&str>::new());
+    let mut values = diferent_substrings_generator("abaaba");
+    values.sort();
+    assert_eq!(
+        values,
+        vec![
+            "a", "aa", "aab", "aaba", "ab", "aba", "abaa", "abaab", "abaaba", "b", "ba", "baa",
+            "baab", "baaba"
+        ]
+    );
+    assert_eq!(distinct_substrings_count("abacabadabacaba"), 85);
+}
+
+/*
+* Suffix array of a string over bytes: the starting positions of all suffixes in the lexicographic order.
+* Built with SA-IS in O(n)
+*/
+pub fn suffix_array(s: &[u8]) -> Vec<usize> {
+    let s: Vec<usize> = s.iter().map(|ch| *ch as usize).collect();
+    sa_is(&s, 255)
+}
+
+/*
+* Suffix array of a string over integers, all values must not exceed upper. O(n + upper)
+*/
+pub fn suffix_array_int(s: &[usize], upper: usize) -> Vec<usize> {
+    assert!(s.iter().all(|value| *value <= upper));
+    sa_is(s, upper)
+}
+
+/*
+* Suffix array of a sequence of any ordered values, they are replaced by their ranks first. O(n log n)
+*/
+pub fn suffix_array_ord<T: Ord>(s: &[T]) -> Vec<usize> {
+    let mut values: Vec<&T> = s.iter().collect();
+    values.sort();
+    values.dedup();
+    let ranks: Vec<usize> = s
+        .iter()
+        .map(|value| values.binary_search(&value).unwrap())
+        .collect();
+    sa_is(&ranks, values.len().saturating_sub(1))
+}
+
+// SA-IS: the suffixes are classified as S (less than the next suffix) and L, the leftmost S suffixes (LMS)
+// are sorted recursively and the order of all suffixes is induced from them
+fn sa_is(s: &[usize], upper: usize) -> Vec<usize> {
+    let n = s.len();
+    match n {
+        0 => return vec![],
+        1 => return vec![0],
+        2 => return if s[0] < s[1] { vec![0, 1] } else { vec![1, 0] },
+        _ => {}
+    }
+    let mut ls = vec![false; n];
+    for idx in (0..n - 1).rev() {
+        ls[idx] = if s[idx] == s[idx + 1] {
+            ls[idx + 1]
+        } else {
+            s[idx] < s[idx + 1]
+        };
+    }
+    // the starts of the L and S parts of the bucket of every value
+    let mut sum_l = vec![0; upper + 2];
+    let mut sum_s = vec![0; upper + 2];
+    for idx in 0..n {
+        if ls[idx] {
+            sum_l[s[idx] + 1] += 1;
+        } else {
+            sum_s[s[idx]] += 1;
+        }
+    }
+    for value in 0..=upper {
+        sum_s[value] += sum_l[value];
+        sum_l[value + 1] += sum_s[value];
+    }
+    let is_lms = |idx: usize| idx > 0 && !ls[idx - 1] && ls[idx];
+    let lms: Vec<usize> = (1..n).filter(|idx| is_lms(*idx)).collect();
+    let mut lms_map = vec![usize::MAX; n];
+    for (num, idx) in lms.iter().enumerate() {
+        lms_map[*idx] = num;
+    }
+    let mut sa = vec![usize::MAX; n];
+    sa_is_induce(s, &ls, &sum_l, &sum_s, &lms, &mut sa);
+    let m = lms.len();
+    if m > 0 {
+        let mut sorted_lms: Vec<usize> = sa
+            .iter()
+            .copied()
+            .filter(|idx| lms_map[*idx] != usize::MAX)
+            .collect();
+        // equal LMS substrings get equal names
+        let mut rec_s = vec![0; m];
+        let mut rec_upper = 0;
+        for idx in 1..m {
+            let (mut l, mut r) = (sorted_lms[idx - 1], sorted_lms[idx]);
+            let end_l = lms.get(lms_map[l] + 1).copied().unwrap_or(n);
+            let end_r = lms.get(lms_map[r] + 1).copied().unwrap_or(n);
+            let mut same = end_l - l == end_r - r;
+            if same {
+                while l < end_l && s[l] == s[r] {
+                    l += 1;
+                    r += 1;
+                }
+                same = l < n && s[l] == s[r];
+            }
+            if !same {
+                rec_upper += 1;
+            }
+            rec_s[lms_map[sorted_lms[idx]]] = rec_upper;
+        }
+        let rec_sa = sa_is(&rec_s, rec_upper);
+        for (idx, value) in rec_sa.iter().enumerate() {
+            sorted_lms[idx] = lms[*value];
+        }
+        sa_is_induce(s, &ls, &sum_l, &sum_s, &sorted_lms, &mut sa);
+    }
+    sa
+}
+
+fn sa_is_induce(
+    s: &[usize],
+    ls: &[bool],
+    sum_l: &[usize],
+    sum_s: &[usize],
+    lms: &[usize],
+    sa: &mut [usize],
+) {
+    let n = s.len();
+    sa.fill(usize::MAX);
+    let mut buf = sum_s.to_vec();
+    for idx in lms {
+        sa[buf[s[*idx]]] = *idx;
+        buf[s[*idx]] += 1;
+    }
+    let mut buf = sum_l.to_vec();
+    sa[buf[s[n - 1]]] = n - 1;
+    buf[s[n - 1]] += 1;
+    for pos in 0..n {
+        let value = sa[pos];
+        if value != usize::MAX && value >= 1 && !ls[value - 1] {
+            sa[buf[s[value - 1]]] = value - 1;
+            buf[s[value - 1]] += 1;
+        }
+    }
+    let mut buf = sum_l.to_vec();
+    for pos in (0..n).rev() {
+        let value = sa[pos];
+        if value != usize::MAX && value >= 1 && ls[value - 1] {
+            buf[s[value - 1] + 1] -= 1;
+            sa[buf[s[value - 1] + 1]] = value - 1;
+        }
+    }
+}
+
+/*
+* Kasai's algorithm: lcp[i] is the length of the longest common prefix of the suffixes sa[i] and sa[i + 1]. O(n)
+*/
+pub fn lcp_array<T: Eq>(s: &[T], sa: &[usize]) -> Vec<usize> {
+    let n = s.len();
+    if n == 0 {
+        return vec![];
+    }
+    let mut rank = vec![0; n];
+    for (idx, pos) in sa.iter().enumerate() {
+        rank[*pos] = idx;
+    }
+    let mut lcp = vec![0; n - 1];
+    let mut h: usize = 0;
+    for pos in 0..n {
+        h = h.saturating_sub(1);
+        if rank[pos] == 0 {
+            continue;
+        }
+        let prev = sa[rank[pos] - 1];
+        while prev + h < n && pos + h < n && s[prev + h] == s[pos + h] {
+            h += 1;
+        }
+        lcp[rank[pos] - 1] = h;
+    }
+    lcp
+}
+
+/*
+* Suffix array together with the LCP array and queries built on them.
+* The longest common prefix of any two suffixes is answered in O(1) with the range minimum query over the LCP array.
+*/
+pub struct SuffixArray<'a> {
+    text: &'a [u8],
+    sa: Vec<usize>,
+    rank: Vec<usize>,
+    lcp: Vec<usize>,
+    rmq: Rmq<usize>,
+}
+
+impl<'a> SuffixArray<'a> {
+    pub fn new(text: &'a [u8]) -> Self {
+        let sa = suffix_array(text);
+        let lcp = lcp_array(text, &sa);
+        let mut rank = vec![0; text.len()];
+        for (idx, pos) in sa.iter().enumerate() {
+            rank[*pos] = idx;
+        }
+        let rmq = Rmq::from_slice(&lcp);
+        SuffixArray {
+            text,
+            sa,
+            rank,
+            lcp,
+            rmq,
+        }
+    }
+
+    pub fn suffixes(&self) -> &[usize] {
+        &self.sa
+    }
+
+    pub fn lcp(&self) -> &[usize] {
+        &self.lcp
+    }
+
+    /*
+     * returns the range of the suffix array with the suffixes starting with the pattern. O(|pattern| log n)
+     */
+    pub fn find_range(&self, pattern: &[u8]) -> std::ops::Range<usize> {
+        let prefix =
+            |pos: usize| &self.text[pos..std::cmp::min(pos + pattern.len(), self.text.len())];
+        let start = self.sa.partition_point(|pos| prefix(*pos) < pattern);
+        let end = self.sa.partition_point(|pos| prefix(*pos) <= pattern);
+        start..end
+    }
+
+    /*
+     * returns the sorted positions of all occurrences of the pattern
+     */
+    pub fn find_all(&self, pattern: &[u8]) -> Vec<usize> {
+        let mut res = self.sa[self.find_range(pattern)].to_vec();
+        res.sort_unstable();
+        res
+    }
+
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        self.find_range(pattern).len()
+    }
+
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        !self.find_range(pattern).is_empty()
+    }
+
+    /*
+     * returns the length of the longest common prefix of the suffixes starting at first and second
+     */
+    pub fn longest_common_prefix(&self, first: usize, second: usize) -> usize {
+        if first == second {
+            return self.text.len() - first;
+        }
+        let (l, r) = (self.rank[first], self.rank[second]);
+        let (l, r) = (std::cmp::min(l, r), std::cmp::max(l, r));
+        *self.rmq.min(l, r).unwrap()
+    }
+
+    /*
+     * every suffix adds the prefixes that are not prefixes of the previous suffix
+     */
+    pub fn distinct_substrings_count(&self) -> usize {
+        let n = self.text.len();
+        n * (n + 1) / 2 - self.lcp.iter().sum::<usize>()
+    }
+
+    /*
+     * returns the leftmost of the longest substrings occurring at least twice
+     */
+    pub fn longest_repeated_substring(&self) -> Option<&'a [u8]> {
+        let best = *self.lcp.iter().max()?;
+        if best == 0 {
+            return None;
+        }
+        let start = self
+            .lcp
+            .iter()
+            .enumerate()
+            .filter(|(_, len)| **len == best)
+            .map(|(idx, _)| std::cmp::min(self.sa[idx], self.sa[idx + 1]))
+            .min()
+            .unwrap();
+        Some(&self.text[start..start + best])
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn suffix_array_test() {
+    let naive = |s: &[u8]| {
+        let mut sa: Vec<usize> = (0..s.len()).collect();
+        sa.sort_by_key(|pos| &s[*pos..]);
+        sa
+    };
+    assert_eq!(suffix_array(b"banana"), vec![5, 3, 1, 0, 4, 2]);
+    assert_eq!(
+        lcp_array(b"banana", &[5, 3, 1, 0, 4, 2]),
+        vec![1, 3, 0, 0, 2]
+    );
+    assert_eq!(suffix_array(b""), Vec::<usize>::new());
+    let mut rng = crate::random::xorshift_32(23);
+    for len in [1, 2, 3, 10, 100, 1000] {
+        for alphabet in [1, 2, 4, 26] {
+            let s: Vec<u8> = (0..len)
+                .map(|_| b'a' + (rng.next().unwrap() % alphabet) as u8)
+                .collect();
+            let sa = suffix_array(&s);
+            assert_eq!(sa, naive(&s));
+            let lcp = lcp_array(&s, &sa);
+            for idx in 0..lcp.len() {
+                let (a, b) = (&s[sa[idx]..], &s[sa[idx + 1]..]);
+                assert_eq!(
+                    lcp[idx],
+                    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+                );
+            }
+            let ints: Vec<usize> = s.iter().map(|ch| (*ch - b'a') as usize * 1000).collect();
+            assert_eq!(suffix_array_int(&ints, 26000), sa);
+            assert_eq!(suffix_array_ord(&ints), sa);
+        }
+    }
+    let words = ["to", "be", "or", "not", "to", "be"];
+    assert_eq!(suffix_array_ord(&words), vec![5, 1, 3, 2, 4, 0]);
+
+    let text = b"abracadabra";
+    let suffixes = SuffixArray::new(text);
+    assert_eq!(suffixes.find_all(b"abra"), vec![0, 7]);
+    assert_eq!(suffixes.find_all(b"a"), vec![0, 3, 5, 7, 10]);
+    assert_eq!(suffixes.count(b"cad"), 1);
+    assert!(!suffixes.contains(b"abrab"));
+    assert_eq!(suffixes.count(b""), text.len());
+    assert_eq!(suffixes.longest_common_prefix(0, 7), 4);
+    assert_eq!(suffixes.longest_common_prefix(3, 3), 8);
+    assert_eq!(suffixes.longest_common_prefix(1, 2), 0);
+    assert_eq!(suffixes.longest_repeated_substring(), Some(&b"abra"[..]));
+    assert_eq!(SuffixArray::new(b"abc").longest_repeated_substring(), None);
+    assert_eq!(
+        SuffixArray::new(b"abacabadabacaba").distinct_substrings_count(),
+        85
+    );
+    assert_eq!(suffixes.suffixes().len(), suffixes.lcp().len() + 1);
+}
+
+struct SuffixAutomatonState {
+    len: usize,
+    link: Option<usize>,
+    next: BTreeMap<u8, usize>,
+    // the end of the first occurrence of the strings of the state
+    first_end: usize,
+    is_clone: bool,
+}
+
+// values depending on the whole automaton, computed on the first query
+struct SuffixAutomatonStats {
+    // the states ordered by len
+    order: Vec<usize>,
+    occurrences: Vec<usize>,
+    // the number of paths starting in the state (including the empty one)
+    paths: Vec<usize>,
+}
+
+/*
+* Suffix automaton: the minimal automaton accepting all suffixes of a string, its paths from the start state
+* are exactly the substrings. It is built online in O(n log k) and has at most 2n states.
+* Every state is a class of substrings with the same set of end positions.
+*/
+pub struct SuffixAutomaton {
+    states: Vec<SuffixAutomatonState>,
+    last: usize,
+    stats: OnceCell<SuffixAutomatonStats>,
+}
+
+impl SuffixAutomaton {
+    pub fn new() -> Self {
+        SuffixAutomaton {
+            states: vec![SuffixAutomatonState {
+                len: 0,
+                link: None,
+                next: BTreeMap::new(),
+                first_end: 0,
+                is_clone: false,
+            }],
+            last: 0,
+            stats: OnceCell::new(),
+        }
+    }
+
+    pub fn build(s: &[u8]) -> Self {
+        let mut automaton = Self::new();
+        for ch in s {
+            automaton.extend(*ch);
+        }
+        automaton
+    }
+
+    /*
+     * appends the character to the string
+     */
+    pub fn extend(&mut self, ch: u8) {
+        self.stats = OnceCell::new();
+        let len = self.states[self.last].len + 1;
+        let cur = self.states.len();
+        self.states.push(SuffixAutomatonState {
+            len,
+            link: None,
+            next: BTreeMap::new(),
+            first_end: len - 1,
+            is_clone: false,
+        });
+        let mut p = Some(self.last);
+        while let Some(state) = p {
+            if self.states[state].next.contains_key(&ch) {
+                break;
+            }
+            self.states[state].next.insert(ch, cur);
+            p = self.states[state].link;
+        }
+        self.states[cur].link = match p {
+            None => Some(0),
+            Some(state) => {
+                let q = self.states[state].next[&ch];
+                if self.states[state].len + 1 == self.states[q].len {
+                    Some(q)
+                } else {
+                    let clone = self.states.len();
+                    self.states.push(SuffixAutomatonState {
+                        len: self.states[state].len + 1,
+                        link: self.states[q].link,
+                        next: self.states[q].next.clone(),
+                        first_end: self.states[q].first_end,
+                        is_clone: true,
+                    });
+                    let mut p = Some(state);
+                    while let Some(state) = p {
+                        if self.states[state].next.get(&ch) != Some(&q) {
+                            break;
+                        }
+                        self.states[state].next.insert(ch, clone);
+                        p = self.states[state].link;
+                    }
+                    self.states[q].link = Some(clone);
+                    Some(clone)
+                }
+            }
+        };
+        self.last = cur;
+    }
+
+    /*
+     * the length of the string
+     */
+    pub fn len(&self) -> usize {
+        self.states[self.last].len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        self.state_of(pattern).is_some()
+    }
+
+    /*
+     * returns the number of occurrences of the pattern
+     */
+    pub fn occurrences(&self, pattern: &[u8]) -> usize {
+        if pattern.is_empty() {
+            return self.len() + 1;
+        }
+        self.state_of(pattern)
+            .map_or(0, |state| self.stats().occurrences[state])
+    }
+
+    /*
+     * returns the position of the first occurrence of the pattern
+     */
+    pub fn first_occurrence(&self, pattern: &[u8]) -> Option<usize> {
+        if pattern.is_empty() {
+            return Some(0);
+        }
+        self.state_of(pattern)
+            .map(|state| self.states[state].first_end + 1 - pattern.len())
+    }
+
+    /*
+     * returns the number of distinct non-empty substrings
+     */
+    pub fn distinct_substrings_count(&self) -> usize {
+        self.states
+            .iter()
+            .skip(1)
+            .map(|state| state.len - self.states[state.link.unwrap()].len)
+            .sum()
+    }
+
+    /*
+     * returns the k-th (numbering from zero) distinct non-empty substring in the lexicographic order
+     */
+    pub fn kth_substring(&self, mut k: usize) -> Option<Vec<u8>> {
+        let paths = &self.stats().paths;
+        let mut res = vec![];
+        let mut state = 0;
+        'outer: loop {
+            for (ch, next) in self.states[state].next.iter() {
+                if k < paths[*next] {
+                    res.push(*ch);
+                    if k == 0 {
+                        return Some(res);
+                    }
+                    k -= 1;
+                    state = *next;
+                    continue 'outer;
+                }
+                k -= paths[*next];
+            }
+            return None;
+        }
+    }
+
+    fn state_of(&self, pattern: &[u8]) -> Option<usize> {
+        let mut state = 0;
+        for ch in pattern {
+            state = *self.states[state].next.get(ch)?;
+        }
+        Some(state)
+    }
+
+    fn order(&self) -> Vec<usize> {
+        let mut count = vec![0; self.len() + 2];
+        for state in self.states.iter() {
+            count[state.len + 1] += 1;
+        }
+        for idx in 1..count.len() {
+            count[idx] += count[idx - 1];
+        }
+        let mut order = vec![0; self.states.len()];
+        for (idx, state) in self.states.iter().enumerate() {
+            order[count[state.len]] = idx;
+            count[state.len] += 1;
+        }
+        order
+    }
+
+    fn stats(&self) -> &SuffixAutomatonStats {
+        self.stats.get_or_init(|| {
+            let order = self.order();
+            let mut occurrences: Vec<usize> = self
+                .states
+                .iter()
+                .enumerate()
+                .map(|(idx, state)| usize::from(idx != 0 && !state.is_clone))
+                .collect();
+            let mut paths = vec![1; self.states.len()];
+            for state in order.iter().rev() {
+                if let Some(link) = self.states[*state].link {
+                    occurrences[link] += occurrences[*state];
+                }
+                for next in self.states[*state].next.values() {
+                    paths[*state] += paths[*next];
+                }
+            }
+            SuffixAutomatonStats {
+                order,
+                occurrences,
+                paths,
+            }
+        })
+    }
+}
+
+impl Default for SuffixAutomaton {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/*
+* Longest common substring of several strings with the suffix automaton of the first one. O(total length)
+*/
+pub fn longest_common_substring<'a>(strings: &[&'a [u8]]) -> Option<&'a [u8]> {
+    let range = longest_common_substring_range(strings)?;
+    Some(&strings[0][range])
+}
+
+// the position of the answer in the first string
+fn longest_common_substring_range(strings: &[&[u8]]) -> Option<std::ops::Range<usize>> {
+    let (first, rest) = strings.split_first()?;
+    let automaton = SuffixAutomaton::build(first);
+    let states = &automaton.states;
+    let order = &automaton.stats().order;
+    // the longest common length for the strings of every state
+    let mut common: Vec<usize> = states.iter().map(|state| state.len).collect();
+    for s in rest {
+        let mut matched = vec![0; states.len()];
+        let (mut state, mut len) = (0, 0);
+        for ch in s.iter() {
+            while state != 0 && !states[state].next.contains_key(ch) {
+                state = states[state].link.unwrap();
+                len = states[state].len;
+            }
+            if let Some(next) = states[state].next.get(ch) {
+                state = *next;
+                len += 1;
+            }
+            matched[state] = std::cmp::max(matched[state], len);
+        }
+        // a match in a state is a full match of its suffix link
+        for state in order.iter().rev() {
+            if let Some(link) = states[*state].link {
+                if matched[*state] > 0 {
+                    matched[link] = std::cmp::max(matched[link], states[link].len);
+                }
+            }
+        }
+        for (value, matched) in common.iter_mut().zip(matched) {
+            *value = std::cmp::min(*value, matched);
         }
     }
-    seq
+    let (len, end) = (1..states.len())
+        .map(|state| (common[state], states[state].first_end))
+        .filter(|(len, _)| *len > 0)
+        .min_by_key(|(len, end)| (std::cmp::Reverse(*len), end + 1 - len))?;
+    Some(end + 1 - len..end + 1)
 }
 
-#[allow(unused)]
-pub fn distinct_substrings_count(s: &str) -> usize {
-    let mut cnt = 0;
-    for i in (0..s.len()).rev() {
-        let pr = z_function(&s[i..s.len()]);
-        let res = s.len() - i - pr.iter().max_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
-        cnt += res;
+#[cfg(test)]
+#[test]
+fn suffix_automaton_test() {
+    let text = b"abcbcbab";
+    let automaton = SuffixAutomaton::build(text);
+    assert_eq!(automaton.len(), text.len());
+    assert!(automaton.contains(b"cbcb"));
+    assert!(!automaton.contains(b"ac"));
+    assert_eq!(automaton.occurrences(b"bc"), 2);
+    assert_eq!(automaton.occurrences(b"b"), 4);
+    assert_eq!(automaton.occurrences(b"abcbcbab"), 1);
+    assert_eq!(automaton.occurrences(b"ba"), 1);
+    assert_eq!(automaton.occurrences(b"x"), 0);
+    assert_eq!(automaton.first_occurrence(b"cb"), Some(2));
+    assert_eq!(automaton.first_occurrence(b"ab"), Some(0));
+    assert_eq!(automaton.first_occurrence(b"bab"), Some(5));
+    assert_eq!(automaton.first_occurrence(b"aa"), None);
+
+    let mut rng = crate::random::xorshift_32(31);
+    let s: Vec<u8> = (0..200)
+        .map(|_| b'a' + (rng.next().unwrap() % 3) as u8)
+        .collect();
+    let mut automaton = SuffixAutomaton::new();
+    for (idx, ch) in s.iter().enumerate() {
+        automaton.extend(*ch);
+        if idx % 50 == 49 {
+            let prefix = &s[..=idx];
+            let mut substrings: Vec<&[u8]> = (0..prefix.len())
+                .flat_map(|start| (start + 1..=prefix.len()).map(move |end| &prefix[start..end]))
+                .collect();
+            substrings.sort();
+            substrings.dedup();
+            assert_eq!(automaton.distinct_substrings_count(), substrings.len());
+            for k in (0..substrings.len()).step_by(97) {
+                assert_eq!(automaton.kth_substring(k).as_deref(), Some(substrings[k]));
+            }
+            assert_eq!(automaton.kth_substring(substrings.len()), None);
+            let pattern = &prefix[idx / 2..idx / 2 + 3];
+            let naive = prefix
+                .windows(3)
+                .filter(|window| *window == pattern)
+                .count();
+            assert_eq!(automaton.occurrences(pattern), naive);
+            let first = prefix.windows(3).position(|window| window == pattern);
+            assert_eq!(automaton.first_occurrence(pattern), first);
+        }
+    }
+    assert_eq!(
+        SuffixAutomaton::build(b"abacabadabacaba").distinct_substrings_count(),
+        85
+    );
+
+    let strings: [&[u8]; 3] = [b"xabcdefy", b"zzbcdefabc", b"bcdeqqabc"];
+    assert_eq!(longest_common_substring(&strings), Some(&b"bcde"[..]));
+    assert_eq!(longest_common_substring(&[b"abc", b"xyz"]), None);
+    assert_eq!(longest_common_substring(&[b"abc"]), Some(&b"abc"[..]));
+    assert_eq!(longest_common_substring(&[]), None);
+}
+
+// the symbols 0..256 are bytes, the string number i is terminated by the symbol 256 + i
+const SUFFIX_TREE_TERMINATOR: u32 = 256;
+
+struct SuffixTreeNode {
+    // the edge from the parent is text[start..=end], the leaves end at the end of the text
+    start: usize,
+    end: Option<usize>,
+    link: usize,
+    children: BTreeMap<u32, usize>,
+    // for the leaves - the position of the suffix in the text
+    suffix: Option<usize>,
+}
+
+/*
+* Suffix tree built with Ukkonen's algorithm in O(n log k). The generalized tree contains all suffixes of
+* several strings, every string ends with its own terminator, so every suffix ends in a leaf.
+* A substring is a path from the root, the leaves below the path are its occurrences.
+*/
+pub struct SuffixTree {
+    strings: Vec<Vec<u8>>,
+    // the start of every string in the text
+    offsets: Vec<usize>,
+    text: Vec<u32>,
+    nodes: Vec<SuffixTreeNode>,
+    // the length of the path from the root to the node
+    depths: Vec<usize>,
+}
+
+impl SuffixTree {
+    pub fn new(s: &[u8]) -> Self {
+        Self::generalized(&[s])
+    }
+
+    pub fn generalized(strings: &[&[u8]]) -> Self {
+        let mut text = vec![];
+        let mut offsets = vec![];
+        for (idx, s) in strings.iter().enumerate() {
+            offsets.push(text.len());
+            text.extend(s.iter().map(|ch| *ch as u32));
+            text.push(SUFFIX_TREE_TERMINATOR + idx as u32);
+        }
+        let mut tree = SuffixTree {
+            strings: strings.iter().map(|s| s.to_vec()).collect(),
+            offsets,
+            text,
+            nodes: vec![],
+            depths: vec![],
+        };
+        tree.make_node(0, Some(0));
+        tree.build();
+        tree.compute_depths();
+        tree
+    }
+
+    pub fn strings_len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        self.locate(pattern).is_some()
+    }
+
+    /*
+     * returns all occurrences of the pattern as pairs (number of the string, position), sorted
+     */
+    pub fn find_all(&self, pattern: &[u8]) -> Vec<(usize, usize)> {
+        let mut res: Vec<(usize, usize)> = match self.locate(pattern) {
+            Some(node) => self
+                .leaves(node)
+                .into_iter()
+                .map(|suffix| self.position(suffix))
+                .collect(),
+            None => vec![],
+        };
+        res.sort_unstable();
+        res
+    }
+
+    /*
+     * returns the longest substring occurring at least twice (in one or in different strings)
+     */
+    pub fn longest_repeated_substring(&self) -> Option<&[u8]> {
+        let node = (1..self.nodes.len())
+            .filter(|node| self.nodes[*node].suffix.is_none())
+            .max_by_key(|node| (self.depths[*node], std::cmp::Reverse(*node)))?;
+        Some(self.label(node))
+    }
+
+    /*
+     * returns the longest substring occurring in at least k of the strings
+     */
+    pub fn longest_common_substring(&self, k: usize) -> Option<&[u8]> {
+        assert!(k > 0);
+        let words = self.strings.len().div_ceil(64);
+        let mut sets = vec![vec![0u64; words]; self.nodes.len()];
+        let mut best: Option<usize> = None;
+        for node in self.post_order() {
+            if let Some(suffix) = self.nodes[node].suffix {
+                let (string, _) = self.position(suffix);
+                sets[node][string / 64] |= 1 << (string % 64);
+                continue;
+            }
+            let mut set = vec![0u64; words];
+            for child in self.nodes[node].children.values() {
+                for (value, other) in set.iter_mut().zip(sets[*child].iter()) {
+                    *value |= *other;
+                }
+            }
+            sets[node] = set;
+            let count: u32 = sets[node].iter().map(|word| word.count_ones()).sum();
+            if node != 0
+                && count as usize >= k
+                && best.is_none_or(|best| self.depths[node] > self.depths[best])
+            {
+                best = Some(node);
+            }
+        }
+        best.map(|node| self.label(node))
+    }
+
+    /*
+     * exports the tree in the Graphviz dot format, the terminators are shown as $0, $1, ...
+     */
+    pub fn to_dot(&self) -> String {
+        let mut res = String::from("digraph SuffixTree {\n");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            match node.suffix {
+                Some(suffix) => {
+                    let (string, pos) = self.position(suffix);
+                    res.push_str(&format!(
+                        "  {} [shape=box, label=\"{}:{}\"];\n",
+                        idx, string, pos
+                    ));
+                }
+                None => res.push_str(&format!("  {} [shape=point];\n", idx)),
+            }
+            for child in node.children.values() {
+                let mut label = String::new();
+                for symbol in &self.text[self.nodes[*child].start..=self.end(*child)] {
+                    if *symbol >= SUFFIX_TREE_TERMINATOR {
+                        label.push_str(&format!("${}", symbol - SUFFIX_TREE_TERMINATOR));
+                        break;
+                    }
+                    label.extend((*symbol as u8).escape_ascii().map(char::from));
+                }
+                res.push_str(&format!(
+                    "  {} -> {} [label=\"{}\"];\n",
+                    idx,
+                    child,
+                    label.replace('\\', "\\\\").replace('"', "\\\"")
+                ));
+            }
+        }
+        res.push_str("}\n");
+        res
+    }
+
+    fn build(&mut self) {
+        let (mut active_node, mut active_edge, mut active_len) = (0, 0, 0);
+        let mut remainder = 0;
+        for pos in 0..self.text.len() {
+            remainder += 1;
+            let mut last_new: Option<usize> = None;
+            while remainder > 0 {
+                if active_len == 0 {
+                    active_edge = pos;
+                }
+                let symbol = self.text[active_edge];
+                match self.nodes[active_node].children.get(&symbol).copied() {
+                    None => {
+                        let leaf = self.make_node(pos, None);
+                        self.nodes[active_node].children.insert(symbol, leaf);
+                        if let Some(node) = last_new.take() {
+                            self.nodes[node].link = active_node;
+                        }
+                    }
+                    Some(next) => {
+                        let edge_len =
+                            std::cmp::min(self.end(next), pos) + 1 - self.nodes[next].start;
+                        if active_len >= edge_len {
+                            // walk down
+                            active_edge += edge_len;
+                            active_len -= edge_len;
+                            active_node = next;
+                            continue;
+                        }
+                        if self.text[self.nodes[next].start + active_len] == self.text[pos] {
+                            // the suffix is already in the tree
+                            if active_node != 0 {
+                                if let Some(node) = last_new.take() {
+                                    self.nodes[node].link = active_node;
+                                }
+                            }
+                            active_len += 1;
+                            break;
+                        }
+                        let start = self.nodes[next].start;
+                        let split = self.make_node(start, Some(start + active_len - 1));
+                        self.nodes[active_node].children.insert(symbol, split);
+                        let leaf = self.make_node(pos, None);
+                        self.nodes[split].children.insert(self.text[pos], leaf);
+                        self.nodes[next].start += active_len;
+                        let next_symbol = self.text[self.nodes[next].start];
+                        self.nodes[split].children.insert(next_symbol, next);
+                        if let Some(node) = last_new {
+                            self.nodes[node].link = split;
+                        }
+                        last_new = Some(split);
+                    }
+                }
+                remainder -= 1;
+                if active_node == 0 && active_len > 0 {
+                    active_len -= 1;
+                    active_edge = pos + 1 - remainder;
+                } else if active_node != 0 {
+                    active_node = self.nodes[active_node].link;
+                }
+            }
+        }
+    }
+
+    fn compute_depths(&mut self) {
+        self.depths = vec![0; self.nodes.len()];
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            for child in self.nodes[node].children.values() {
+                self.depths[*child] =
+                    self.depths[node] + self.end(*child) + 1 - self.nodes[*child].start;
+                stack.push(*child);
+            }
+            if self.nodes[node].children.is_empty() && node != 0 {
+                self.nodes[node].suffix = Some(self.text.len() - self.depths[node]);
+            }
+        }
+    }
+
+    fn make_node(&mut self, start: usize, end: Option<usize>) -> usize {
+        self.nodes.push(SuffixTreeNode {
+            start,
+            end,
+            link: 0,
+            children: BTreeMap::new(),
+            suffix: None,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn end(&self, node: usize) -> usize {
+        self.nodes[node].end.unwrap_or(self.text.len() - 1)
+    }
+
+    // the number of the string and the position in it
+    fn position(&self, suffix: usize) -> (usize, usize) {
+        let string = self.offsets.partition_point(|offset| *offset <= suffix) - 1;
+        (string, suffix - self.offsets[string])
+    }
+
+    // the string of an internal node (it never contains a terminator)
+    fn label(&self, node: usize) -> &[u8] {
+        let mut leaf = node;
+        while let Some(child) = self.nodes[leaf].children.values().next() {
+            leaf = *child;
+        }
+        let (string, pos) = self.position(self.nodes[leaf].suffix.unwrap());
+        &self.strings[string][pos..pos + self.depths[node]]
+    }
+
+    // the node at or below the end of the pattern path
+    fn locate(&self, pattern: &[u8]) -> Option<usize> {
+        let mut node = 0;
+        let mut idx = 0;
+        while idx < pattern.len() {
+            node = *self.nodes[node].children.get(&(pattern[idx] as u32))?;
+            for pos in self.nodes[node].start..=self.end(node) {
+                if idx == pattern.len() {
+                    break;
+                }
+                if self.text[pos] != pattern[idx] as u32 {
+                    return None;
+                }
+                idx += 1;
+            }
+        }
+        Some(node)
+    }
+
+    fn leaves(&self, node: usize) -> Vec<usize> {
+        let mut res = vec![];
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            match self.nodes[node].suffix {
+                Some(suffix) => res.push(suffix),
+                None => stack.extend(self.nodes[node].children.values()),
+            }
+        }
+        res
+    }
+
+    fn post_order(&self) -> Vec<usize> {
+        let mut order = vec![];
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(self.nodes[node].children.values());
+        }
+        order.reverse();
+        order
     }
-    cnt
 }
 
 #[cfg(test)]
 #[test]
-fn test_distinct_substrings() {
-    assert_eq!(diferent_substrings_generator("a"), vec!["a"]);
+fn suffix_tree_test() {
+    let tree = SuffixTree::new(b"banana");
+    assert!(tree.contains(b"nan"));
+    assert!(!tree.contains(b"nab"));
+    assert_eq!(tree.find_all(b"ana"), vec![(0, 1), (0, 3)]);
+    assert_eq!(tree.find_all(b"a"), vec![(0, 1), (0, 3), (0, 5)]);
     assert_eq!(
-        diferent_substrings_generator("aaaa"),
-        vec!["a", "aa", "aaa", "aaaa"]
+        tree.find_all(b""),
+        (0..7).map(|pos| (0, pos)).collect::<Vec<_>>()
     );
-    assert_eq!(diferent_substrings_generator(""), Vec::<&str>::new());
-    let mut values = diferent_substrings_generator("abaaba");
-    values.sort();
+    assert_eq!(tree.longest_repeated_substring(), Some(&b"ana"[..]));
+    assert_eq!(SuffixTree::new(b"abc").longest_repeated_substring(), None);
+    let dot = tree.to_dot();
+    assert!(dot.starts_with("digraph SuffixTree {"));
+    assert!(dot.contains("[label=\"na\"]"));
+    assert!(dot.contains("[label=\"$0\"]"));
+
+    let strings: [&[u8]; 3] = [b"xabcdefy", b"zzbcdefabc", b"bcdeqqabc"];
+    let tree = SuffixTree::generalized(&strings);
+    assert_eq!(tree.strings_len(), 3);
+    assert_eq!(tree.longest_common_substring(3), Some(&b"bcde"[..]));
+    assert_eq!(tree.longest_common_substring(2), Some(&b"bcdef"[..]));
+    assert_eq!(tree.find_all(b"abc"), vec![(0, 1), (1, 7), (2, 6)]);
+    assert_eq!(tree.longest_repeated_substring(), Some(&b"bcdef"[..]));
     assert_eq!(
-        values,
-        vec![
-            "a", "aa", "aab", "aaba", "ab", "aba", "abaa", "abaab", "abaaba", "b", "ba", "baa",
-            "baab", "baaba"
-        ]
+        SuffixTree::generalized(&[b"abc", b"xyz"]).longest_common_substring(2),
+        None
+    );
+
+    // random strings compared with the naive search
+    let mut rng = crate::random::xorshift_32(41);
+    let strings: Vec<Vec<u8>> = (0..4)
+        .map(|_| {
+            (0..60)
+                .map(|_| b'a' + (rng.next().unwrap() % 3) as u8)
+                .collect()
+        })
+        .collect();
+    let refs: Vec<&[u8]> = strings.iter().map(|s| s.as_slice()).collect();
+    let tree = SuffixTree::generalized(&refs);
+    for len in 1..6 {
+        for start in (0..50).step_by(7) {
+            let pattern = &strings[1][start..start + len];
+            let mut expected = vec![];
+            for (string, s) in strings.iter().enumerate() {
+                for (pos, window) in s.windows(len).enumerate() {
+                    if window == pattern {
+                        expected.push((string, pos));
+                    }
+                }
+            }
+            assert_eq!(tree.find_all(pattern), expected);
+        }
+    }
+    let common = tree.longest_common_substring(4).unwrap();
+    assert_eq!(
+        Some(common.len()),
+        longest_common_substring(&refs).map(|s| s.len())
     );
-    assert_eq!(distinct_substrings_count("abacabadabacaba"), 85);
 }
 
 // Find minimum string period
 #[allow(unused)]
 pub fn msp(src: &str) -> &str {
-    for (idx, value) in z_function(src).iter().enumerate() {
-        if value + idx == src.len() && src.len() % idx == 0 {
-            return &src[..idx];
-        } else if value + idx == src.len() {
-            let k = src.len() % idx;
-            if src[..k] == src[src.len() - k..] {
-                return &src[..idx];
-            }
-        }
-    }
-    src
+    &src[..msp_of(src.as_bytes())]
+}
+
+/*
+* returns the length of the minimum period of the sequence:
+* the least p such that src[i] == src[i + p] for all i
+*/
+pub fn msp_of<T: Eq>(src: &[T]) -> usize {
+    z_function_of(src)
+        .iter()
+        .enumerate()
+        .skip(1)
+        .find(|(idx, value)| *idx + **value == src.len())
+        .map_or(src.len(), |(idx, _)| idx)
 }
 
 #[test]
@@ -460,66 +4578,198 @@ fn test_minimum_string_period() {
     assert_eq!(msp(""), "");
 }
 
-// Longest Common Substring
-#[allow(unused)]
-pub fn lcs<'a>(a: &'a str, b: &'a str) -> Option<&'a str> {
-    if a.is_empty() || b.is_empty() {
-        return None;
+/*
+* all border lengths (proper prefixes that are also suffixes) in decreasing order, from the prefix function
+*/
+pub fn borders_of<T: Eq>(src: &[T]) -> Vec<usize> {
+    let prefix = prefix_function_of(src);
+    let mut res = vec![];
+    let mut border = prefix.last().copied().unwrap_or(0);
+    while border > 0 {
+        res.push(border);
+        border = prefix[border - 1];
     }
-    let mut p: Vec<u64> = vec![1; std::cmp::max(a.len(), b.len())];
-    let mut h1: Vec<u64> = vec![0; a.len()];
-    let mut h2: Vec<u64> = vec![0; b.len()];
-    for idx in 1..p.len() {
-        p[idx] = p[idx - 1].wrapping_mul(31);
-    }
-    for (idx, ch) in a.as_bytes().iter().enumerate() {
-        h1[idx] = (*ch as u64).wrapping_mul(p[idx]);
-        if idx != 0 {
-            h1[idx] = h1[idx].wrapping_add(h1[idx - 1]);
-        }
-    }
-    for (idx, ch) in b.as_bytes().iter().enumerate() {
-        h2[idx] = (*ch as u64).wrapping_mul(p[idx]);
-        if idx != 0 {
-            h2[idx] = h2[idx].wrapping_add(h2[idx - 1]);
-        }
-    }
-    let mut res = None;
-    let mut l = 0;
-    let mut r = std::cmp::min(a.len(), b.len()) - 1;
-    while l < r {
-        let mid = r - (r - l) / 2;
-        let mut map = std::collections::HashMap::new();
-        for i in 0..a.len() - mid {
-            let mut hash = h1[i + mid];
-            if i != 0 {
-                hash = hash.wrapping_sub(h1[i - 1]);
-            }
-            hash = hash.wrapping_mul(p[p.len() - i - 1]);
-            map.insert(hash, i);
-        }
-        let mut f = false;
-        for i in 0..b.len() - mid {
-            let mut hash = h2[i + mid];
-            if i != 0 {
-                hash = hash.wrapping_sub(h2[i - 1]);
-            }
-            hash = hash.wrapping_mul(p[p.len() - i - 1]);
-            if let Some(idx) = map.get(&hash) {
-                if b[i..i + mid + 1] == a[*idx..*idx + mid + 1] {
-                    res = Some(&b[i..i + mid + 1]);
-                    f = true;
-                    break;
-                }
+    res
+}
+
+/*
+* all periods in increasing order, the length of the sequence included: p is a period iff len - p is a border
+*/
+pub fn periods_of<T: Eq>(src: &[T]) -> Vec<usize> {
+    let mut res: Vec<usize> = borders_of(src)
+        .into_iter()
+        .map(|border| src.len() - border)
+        .collect();
+    if !src.is_empty() {
+        res.push(src.len());
+    }
+    res
+}
+
+/*
+* Duval's algorithm in O(n): splits the sequence into non-increasing Lyndon words
+* (words strictly smaller than all their proper rotations)
+*/
+pub fn lyndon_factorization_of<T: Ord>(s: &[T]) -> Vec<std::ops::Range<usize>> {
+    let mut res = vec![];
+    let mut i = 0;
+    while i < s.len() {
+        let (mut j, mut k) = (i + 1, i);
+        while j < s.len() && s[k] <= s[j] {
+            k = if s[k] < s[j] { i } else { k + 1 };
+            j += 1;
+        }
+        while i <= k {
+            res.push(i..i + j - k);
+            i += j - k;
+        }
+    }
+    res
+}
+
+pub fn lyndon_factorization(s: &str) -> Vec<&str> {
+    let bounds: Vec<usize> = s
+        .char_indices()
+        .map(|(byte, _)| byte)
+        .chain([s.len()])
+        .collect();
+    let chars: Vec<char> = s.chars().collect();
+    lyndon_factorization_of(&chars)
+        .into_iter()
+        .map(|range| &s[bounds[range.start]..bounds[range.end]])
+        .collect()
+}
+
+pub fn is_lyndon_word_of<T: Ord>(s: &[T]) -> bool {
+    lyndon_factorization_of(s).len() == 1
+}
+
+/*
+* Booth's algorithm in O(n): the least start of the lexicographically minimal cyclic rotation
+*/
+pub fn minimal_rotation_of<T: Ord>(s: &[T]) -> usize {
+    let n = s.len();
+    if n == 0 {
+        return 0;
+    }
+    // failure function of the minimal rotation found so far, over the doubled sequence
+    let mut failure: Vec<Option<usize>> = vec![None; 2 * n];
+    let mut k = 0;
+    for j in 1..2 * n {
+        let symbol = &s[j % n];
+        let mut i = failure[j - k - 1];
+        let next = |i: Option<usize>, k: usize| &s[(k + i.map_or(0, |i| i + 1)) % n];
+        while let Some(prev) = i {
+            if symbol == next(i, k) {
+                break;
+            }
+            if symbol < next(i, k) {
+                k = j - prev - 1;
             }
+            i = failure[prev];
         }
-        if f {
-            l = mid;
+        if i.is_none() && symbol != next(None, k) {
+            if symbol < next(None, k) {
+                k = j;
+            }
+            failure[j - k] = None;
         } else {
-            r = mid - 1;
+            failure[j - k] = Some(i.map_or(0, |i| i + 1));
         }
     }
-    res
+    // equal rotations repeat with the length of the primitive root
+    let period = msp_of(s);
+    k % if n.is_multiple_of(period) { period } else { n }
+}
+
+/*
+* canonical form of a necklace (a sequence up to rotation): its minimal rotation
+*/
+pub fn necklace_canonical_of<T: Ord + Clone>(s: &[T]) -> Vec<T> {
+    let start = minimal_rotation_of(s);
+    s[start..]
+        .iter()
+        .chain(s[..start].iter())
+        .cloned()
+        .collect()
+}
+
+pub fn necklace_canonical(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    necklace_canonical_of(&chars).into_iter().collect()
+}
+
+#[cfg(test)]
+#[test]
+fn test_lyndon_and_rotations() {
+    assert_eq!(borders_of(b"abacaba"), [3, 1]);
+    assert_eq!(periods_of(b"abacaba"), [4, 6, 7]);
+    assert!(periods_of::<u8>(&[]).is_empty());
+    assert_eq!(lyndon_factorization("banana"), ["b", "an", "an", "a"]);
+    assert_eq!(lyndon_factorization("яблоко"), ["я", "блоко"]);
+    assert!(is_lyndon_word_of(b"aabab"));
+    assert!(!is_lyndon_word_of(b"abab"));
+    assert_eq!(minimal_rotation_of(b"bbaaccaadd"), 2);
+    assert_eq!(minimal_rotation_of(b"abab"), 0);
+    assert_eq!(necklace_canonical("cab"), "abc");
+    assert_eq!(necklace_canonical("ёжик"), necklace_canonical("кёжи"));
+
+    let mut rng = crate::random::xorshift_32(48);
+    for _ in 0..300 {
+        let len = (rng.next().unwrap() % 12) as usize;
+        let s: Vec<u8> = (0..len)
+            .map(|_| b"abc"[(rng.next().unwrap() % 3) as usize])
+            .collect();
+        let rotation =
+            |k: usize| -> Vec<u8> { s[k..].iter().chain(s[..k].iter()).copied().collect() };
+        if len > 0 {
+            let expected = (0..len).min_by_key(|k| rotation(*k)).unwrap();
+            assert_eq!(minimal_rotation_of(&s), expected);
+        }
+        let expected_periods: Vec<usize> = (1..=len)
+            .filter(|p| (0..len - p).all(|i| s[i] == s[i + p]))
+            .collect();
+        assert_eq!(periods_of(&s), expected_periods);
+
+        let brute_lyndon = |w: &[u8]| (1..w.len()).all(|k| w < &[&w[k..], &w[..k]].concat()[..]);
+        let factors = lyndon_factorization_of(&s);
+        assert_eq!(factors.iter().map(|range| range.len()).sum::<usize>(), len);
+        assert!(factors
+            .iter()
+            .all(|range| !range.is_empty() && brute_lyndon(&s[range.clone()])));
+        assert!(factors
+            .windows(2)
+            .all(|pair| s[pair[0].clone()] >= s[pair[1].clone()]));
+    }
+}
+
+// Longest Common Substring, compared by characters so the result never splits a character
+#[allow(unused)]
+pub fn lcs<'a>(a: &'a str, b: &'a str) -> Option<&'a str> {
+    lcs_chars(a, b)
+}
+
+/*
+* Longest common substring of sequences of any ordered elements with the suffix array
+* of a + separator + b, returns its range in a. O(n log n)
+*/
+pub fn lcs_of<T: Ord>(a: &[T], b: &[T]) -> Option<std::ops::Range<usize>> {
+    let mut values: Vec<&T> = a.iter().chain(b.iter()).collect();
+    values.sort();
+    values.dedup();
+    let rank = |value: &T| values.binary_search(&value).unwrap() + 1;
+    // 0 is the separator
+    let mut s: Vec<usize> = a.iter().map(rank).collect();
+    s.push(0);
+    s.extend(b.iter().map(rank));
+    let sa = suffix_array_int(&s, values.len());
+    let lcp = lcp_array(&s, &sa);
+    let (len, start) = (0..lcp.len())
+        .filter(|idx| (sa[*idx] < a.len()) != (sa[*idx + 1] < a.len()))
+        .map(|idx| (lcp[idx], std::cmp::min(sa[idx], sa[idx + 1])))
+        .filter(|(len, _)| *len > 0)
+        .min_by_key(|(len, start)| (std::cmp::Reverse(*len), *start))?;
+    Some(start..start + len)
 }
 
 #[cfg(test)]
@@ -536,8 +4786,204 @@ fn test_longest_common_substring() {
     assert_eq!(lcs("aaaaa", "bbaaa"), Some("aaa"));
     assert_eq!(lcs("", "bbaaa"), None);
     assert_eq!(lcs("abcde", "abcde"), Some("abcde"));
+    // the longest common byte run is not on character boundaries here
+    assert_eq!(lcs("аб", "Ѱб"), Some("б"));
     assert_eq!(
         lcs("aaaaaaaaaaaaaaaaaaaaaaaaab", "aaaaaaaaaaaaaaaaaaaaaaaaac"),
         Some("aaaaaaaaaaaaaaaaaaaaaaaaa")
     );
 }
+
+/*
+* Position in a string: the offset in bytes (for slicing) and in characters
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextPosition {
+    pub byte: usize,
+    pub char: usize,
+}
+
+// the characters of the string and the positions of their starts (and of the end of the string)
+fn split_chars(s: &str) -> (Vec<char>, Vec<TextPosition>) {
+    let mut positions: Vec<TextPosition> = s
+        .char_indices()
+        .enumerate()
+        .map(|(char, (byte, _))| TextPosition { byte, char })
+        .collect();
+    positions.push(TextPosition {
+        byte: s.len(),
+        char: positions.len(),
+    });
+    (s.chars().collect(), positions)
+}
+
+// the extended grapheme clusters (user-perceived characters) and the positions of their starts
+fn split_graphemes(s: &str) -> (Vec<&str>, Vec<TextPosition>) {
+    let mut units = vec![];
+    let mut positions = vec![];
+    let mut char = 0;
+    for (byte, grapheme) in s.grapheme_indices(true) {
+        units.push(grapheme);
+        positions.push(TextPosition { byte, char });
+        char += grapheme.chars().count();
+    }
+    positions.push(TextPosition {
+        byte: s.len(),
+        char,
+    });
+    (units, positions)
+}
+
+/*
+* prefix function over the characters of the string
+*/
+pub fn prefix_function_chars(src: &str) -> Vec<usize> {
+    prefix_function_of(&src.chars().collect::<Vec<char>>())
+}
+
+/*
+* z function over the characters of the string
+*/
+pub fn z_function_chars(src: &str) -> Vec<usize> {
+    z_function_of(&src.chars().collect::<Vec<char>>())
+}
+
+/*
+* occurrences of p in t compared by characters
+*/
+pub fn kmp_chars(t: &str, p: &str) -> Vec<TextPosition> {
+    let (text, positions) = split_chars(t);
+    let pattern: Vec<char> = p.chars().collect();
+    kmp_of(&text, &pattern)
+        .into_iter()
+        .map(|idx| positions[idx])
+        .collect()
+}
+
+/*
+* occurrences of p in t compared by grapheme clusters, so a match never starts or ends inside a cluster
+* (for example between a letter and its combining accent)
+*/
+pub fn kmp_graphemes(t: &str, p: &str) -> Vec<TextPosition> {
+    let (text, positions) = split_graphemes(t);
+    let (pattern, _) = split_graphemes(p);
+    kmp_of(&text, &pattern)
+        .into_iter()
+        .map(|idx| positions[idx])
+        .collect()
+}
+
+pub fn levenshtein_distance_chars(
+    first: &str,
+    second: &str,
+    delete_cost: u32,
+    insert_cost: u32,
+    replace_cost: u32,
+) -> u32 {
+    levenshtein_distance_of(
+        &first.chars().collect::<Vec<char>>(),
+        &second.chars().collect::<Vec<char>>(),
+        delete_cost,
+        insert_cost,
+        replace_cost,
+    )
+}
+
+pub fn levenshtein_distance_graphemes(
+    first: &str,
+    second: &str,
+    delete_cost: u32,
+    insert_cost: u32,
+    replace_cost: u32,
+) -> u32 {
+    levenshtein_distance_of(
+        &split_graphemes(first).0,
+        &split_graphemes(second).0,
+        delete_cost,
+        insert_cost,
+        replace_cost,
+    )
+}
+
+/*
+* minimum period of the string counted in characters
+*/
+pub fn msp_chars(src: &str) -> &str {
+    let (chars, positions) = split_chars(src);
+    &src[..positions[msp_of(&chars)].byte]
+}
+
+pub fn msp_graphemes(src: &str) -> &str {
+    let (graphemes, positions) = split_graphemes(src);
+    &src[..positions[msp_of(&graphemes)].byte]
+}
+
+/*
+* longest common substring compared by characters, the result is a slice of a
+*/
+pub fn lcs_chars<'a>(a: &'a str, b: &str) -> Option<&'a str> {
+    let (first, positions) = split_chars(a);
+    let second: Vec<char> = b.chars().collect();
+    let range = lcs_of(&first, &second)?;
+    Some(&a[positions[range.start].byte..positions[range.end].byte])
+}
+
+pub fn lcs_graphemes<'a>(a: &'a str, b: &str) -> Option<&'a str> {
+    let (first, positions) = split_graphemes(a);
+    let (second, _) = split_graphemes(b);
+    let range = lcs_of(&first, &second)?;
+    Some(&a[positions[range.start].byte..positions[range.end].byte])
+}
+
+#[cfg(test)]
+#[test]
+fn unicode_test() {
+    let text = "привет, мир! привет!";
+    assert_eq!(
+        kmp_chars(text, "привет"),
+        vec![
+            TextPosition { byte: 0, char: 0 },
+            TextPosition { byte: 22, char: 13 }
+        ]
+    );
+    assert_eq!(kmp(text, "привет"), vec![0, 22]);
+    assert_eq!(
+        kmp_chars("東京都と京都", "京都"),
+        vec![
+            TextPosition { byte: 3, char: 1 },
+            TextPosition { byte: 12, char: 4 }
+        ]
+    );
+    assert_eq!(prefix_function_chars("абаба"), [0, 0, 1, 2, 3]);
+    assert_eq!(z_function_chars("日本日本"), [0, 0, 2, 0]);
+
+    assert_eq!(levenshtein_distance("кот", "код", 1, 1, 1), 2);
+    assert_eq!(levenshtein_distance_chars("кот", "код", 1, 1, 1), 1);
+    // "é" written as "e" with a combining accent is one grapheme of two characters
+    assert_eq!(
+        levenshtein_distance_chars("cafe\u{301}", "cafe", 1, 1, 1),
+        1
+    );
+    assert_eq!(
+        levenshtein_distance_graphemes("cafe\u{301}", "cafx", 1, 1, 1),
+        1
+    );
+    assert_eq!(kmp_chars("cafe\u{301}", "cafe").len(), 1);
+    assert!(kmp_graphemes("cafe\u{301}", "cafe").is_empty());
+    assert_eq!(
+        kmp_graphemes("e\u{301}e\u{301}", "e\u{301}"),
+        vec![
+            TextPosition { byte: 0, char: 0 },
+            TextPosition { byte: 3, char: 2 }
+        ]
+    );
+
+    assert_eq!(msp_chars("абвабваб"), "абв");
+    assert_eq!(msp_chars("ёж"), "ёж");
+    assert_eq!(msp_graphemes("e\u{301}xe\u{301}x"), "e\u{301}x");
+    assert_eq!(lcs_chars("синхрофазотрон", "фазотрон-2"), Some("фазотрон"));
+    assert_eq!(lcs_chars("日本語", "中文"), None);
+    assert_eq!(lcs_graphemes("xe\u{301}y", "e\u{301}y"), Some("e\u{301}y"));
+    assert_eq!(lcs_of(b"xabcy", b"zabcz"), Some(1..4));
+    assert_eq!(msp_of(&[1, 2, 1, 2, 1]), 2);
+}
@@ -1,3 +1,11 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::{Bound, RangeBounds};
+
+use crate::random::{xorshift_32, XorShift32};
+
+pub mod persistent;
+
 /*
 * This data structure provides the following features. Initially, there are several elements, each of which is in a separate (its own) set.
 * In one operation, you can combine any two sets, and you can also query which set the specified element is currently in.
@@ -5,14 +13,16 @@
 */
 pub struct Dsu {
     parents: Vec<usize>,
-    ranks: Vec<usize>,
+    sizes: Vec<usize>,
+    count: usize,
 }
 
 impl Dsu {
     pub fn new(n: usize) -> Self {
         Dsu {
             parents: (0..n).collect(),
-            ranks: vec![1; n],
+            sizes: vec![1; n],
+            count: n,
         }
     }
 
@@ -20,34 +30,91 @@ impl Dsu {
      * returns which set contains the specified element
      */
     pub fn lookup(&mut self, key: usize) -> Option<usize> {
+        self.find(key)
+    }
+
+    /*
+     * returns the representative of the set containing the specified element.
+     * The search is iterative, so long chains do not overflow the stack
+     */
+    pub fn find(&mut self, key: usize) -> Option<usize> {
         if key >= self.parents.len() {
             return None;
         }
-        if key == self.parents[key] {
-            return Some(key);
+        let mut root = key;
+        while root != self.parents[root] {
+            root = self.parents[root];
+        }
+        let mut curr = key;
+        while curr != root {
+            let next = self.parents[curr];
+            self.parents[curr] = root;
+            curr = next;
         }
-        self.parents[key] = self.lookup(self.parents[key]).unwrap();
-        Some(self.parents[key])
+        Some(root)
     }
 
     /*
      * combines the two specified sets (the set containing the first element and the set containing the second element)
      */
     pub fn union(&mut self, first: usize, second: usize) -> bool {
-        if let (Some(first), Some(second)) = (self.lookup(first), self.lookup(second)) {
+        if let (Some(mut first), Some(mut second)) = (self.find(first), self.find(second)) {
             if first != second {
-                if self.ranks[first] < self.ranks[second] {
-                    self.ranks.swap(first, second);
+                if self.sizes[first] < self.sizes[second] {
+                    std::mem::swap(&mut first, &mut second);
                 }
                 self.parents[second] = first;
-                if self.ranks[first] == self.ranks[second] {
-                    self.ranks[first] += 1;
-                }
+                self.sizes[first] += self.sizes[second];
+                self.count -= 1;
             }
             return true;
         }
         false
     }
+
+    /*
+     * checks whether the two specified elements are in the same set
+     */
+    pub fn same(&mut self, first: usize, second: usize) -> bool {
+        match (self.find(first), self.find(second)) {
+            (Some(first), Some(second)) => first == second,
+            _ => false,
+        }
+    }
+
+    /*
+     * returns the number of elements in the set containing the specified element
+     */
+    pub fn size(&mut self, key: usize) -> Option<usize> {
+        self.find(key).map(|root| self.sizes[root])
+    }
+
+    /*
+     * returns the number of disjoint sets
+     */
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /*
+     * returns the members of every set, each group is sorted in ascending order
+     * and groups are ordered by their smallest element
+     */
+    pub fn groups(&mut self) -> Vec<Vec<usize>> {
+        let mut index: Vec<Option<usize>> = vec![None; self.parents.len()];
+        let mut groups: Vec<Vec<usize>> = Vec::with_capacity(self.count);
+        for key in 0..self.parents.len() {
+            let root = self.find(key).unwrap();
+            match index[root] {
+                Some(idx) => groups[idx].push(key),
+                None => {
+                    index[root] = Some(groups.len());
+                    groups.push(vec![key]);
+                }
+            }
+        }
+        groups
+    }
 }
 
 #[cfg(test)]
@@ -63,6 +130,166 @@ fn dsu_test() {
     assert_eq!(dsu.lookup(1).unwrap(), dsu.lookup(3).unwrap());
     assert_ne!(dsu.lookup(1).unwrap(), dsu.lookup(8).unwrap());
     assert_eq!(dsu.lookup(9).unwrap(), 9);
+
+    assert!(dsu.same(1, 7));
+    assert!(!dsu.same(1, 8));
+    assert!(!dsu.same(1, 10));
+    assert_eq!(dsu.size(3), Some(4));
+    assert_eq!(dsu.size(8), Some(1));
+    assert_eq!(dsu.size(10), None);
+    assert_eq!(dsu.count(), 7);
+    assert_eq!(
+        dsu.groups(),
+        vec![
+            vec![0],
+            vec![1, 2, 3, 7],
+            vec![4],
+            vec![5],
+            vec![6],
+            vec![8],
+            vec![9]
+        ]
+    );
+
+    let n = 1_000_000;
+    let mut dsu = Dsu::new(n);
+    for idx in 1..n {
+        dsu.parents[idx - 1] = idx;
+    }
+    assert_eq!(dsu.find(0), Some(n - 1));
+}
+
+/*
+* Dsu with potentials (weighted dsu). Besides the set, every element stores its offset relative to
+* the representative of its set, so the structure can answer "value[second] - value[first]"
+* for any two elements of the same set and detect contradicting relations.
+* The asymptotics of all operations are on average O(1)
+*/
+pub struct WeightedDsu<T> {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+    // potentials[x] = value[x] - value[parents[x]]
+    potentials: Vec<T>,
+    count: usize,
+}
+
+impl<T> WeightedDsu<T>
+where
+    T: Copy + Default + PartialEq + std::ops::Add<Output = T> + std::ops::Sub<Output = T>,
+{
+    pub fn new(n: usize) -> Self {
+        WeightedDsu {
+            parents: (0..n).collect(),
+            sizes: vec![1; n],
+            potentials: vec![T::default(); n],
+            count: n,
+        }
+    }
+
+    /*
+     * returns the representative of the set and the offset of the element relative to it
+     */
+    pub fn find(&mut self, key: usize) -> Option<(usize, T)> {
+        if key >= self.parents.len() {
+            return None;
+        }
+        let mut path = vec![];
+        let mut root = key;
+        while root != self.parents[root] {
+            path.push(root);
+            root = self.parents[root];
+        }
+        // walk from the element closest to the root, so every parent is already compressed
+        for &curr in path.iter().rev() {
+            let parent = self.parents[curr];
+            if parent != root {
+                self.potentials[curr] = self.potentials[curr] + self.potentials[parent];
+            }
+            self.parents[curr] = root;
+        }
+        Some((root, self.potentials[key]))
+    }
+
+    /*
+     * adds the relation value[second] - value[first] = diff.
+     * Returns false if one of the elements doesn't exist or the relation contradicts the known ones
+     */
+    pub fn union(&mut self, first: usize, second: usize, diff: T) -> bool {
+        if let (Some((mut first, first_pot)), Some((mut second, second_pot))) =
+            (self.find(first), self.find(second))
+        {
+            // value[second root] - value[first root]
+            let mut diff = diff + first_pot - second_pot;
+            if first == second {
+                return diff == T::default();
+            }
+            if self.sizes[first] < self.sizes[second] {
+                std::mem::swap(&mut first, &mut second);
+                diff = T::default() - diff;
+            }
+            self.parents[second] = first;
+            self.potentials[second] = diff;
+            self.sizes[first] += self.sizes[second];
+            self.count -= 1;
+            return true;
+        }
+        false
+    }
+
+    /*
+     * returns value[second] - value[first] if both elements are in the same set
+     */
+    pub fn diff(&mut self, first: usize, second: usize) -> Option<T> {
+        match (self.find(first), self.find(second)) {
+            (Some((first, first_pot)), Some((second, second_pot))) if first == second => {
+                Some(second_pot - first_pot)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn same(&mut self, first: usize, second: usize) -> bool {
+        match (self.find(first), self.find(second)) {
+            (Some((first, _)), Some((second, _))) => first == second,
+            _ => false,
+        }
+    }
+
+    pub fn size(&mut self, key: usize) -> Option<usize> {
+        self.find(key).map(|(root, _)| self.sizes[root])
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn weighted_dsu_test() {
+    let mut dsu = WeightedDsu::<i64>::new(6);
+
+    // x1 - x0 = 3, x2 - x1 = 4, x4 - x3 = -2
+    assert!(dsu.union(0, 1, 3));
+    assert!(dsu.union(1, 2, 4));
+    assert!(dsu.union(3, 4, -2));
+    assert_eq!(dsu.diff(0, 2), Some(7));
+    assert_eq!(dsu.diff(2, 0), Some(-7));
+    assert_eq!(dsu.diff(0, 3), None);
+    assert_eq!(dsu.count(), 3);
+
+    // x3 - x2 = 1 joins both chains
+    assert!(dsu.union(2, 3, 1));
+    assert_eq!(dsu.diff(0, 4), Some(6));
+    assert_eq!(dsu.size(4), Some(5));
+    assert!(dsu.same(0, 4));
+    assert!(!dsu.same(0, 5));
+
+    // consistent and contradicting relations inside one set
+    assert!(dsu.union(4, 0, -6));
+    assert!(!dsu.union(4, 0, 5));
+    assert!(!dsu.union(0, 6, 1));
+    assert_eq!(dsu.count(), 2);
 }
 
 /*
@@ -80,8 +307,18 @@ pub struct BloomFilter {
     data: Vec<u8>,
     hash_count: u8,
     false_positive_probability: f64,
+    // number of set bits
+    ones: usize,
+    seed: u64,
 }
 
+// version of the binary format produced by to_bytes of the bloom filters
+const BLOOM_FORMAT_VERSION: u8 = 2;
+const BLOOM_DEFAULT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+const BLOOM_FILTER_MAGIC: &[u8; 4] = b"BLMF";
+const COUNTING_BLOOM_FILTER_MAGIC: &[u8; 4] = b"CBLM";
+const SCALABLE_BLOOM_FILTER_MAGIC: &[u8; 4] = b"SBLM";
+
 impl BloomFilter {
     /*
      * n - storage size in bytes
@@ -92,23 +329,59 @@ impl BloomFilter {
         BloomFilter {
             data: vec![0; n],
             hash_count: k,
-            false_positive_probability: (1.0
-                - f64::exp(-1.0 * k as f64 * m as f64 / (8.0 * n as f64)))
-            .powf(k as f64),
+            false_positive_probability: (1.0 - f64::exp(-(k as f64) * m as f64 / (8.0 * n as f64)))
+                .powf(k as f64),
+            ones: 0,
+            seed: BLOOM_DEFAULT_SEED,
         }
     }
 
-    pub fn insert(&mut self, key: &str) {
-        for idx in 0..self.hash_count {
-            let hash = self.hash(&format!("{}{}", idx, key));
+    /*
+     * builds the filter of the optimal size for the expected number of items and the false positive probability:
+     * bits = -expected_items * ln(p) / ln(2)^2, number of hash functions = bits / expected_items * ln(2)
+     */
+    pub fn with_rate(expected_items: usize, false_positive_probability: f64) -> Self {
+        Self::with_rate_seeded(
+            expected_items,
+            false_positive_probability,
+            BLOOM_DEFAULT_SEED,
+        )
+    }
+
+    /*
+     * the same as with_rate, the seed selects the hash functions
+     */
+    pub fn with_rate_seeded(
+        expected_items: usize,
+        false_positive_probability: f64,
+        seed: u64,
+    ) -> Self {
+        assert!(false_positive_probability > 0.0 && false_positive_probability < 1.0);
+        let expected_items = std::cmp::max(1, expected_items);
+        let ln2 = std::f64::consts::LN_2;
+        let bits =
+            (-(expected_items as f64) * false_positive_probability.ln() / (ln2 * ln2)).ceil();
+        let hash_count = (bits / expected_items as f64 * ln2)
+            .round()
+            .clamp(1.0, 255.0) as u8;
+        let bytes = std::cmp::max(1, (bits / 8.0).ceil() as usize);
+        let mut filter = Self::build(bytes, expected_items, hash_count);
+        filter.seed = seed;
+        filter
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, key: &T) {
+        for hash in bloom_positions(key, self.seed, self.hash_count, 8 * self.data.len()) {
             let mask = 128 >> (hash % 8);
+            if self.data[hash / 8] & mask == 0 {
+                self.ones += 1;
+            }
             self.data[hash / 8] |= mask
         }
     }
 
-    pub fn contains(&self, key: &str) -> bool {
-        for idx in 0..self.hash_count {
-            let hash = self.hash(&format!("{}{}", idx, key));
+    pub fn contains<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        for hash in bloom_positions(key, self.seed, self.hash_count, 8 * self.data.len()) {
             let mask = 128 >> (hash % 8);
             if self.data[hash / 8] & mask != mask {
                 return false;
@@ -117,19 +390,283 @@ impl BloomFilter {
         true
     }
 
-    fn hash(&self, s: &str) -> usize {
-        let mut hash = 5381_usize;
-        for x in s.as_bytes() {
-            hash = hash
-                .wrapping_shl(5)
-                .wrapping_add(hash)
-                .wrapping_add(*x as usize);
+    /*
+     * the false positive probability expected for the number of items the filter was built for
+     */
+    pub fn false_positive_probability(&self) -> f64 {
+        self.false_positive_probability
+    }
+
+    /*
+     * the share of the set bits
+     */
+    pub fn fill_ratio(&self) -> f64 {
+        self.ones as f64 / (8 * self.data.len()) as f64
+    }
+
+    /*
+     * the false positive probability estimated from the current fill ratio: fill_ratio ^ k
+     */
+    pub fn estimated_false_positive_probability(&self) -> f64 {
+        self.fill_ratio().powi(self.hash_count as i32)
+    }
+
+    /*
+     * returns the filter containing the items of both filters.
+     * Filters are compatible if they have the same size and number of hash functions
+     */
+    pub fn union(&self, other: &BloomFilter) -> Option<BloomFilter> {
+        self.combine(other, |a, b| a | b)
+    }
+
+    /*
+     * returns the filter containing the items present in both filters
+     * (it may report more false positives than a filter built from the intersection directly)
+     */
+    pub fn intersection(&self, other: &BloomFilter) -> Option<BloomFilter> {
+        self.combine(other, |a, b| a & b)
+    }
+
+    /*
+     * serializes the filter: header (magic, format version), number of hash functions, seed,
+     * false positive probability, data length and data
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(30 + self.data.len());
+        bytes.extend_from_slice(BLOOM_FILTER_MAGIC);
+        bytes.push(BLOOM_FORMAT_VERSION);
+        bytes.push(self.hash_count);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.false_positive_probability.to_le_bytes());
+        bytes.extend_from_slice(&(self.data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /*
+     * restores the filter serialized by to_bytes, returns None if the bytes are malformed
+     * or were produced by an unsupported format version
+     */
+    pub fn from_bytes(bytes: &[u8]) -> Option<BloomFilter> {
+        let mut reader = ByteReader::new(bytes);
+        reader.header(BLOOM_FILTER_MAGIC, BLOOM_FORMAT_VERSION)?;
+        let hash_count = reader.read_u8()?;
+        let seed = reader.read_u64()?;
+        let false_positive_probability = reader.read_f64()?;
+        let len = reader.read_len()?;
+        let data = reader.take(len)?.to_vec();
+        if !reader.is_empty() || data.is_empty() {
+            return None;
         }
-        hash % (8 * self.data.len())
+        Some(BloomFilter {
+            ones: data.iter().map(|byte| byte.count_ones() as usize).sum(),
+            data,
+            hash_count,
+            false_positive_probability,
+            seed,
+        })
     }
 
-    pub fn false_positive_probability(&self) -> f64 {
-        self.false_positive_probability
+    fn combine<F>(&self, other: &BloomFilter, op: F) -> Option<BloomFilter>
+    where
+        F: Fn(u8, u8) -> u8,
+    {
+        if self.data.len() != other.data.len()
+            || self.hash_count != other.hash_count
+            || self.seed != other.seed
+        {
+            return None;
+        }
+        let data: Vec<u8> = self
+            .data
+            .iter()
+            .zip(other.data.iter())
+            .map(|(a, b)| op(*a, *b))
+            .collect();
+        Some(BloomFilter {
+            ones: data.iter().map(|byte| byte.count_ones() as usize).sum(),
+            data,
+            hash_count: self.hash_count,
+            false_positive_probability: f64::max(
+                self.false_positive_probability,
+                other.false_positive_probability,
+            ),
+            seed: self.seed,
+        })
+    }
+}
+
+/*
+* Positions of the key in a filter of modulo cells. Two independent 64-bit hashes h1, h2 are computed once
+* and the i-th hash function is h1 + i * h2 (Kirsch–Mitzenmacher double hashing)
+*/
+fn bloom_positions<T: Hash + ?Sized>(
+    key: &T,
+    seed: u64,
+    hash_count: u8,
+    modulo: usize,
+) -> impl Iterator<Item = usize> {
+    let first = seeded_hash(key, seed, 0);
+    // an odd step visits different cells for every i when modulo is a power of two
+    let second = seeded_hash(key, seed, 1) | 1;
+    (0..hash_count as u64)
+        .map(move |idx| (first.wrapping_add(idx.wrapping_mul(second)) % modulo as u64) as usize)
+}
+
+fn seeded_hash<T: Hash + ?Sized>(key: &T, k0: u64, k1: u64) -> u64 {
+    let mut hasher = SipHasher24::new_with_keys(k0, k1);
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/*
+* SipHash-2-4, a keyed 64-bit hash function. Unlike std's DefaultHasher its algorithm is fixed, but keys are
+* fed through std::hash::Hash, which writes integers in native endianness and usize at native width and is not
+* guaranteed to stay the same between toolchains. Serialized structures are only valid for the same target and
+* toolchain that produced them.
+*/
+pub struct SipHasher24 {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    tail: u64,
+    ntail: usize,
+    length: usize,
+}
+
+impl SipHasher24 {
+    pub fn new_with_keys(k0: u64, k1: u64) -> Self {
+        SipHasher24 {
+            v0: k0 ^ 0x736f_6d65_7073_6575,
+            v1: k1 ^ 0x646f_7261_6e64_6f6d,
+            v2: k0 ^ 0x6c79_6765_6e65_7261,
+            v3: k1 ^ 0x7465_6462_7974_6573,
+            tail: 0,
+            ntail: 0,
+            length: 0,
+        }
+    }
+
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    fn compress(&mut self, m: u64) {
+        let mut v = [self.v0, self.v1, self.v2, self.v3 ^ m];
+        Self::round(&mut v);
+        Self::round(&mut v);
+        self.v0 = v[0] ^ m;
+        self.v1 = v[1];
+        self.v2 = v[2];
+        self.v3 = v[3];
+    }
+}
+
+impl Hasher for SipHasher24 {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.tail |= (*byte as u64) << (8 * self.ntail);
+            self.ntail += 1;
+            if self.ntail == 8 {
+                self.compress(self.tail);
+                self.tail = 0;
+                self.ntail = 0;
+            }
+        }
+        self.length += bytes.len();
+    }
+
+    fn finish(&self) -> u64 {
+        let b = ((self.length as u64 & 0xff) << 56) | self.tail;
+        let mut v = [self.v0, self.v1, self.v2, self.v3 ^ b];
+        Self::round(&mut v);
+        Self::round(&mut v);
+        v[0] ^= b;
+        v[2] ^= 0xff;
+        for _ in 0..4 {
+            Self::round(&mut v);
+        }
+        v[0] ^ v[1] ^ v[2] ^ v[3]
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn sip_hasher_test() {
+    // reference vectors of SipHash-2-4 with the key 00 01 .. 0f and the messages 00 01 .. (len - 1)
+    let k0 = u64::from_le_bytes([0, 1, 2, 3, 4, 5, 6, 7]);
+    let k1 = u64::from_le_bytes([8, 9, 10, 11, 12, 13, 14, 15]);
+    let expected = [
+        (0, 0x726f_db47_dd0e_0e31),
+        (1, 0x74f8_39c5_93dc_67fd),
+        (8, 0x93f5_f579_9a93_2462),
+        (15, 0xa129_ca61_49be_45e5),
+    ];
+    for (len, hash) in expected {
+        let message: Vec<u8> = (0..len as u8).collect();
+        let mut hasher = SipHasher24::new_with_keys(k0, k1);
+        hasher.write(&message[..len / 2]);
+        hasher.write(&message[len / 2..]);
+        assert_eq!(hasher.finish(), hash);
+    }
+}
+
+/*
+* Reads the little-endian binary formats of the structures,
+* every read returns None if the input is too short
+*/
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        ByteReader { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let res = self.bytes.get(self.pos..end)?;
+        self.pos = end;
+        Some(res)
+    }
+
+    fn header(&mut self, magic: &[u8; 4], version: u8) -> Option<()> {
+        if self.take(4)? != magic || self.read_u8()? != version {
+            return None;
+        }
+        Some(())
+    }
+
+    fn read_u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn read_u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn read_f64(&mut self) -> Option<f64> {
+        Some(f64::from_bits(self.read_u64()?))
+    }
+
+    fn read_len(&mut self) -> Option<usize> {
+        usize::try_from(self.read_u64()?).ok()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
     }
 }
 
@@ -149,10 +686,4561 @@ fn bloom_filter_test() {
     bloom_filter.insert("facebook");
     bloom_filter.insert("yandex");
 
-    assert_eq!(bloom_filter.contains("google"), true);
-    assert_eq!(bloom_filter.contains("facebook"), true);
-    assert_eq!(bloom_filter.contains("yandex"), true);
-    assert_eq!(bloom_filter.contains("microsoft"), false);
-    assert_eq!(bloom_filter.contains("oracle"), false);
-    assert_eq!(bloom_filter.contains("redhat"), false);
+    assert!(bloom_filter.contains("google"));
+    assert!(bloom_filter.contains("facebook"));
+    assert!(bloom_filter.contains("yandex"));
+    assert!(!bloom_filter.contains("microsoft"));
+    assert!(!bloom_filter.contains("oracle"));
+    assert!(!bloom_filter.contains("redhat"));
+
+    let bytes = bloom_filter.to_bytes();
+    let restored = BloomFilter::from_bytes(&bytes).unwrap();
+    assert!(restored.contains("google"));
+    assert!(!restored.contains("oracle"));
+    assert_eq!(
+        restored.false_positive_probability(),
+        bloom_filter.false_positive_probability()
+    );
+    assert!(BloomFilter::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+    let mut wrong_version = bytes.clone();
+    wrong_version[4] = BLOOM_FORMAT_VERSION + 1;
+    assert!(BloomFilter::from_bytes(&wrong_version).is_none());
+
+    let mut other = BloomFilter::build(n, m, k);
+    other.insert("google");
+    other.insert("oracle");
+    let union = bloom_filter.union(&other).unwrap();
+    assert!(union.contains("yandex"));
+    assert!(union.contains("oracle"));
+    let intersection = bloom_filter.intersection(&other).unwrap();
+    assert!(intersection.contains("google"));
+    assert!(!intersection.contains("yandex"));
+    assert!(!intersection.contains("oracle"));
+    assert!(bloom_filter.union(&BloomFilter::build(n, m, 3)).is_none());
+    assert!(bloom_filter
+        .union(&BloomFilter::build(1024, m, k))
+        .is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn bloom_filter_with_rate_test() {
+    let mut filter = BloomFilter::with_rate(10000, 0.01);
+    assert_eq!(filter.hash_count, 7);
+    assert_eq!(filter.data.len(), 11982);
+    assert!((filter.false_positive_probability() - 0.01).abs() < 0.001);
+    assert_eq!(filter.estimated_false_positive_probability(), 0.0);
+    for idx in 0..10000u32 {
+        filter.insert(&idx);
+    }
+    assert!((0..10000u32).all(|idx| filter.contains(&idx)));
+    let false_positives = (10000..110000u32)
+        .filter(|idx| filter.contains(idx))
+        .count();
+    assert!(false_positives > 500 && false_positives < 1500);
+    assert!((filter.fill_ratio() - 0.5).abs() < 0.05);
+    assert!((filter.estimated_false_positive_probability() - 0.01).abs() < 0.003);
+
+    let mut urls = BloomFilter::with_rate_seeded(100, 0.001, 42);
+    urls.insert("https://example.com");
+    urls.insert(&String::from("https://example.org"));
+    urls.insert(&("example.net", 443));
+    assert!(urls.contains("https://example.org"));
+    assert!(urls.contains(&("example.net", 443)));
+    assert!(!urls.contains(&("example.net", 80)));
+    let restored = BloomFilter::from_bytes(&urls.to_bytes()).unwrap();
+    assert!(restored.contains("https://example.com"));
+    assert_eq!(restored.fill_ratio(), urls.fill_ratio());
+    assert!(urls.union(&BloomFilter::with_rate(100, 0.001)).is_none());
+}
+
+/*
+* Counting Bloom filter. Every bit of the Bloom filter is replaced by a small counter,
+* so items can be removed. A counter that reached its maximum value is never decremented
+* (it could be shared by more items than it can count).
+*/
+pub struct CountingBloomFilter {
+    counters: Vec<u8>,
+    hash_count: u8,
+    false_positive_probability: f64,
+    seed: u64,
+}
+
+impl CountingBloomFilter {
+    /*
+     * n - number of counters
+     * m - the number of expected items in the storage
+     * k - number of hash functions
+     */
+    pub fn build(n: usize, m: usize, k: u8) -> Self {
+        CountingBloomFilter {
+            counters: vec![0; n],
+            hash_count: k,
+            false_positive_probability: (1.0 - f64::exp(-(k as f64) * m as f64 / n as f64))
+                .powf(k as f64),
+            seed: BLOOM_DEFAULT_SEED,
+        }
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, key: &T) {
+        for hash in bloom_positions(key, self.seed, self.hash_count, self.counters.len()) {
+            self.counters[hash] = self.counters[hash].saturating_add(1);
+        }
+    }
+
+    pub fn contains<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        bloom_positions(key, self.seed, self.hash_count, self.counters.len())
+            .all(|hash| self.counters[hash] > 0)
+    }
+
+    /*
+     * removes an item inserted before. Returns false (and changes nothing) if the item is definitely absent.
+     * Removing an item that was never inserted may introduce false negatives
+     */
+    pub fn remove<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        if !self.contains(key) {
+            return false;
+        }
+        for hash in bloom_positions(key, self.seed, self.hash_count, self.counters.len()) {
+            if self.counters[hash] != u8::MAX {
+                self.counters[hash] -= 1;
+            }
+        }
+        true
+    }
+
+    /*
+     * the false positive probability expected for the number of items the filter was built for
+     */
+    pub fn false_positive_probability(&self) -> f64 {
+        self.false_positive_probability
+    }
+
+    /*
+     * the share of the non-zero counters
+     */
+    pub fn fill_ratio(&self) -> f64 {
+        let nonzero = self.counters.iter().filter(|counter| **counter > 0).count();
+        nonzero as f64 / self.counters.len() as f64
+    }
+
+    /*
+     * the false positive probability estimated from the current fill ratio: fill_ratio ^ k
+     */
+    pub fn estimated_false_positive_probability(&self) -> f64 {
+        self.fill_ratio().powi(self.hash_count as i32)
+    }
+
+    /*
+     * returns the filter counting the items of both filters
+     */
+    pub fn union(&self, other: &CountingBloomFilter) -> Option<CountingBloomFilter> {
+        self.combine(other, |a, b| a.saturating_add(b))
+    }
+
+    /*
+     * returns the filter containing the items present in both filters
+     */
+    pub fn intersection(&self, other: &CountingBloomFilter) -> Option<CountingBloomFilter> {
+        self.combine(other, std::cmp::min)
+    }
+
+    /*
+     * serializes the filter: header (magic, format version), number of hash functions, seed,
+     * false positive probability, number of counters and counters
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(30 + self.counters.len());
+        bytes.extend_from_slice(COUNTING_BLOOM_FILTER_MAGIC);
+        bytes.push(BLOOM_FORMAT_VERSION);
+        bytes.push(self.hash_count);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.false_positive_probability.to_le_bytes());
+        bytes.extend_from_slice(&(self.counters.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.counters);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<CountingBloomFilter> {
+        let mut reader = ByteReader::new(bytes);
+        reader.header(COUNTING_BLOOM_FILTER_MAGIC, BLOOM_FORMAT_VERSION)?;
+        let hash_count = reader.read_u8()?;
+        let seed = reader.read_u64()?;
+        let false_positive_probability = reader.read_f64()?;
+        let len = reader.read_len()?;
+        let counters = reader.take(len)?.to_vec();
+        if !reader.is_empty() || counters.is_empty() {
+            return None;
+        }
+        Some(CountingBloomFilter {
+            counters,
+            hash_count,
+            false_positive_probability,
+            seed,
+        })
+    }
+
+    fn combine<F>(&self, other: &CountingBloomFilter, op: F) -> Option<CountingBloomFilter>
+    where
+        F: Fn(u8, u8) -> u8,
+    {
+        if self.counters.len() != other.counters.len()
+            || self.hash_count != other.hash_count
+            || self.seed != other.seed
+        {
+            return None;
+        }
+        Some(CountingBloomFilter {
+            counters: self
+                .counters
+                .iter()
+                .zip(other.counters.iter())
+                .map(|(a, b)| op(*a, *b))
+                .collect(),
+            hash_count: self.hash_count,
+            false_positive_probability: f64::max(
+                self.false_positive_probability,
+                other.false_positive_probability,
+            ),
+            seed: self.seed,
+        })
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn counting_bloom_filter_test() {
+    let mut filter = CountingBloomFilter::build(1 << 16, 1000, 3);
+    filter.insert("google");
+    filter.insert("facebook");
+    filter.insert("google");
+    assert!(filter.contains("google"));
+    assert!(filter.contains("facebook"));
+    assert!(!filter.contains("yandex"));
+
+    assert!(filter.remove("facebook"));
+    assert!(!filter.contains("facebook"));
+    assert!(!filter.remove("facebook"));
+    assert!(filter.remove("google"));
+    assert!(filter.contains("google"));
+    assert!(filter.remove("google"));
+    assert!(!filter.contains("google"));
+
+    filter.insert("oracle");
+    let restored = CountingBloomFilter::from_bytes(&filter.to_bytes()).unwrap();
+    assert!(restored.contains("oracle"));
+    assert!(BloomFilter::from_bytes(&filter.to_bytes()).is_none());
+
+    let mut other = CountingBloomFilter::build(1 << 16, 1000, 3);
+    other.insert("redhat");
+    other.insert("oracle");
+    let mut union = filter.union(&other).unwrap();
+    assert!(union.contains("redhat"));
+    assert!(union.remove("oracle"));
+    assert!(union.contains("oracle"));
+    let intersection = filter.intersection(&other).unwrap();
+    assert!(intersection.contains("oracle"));
+    assert!(!intersection.contains("redhat"));
+
+    let mut filter = CountingBloomFilter::build(9586, 1000, 7);
+    assert!((filter.false_positive_probability() - 0.01).abs() < 0.001);
+    assert_eq!(filter.estimated_false_positive_probability(), 0.0);
+    for idx in 0..1000u32 {
+        filter.insert(&idx);
+    }
+    assert!((filter.fill_ratio() - 0.5).abs() < 0.05);
+    assert!((filter.estimated_false_positive_probability() - 0.01).abs() < 0.004);
+    for idx in 0..500u32 {
+        filter.remove(&idx);
+    }
+    assert!(filter.estimated_false_positive_probability() < 0.002);
+}
+
+/*
+* Scalable Bloom filter. It starts with one Bloom filter sized for initial_capacity items;
+* when the last filter is full a new one is added, twice as large and with a tighter false positive
+* probability, so the overall false positive probability stays below the requested one
+* however many items are inserted.
+*/
+pub struct ScalableBloomFilter {
+    layers: Vec<BloomFilter>,
+    capacities: Vec<usize>,
+    counts: Vec<usize>,
+    initial_capacity: usize,
+    false_positive_probability: f64,
+}
+
+// growth of the capacity and tightening of the false positive probability of every next layer
+const SCALABLE_BLOOM_GROWTH: usize = 2;
+const SCALABLE_BLOOM_TIGHTENING: f64 = 0.9;
+
+impl ScalableBloomFilter {
+    pub fn new(initial_capacity: usize, false_positive_probability: f64) -> Self {
+        assert!(initial_capacity > 0);
+        assert!(false_positive_probability > 0.0 && false_positive_probability < 1.0);
+        let mut filter = ScalableBloomFilter {
+            layers: vec![],
+            capacities: vec![],
+            counts: vec![],
+            initial_capacity,
+            false_positive_probability,
+        };
+        filter.add_layer();
+        filter
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, key: &T) {
+        if self.contains(key) {
+            return;
+        }
+        let last = self.layers.len() - 1;
+        if self.counts[last] >= self.capacities[last] {
+            self.add_layer();
+        }
+        let last = self.layers.len() - 1;
+        self.layers[last].insert(key);
+        self.counts[last] += 1;
+    }
+
+    pub fn contains<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        self.layers.iter().any(|layer| layer.contains(key))
+    }
+
+    /*
+     * returns the number of inserted items (items reported as present were not counted again)
+     */
+    pub fn len(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /*
+     * returns the false positive probability of the current layers when all of them are full
+     */
+    pub fn false_positive_probability(&self) -> f64 {
+        1.0 - self
+            .layers
+            .iter()
+            .map(|layer| 1.0 - layer.false_positive_probability())
+            .product::<f64>()
+    }
+
+    /*
+     * the false positive probability estimated from the current fill ratios of the layers
+     */
+    pub fn estimated_false_positive_probability(&self) -> f64 {
+        1.0 - self
+            .layers
+            .iter()
+            .map(|layer| 1.0 - layer.estimated_false_positive_probability())
+            .product::<f64>()
+    }
+
+    /*
+     * serializes the filter: header (magic, format version), initial capacity, requested false positive probability,
+     * number of layers and every layer (capacity, count, serialized Bloom filter)
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(SCALABLE_BLOOM_FILTER_MAGIC);
+        bytes.push(BLOOM_FORMAT_VERSION);
+        bytes.extend_from_slice(&(self.initial_capacity as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.false_positive_probability.to_le_bytes());
+        bytes.extend_from_slice(&(self.layers.len() as u64).to_le_bytes());
+        for idx in 0..self.layers.len() {
+            let layer = self.layers[idx].to_bytes();
+            bytes.extend_from_slice(&(self.capacities[idx] as u64).to_le_bytes());
+            bytes.extend_from_slice(&(self.counts[idx] as u64).to_le_bytes());
+            bytes.extend_from_slice(&(layer.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(&layer);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<ScalableBloomFilter> {
+        let mut reader = ByteReader::new(bytes);
+        reader.header(SCALABLE_BLOOM_FILTER_MAGIC, BLOOM_FORMAT_VERSION)?;
+        let mut filter = ScalableBloomFilter {
+            layers: vec![],
+            capacities: vec![],
+            counts: vec![],
+            initial_capacity: reader.read_len()?,
+            false_positive_probability: reader.read_f64()?,
+        };
+        let layers = reader.read_len()?;
+        for _ in 0..layers {
+            filter.capacities.push(reader.read_len()?);
+            filter.counts.push(reader.read_len()?);
+            let len = reader.read_len()?;
+            filter
+                .layers
+                .push(BloomFilter::from_bytes(reader.take(len)?)?);
+        }
+        if filter.layers.is_empty() || !reader.is_empty() {
+            return None;
+        }
+        // the values new asserts, and the capacity of the layer the next insert may add must fit
+        let probability = filter.false_positive_probability;
+        if filter.initial_capacity == 0
+            || !(probability > 0.0 && probability < 1.0)
+            || filter.next_capacity().is_none()
+        {
+            return None;
+        }
+        Some(filter)
+    }
+
+    fn next_capacity(&self) -> Option<usize> {
+        let growth = SCALABLE_BLOOM_GROWTH.checked_pow(u32::try_from(self.layers.len()).ok()?)?;
+        self.initial_capacity.checked_mul(growth)
+    }
+
+    fn add_layer(&mut self) {
+        let idx = self.layers.len() as i32;
+        let capacity = self
+            .next_capacity()
+            .expect("the capacity of the next layer overflows usize");
+        // the probabilities of the layers form a geometric series summing up to the requested one
+        let probability = self.false_positive_probability
+            * (1.0 - SCALABLE_BLOOM_TIGHTENING)
+            * SCALABLE_BLOOM_TIGHTENING.powi(idx);
+        self.layers
+            .push(BloomFilter::with_rate(capacity, probability));
+        self.capacities.push(capacity);
+        self.counts.push(0);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn scalable_bloom_filter_test() {
+    let mut filter = ScalableBloomFilter::new(100, 0.01);
+    assert!(filter.is_empty());
+    let keys: Vec<String> = (0..1000)
+        .map(|idx| format!("https://site/{}", idx))
+        .collect();
+    for key in keys.iter() {
+        filter.insert(key);
+    }
+    assert!(filter.layers() >= 4);
+    assert!(filter.len() <= 1000 && filter.len() > 990);
+    assert!(keys.iter().all(|key| filter.contains(key)));
+    assert!(filter.false_positive_probability() < 0.01);
+    assert!(filter.estimated_false_positive_probability() < filter.false_positive_probability());
+    let false_positives = (1000..11000)
+        .filter(|idx| filter.contains(&format!("https://site/{}", idx)))
+        .count();
+    assert!(false_positives < 100);
+
+    let restored = ScalableBloomFilter::from_bytes(&filter.to_bytes()).unwrap();
+    assert_eq!(restored.layers(), filter.layers());
+    assert_eq!(restored.len(), filter.len());
+    assert!(keys.iter().all(|key| restored.contains(key)));
+    assert!(ScalableBloomFilter::from_bytes(&[]).is_none());
+    // initial capacity, false positive probability and number of layers follow the 5 byte header
+    let bytes = filter.to_bytes();
+    let corrupt = |offset: usize, value: &[u8]| {
+        let mut bytes = bytes.clone();
+        bytes[offset..offset + value.len()].copy_from_slice(value);
+        ScalableBloomFilter::from_bytes(&bytes)
+    };
+    assert!(corrupt(5, &0u64.to_le_bytes()).is_none());
+    assert!(corrupt(5, &u64::MAX.to_le_bytes()).is_none());
+    for probability in [0.0, 1.0, -0.5, f64::NAN] {
+        assert!(corrupt(13, &probability.to_le_bytes()).is_none());
+    }
+    assert!(corrupt(13, &0.5f64.to_le_bytes()).is_some());
+}
+
+/*
+* Common interface of the approximate membership structures (filters). A filter never reports
+* an inserted item as absent, but may report an absent item as present with a small probability.
+* The trait is object safe, so the implementation can be chosen at runtime:
+* Box<dyn ApproximateMembership<str>>
+*/
+pub trait ApproximateMembership<T: ?Sized> {
+    /*
+     * inserts the item, returns false if the filter is full and the item wasn't inserted
+     */
+    fn insert(&mut self, key: &T) -> bool;
+    fn contains(&self, key: &T) -> bool;
+    /*
+     * the false positive probability estimated for the current state of the filter
+     */
+    fn false_positive_probability(&self) -> f64;
+}
+
+impl<T: Hash + ?Sized> ApproximateMembership<T> for BloomFilter {
+    fn insert(&mut self, key: &T) -> bool {
+        BloomFilter::insert(self, key);
+        true
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        BloomFilter::contains(self, key)
+    }
+
+    fn false_positive_probability(&self) -> f64 {
+        self.estimated_false_positive_probability()
+    }
+}
+
+impl<T: Hash + ?Sized> ApproximateMembership<T> for CountingBloomFilter {
+    fn insert(&mut self, key: &T) -> bool {
+        CountingBloomFilter::insert(self, key);
+        true
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        CountingBloomFilter::contains(self, key)
+    }
+
+    fn false_positive_probability(&self) -> f64 {
+        self.estimated_false_positive_probability()
+    }
+}
+
+impl<T: Hash + ?Sized> ApproximateMembership<T> for ScalableBloomFilter {
+    fn insert(&mut self, key: &T) -> bool {
+        ScalableBloomFilter::insert(self, key);
+        true
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        ScalableBloomFilter::contains(self, key)
+    }
+
+    fn false_positive_probability(&self) -> f64 {
+        self.estimated_false_positive_probability()
+    }
+}
+
+const CUCKOO_BUCKET_SIZE: usize = 4;
+const CUCKOO_MAX_KICKS: usize = 500;
+const CUCKOO_MAX_LOAD: f64 = 0.95;
+
+/*
+* Cuckoo filter. Stores 16-bit fingerprints in buckets of 4 slots, every item has two candidate buckets
+* (partial-key cuckoo hashing: the second bucket is computed from the first one and the fingerprint),
+* so items can be removed. With the same memory it has a lower false positive probability than
+* a Bloom filter when the probability is below about 3%.
+* Insert is O(1) amortized, lookup and removal are O(1).
+*/
+pub struct CuckooFilter {
+    buckets: Vec<[u16; CUCKOO_BUCKET_SIZE]>,
+    count: usize,
+    // an item evicted by the last failed insertion, it is kept so that nothing inserted is ever lost
+    victim: Option<(usize, u16)>,
+    seed: u64,
+    rng: XorShift32,
+}
+
+impl CuckooFilter {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_seeded(capacity, BLOOM_DEFAULT_SEED)
+    }
+
+    pub fn with_capacity_seeded(capacity: usize, seed: u64) -> Self {
+        let buckets =
+            (capacity as f64 / (CUCKOO_BUCKET_SIZE as f64 * CUCKOO_MAX_LOAD)).ceil() as usize;
+        CuckooFilter {
+            buckets: vec![[0; CUCKOO_BUCKET_SIZE]; std::cmp::max(1, buckets).next_power_of_two()],
+            count: 0,
+            victim: None,
+            seed,
+            rng: xorshift_32(seed as u32),
+        }
+    }
+
+    /*
+     * inserts the item. An item may be inserted several times (and then has to be removed as many times).
+     * Returns false if the filter is full
+     */
+    pub fn insert<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        if self.victim.is_some() {
+            return false;
+        }
+        let (mut fingerprint, first) = self.fingerprint_and_index(key);
+        let second = self.alt_index(first, fingerprint);
+        if self.put(first, fingerprint) || self.put(second, fingerprint) {
+            self.count += 1;
+            return true;
+        }
+        let mut idx = if self.rng.next().unwrap() & 1 == 0 {
+            first
+        } else {
+            second
+        };
+        for _ in 0..CUCKOO_MAX_KICKS {
+            let slot = self.rng.next().unwrap() as usize % CUCKOO_BUCKET_SIZE;
+            std::mem::swap(&mut fingerprint, &mut self.buckets[idx][slot]);
+            idx = self.alt_index(idx, fingerprint);
+            if self.put(idx, fingerprint) {
+                self.count += 1;
+                return true;
+            }
+        }
+        self.victim = Some((idx, fingerprint));
+        self.count += 1;
+        true
+    }
+
+    pub fn contains<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        let (fingerprint, first) = self.fingerprint_and_index(key);
+        let second = self.alt_index(first, fingerprint);
+        self.buckets[first].contains(&fingerprint)
+            || self.buckets[second].contains(&fingerprint)
+            || self
+                .victim
+                .is_some_and(|(idx, value)| value == fingerprint && (idx == first || idx == second))
+    }
+
+    /*
+     * removes one copy of the item, returns false if the item is absent.
+     * Removing an item that was never inserted may remove another item with the same fingerprint
+     */
+    pub fn remove<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        let (fingerprint, first) = self.fingerprint_and_index(key);
+        let second = self.alt_index(first, fingerprint);
+        if let Some((idx, value)) = self.victim {
+            if value == fingerprint && (idx == first || idx == second) {
+                self.victim = None;
+                self.count -= 1;
+                return true;
+            }
+        }
+        for idx in [first, second] {
+            if let Some(slot) = self.buckets[idx]
+                .iter()
+                .position(|value| *value == fingerprint)
+            {
+                self.buckets[idx][slot] = 0;
+                self.count -= 1;
+                // the freed slot may accommodate the victim
+                if let Some((idx, value)) = self.victim {
+                    let alt = self.alt_index(idx, value);
+                    if self.put(idx, value) || self.put(alt, value) {
+                        self.victim = None;
+                    }
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /*
+     * the share of the occupied slots
+     */
+    pub fn load_factor(&self) -> f64 {
+        self.count as f64 / (self.buckets.len() * CUCKOO_BUCKET_SIZE) as f64
+    }
+
+    /*
+     * a lookup compares the fingerprint with at most 2 * 4 occupied slots,
+     * each of them matches with the probability 1 / (2^16 - 1)
+     */
+    pub fn false_positive_probability(&self) -> f64 {
+        let probes = 2.0 * CUCKOO_BUCKET_SIZE as f64 * self.load_factor();
+        1.0 - (1.0 - 1.0 / u16::MAX as f64).powf(probes)
+    }
+
+    fn fingerprint_and_index<T: Hash + ?Sized>(&self, key: &T) -> (u16, usize) {
+        let hash = seeded_hash(key, self.seed, 0);
+        // 0 marks an empty slot
+        let fingerprint = std::cmp::max(1, (hash >> 48) as u16);
+        (fingerprint, hash as usize & (self.buckets.len() - 1))
+    }
+
+    fn alt_index(&self, idx: usize, fingerprint: u16) -> usize {
+        (idx ^ seeded_hash(&fingerprint, self.seed, 1) as usize) & (self.buckets.len() - 1)
+    }
+
+    fn put(&mut self, idx: usize, fingerprint: u16) -> bool {
+        match self.buckets[idx].iter().position(|value| *value == 0) {
+            Some(slot) => {
+                self.buckets[idx][slot] = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<T: Hash + ?Sized> ApproximateMembership<T> for CuckooFilter {
+    fn insert(&mut self, key: &T) -> bool {
+        CuckooFilter::insert(self, key)
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        CuckooFilter::contains(self, key)
+    }
+
+    fn false_positive_probability(&self) -> f64 {
+        CuckooFilter::false_positive_probability(self)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn cuckoo_filter_test() {
+    let mut filter = CuckooFilter::with_capacity(10000);
+    for idx in 0..10000u32 {
+        assert!(filter.insert(&idx));
+    }
+    assert_eq!(filter.len(), 10000);
+    assert!((0..10000u32).all(|idx| filter.contains(&idx)));
+    let false_positives = (10000..110000u32)
+        .filter(|idx| filter.contains(idx))
+        .count();
+    assert!(false_positives < 50);
+    assert!(filter.false_positive_probability() < 0.0002);
+
+    for idx in 0..5000u32 {
+        assert!(filter.remove(&idx));
+    }
+    assert_eq!(filter.len(), 5000);
+    assert!((5000..10000u32).all(|idx| filter.contains(&idx)));
+    assert!((0..5000u32).filter(|idx| filter.contains(idx)).count() < 10);
+
+    // fill a tiny filter up to the end: nothing inserted is lost
+    let mut filter = CuckooFilter::with_capacity(8);
+    let mut inserted = vec![];
+    for idx in 0..100u32 {
+        if !filter.insert(&idx) {
+            break;
+        }
+        inserted.push(idx);
+    }
+    assert!(inserted.len() >= 8 && inserted.len() < 100);
+    assert!(inserted.iter().all(|idx| filter.contains(idx)));
+    assert!(filter.remove(&inserted[0]));
+    assert!(filter.insert(&1000u32));
+}
+
+/*
+* Quotient filter. A p-bit fingerprint of the item is split into a quotient (q bits, the home slot)
+* and a remainder (r = p - q bits, stored in the slot). Remainders of one quotient form a sorted run,
+* neighbouring runs form clusters, and three flags per slot (occupied, continuation, shifted)
+* allow to restore the quotient of every stored remainder. Because of that, filters can be merged
+* and resized without access to the original items.
+* Operations take O(1) on average while the load factor is moderate.
+*/
+pub struct QuotientFilter {
+    // remainder << 3 | shifted << 2 | continuation << 1 | occupied
+    slots: Vec<u64>,
+    quotient_bits: u32,
+    remainder_bits: u32,
+    count: usize,
+    seed: u64,
+}
+
+const QF_OCCUPIED: u64 = 1;
+const QF_CONTINUATION: u64 = 2;
+const QF_SHIFTED: u64 = 4;
+const QF_FLAGS: u64 = 7;
+
+impl QuotientFilter {
+    /*
+     * quotient_bits - the filter has 2^quotient_bits slots
+     * remainder_bits - the number of stored fingerprint bits per item
+     */
+    pub fn new(quotient_bits: u32, remainder_bits: u32) -> Self {
+        Self::new_seeded(quotient_bits, remainder_bits, BLOOM_DEFAULT_SEED)
+    }
+
+    pub fn new_seeded(quotient_bits: u32, remainder_bits: u32, seed: u64) -> Self {
+        assert!(quotient_bits >= 1 && remainder_bits >= 1);
+        assert!(quotient_bits + remainder_bits <= 64 && remainder_bits <= 61);
+        QuotientFilter {
+            slots: vec![0; 1 << quotient_bits],
+            quotient_bits,
+            remainder_bits,
+            count: 0,
+            seed,
+        }
+    }
+
+    /*
+     * builds the filter for the expected number of items with the load factor of 3/4
+     * and the false positive probability about the requested one
+     */
+    pub fn with_rate(expected_items: usize, false_positive_probability: f64) -> Self {
+        assert!(false_positive_probability > 0.0 && false_positive_probability < 1.0);
+        let slots = std::cmp::max(2, (expected_items as f64 / 0.75).ceil() as usize);
+        let quotient_bits = slots.next_power_of_two().trailing_zeros();
+        let remainder_bits = (1.0 / false_positive_probability).log2().ceil() as u32;
+        Self::new(quotient_bits, std::cmp::max(1, remainder_bits))
+    }
+
+    /*
+     * inserts the item, returns false if the filter is full (resize it to insert more items)
+     */
+    pub fn insert<T: Hash + ?Sized>(&mut self, key: &T) -> bool {
+        let (quotient, remainder) = self.split(self.fingerprint(key));
+        self.insert_split(quotient, remainder)
+    }
+
+    pub fn contains<T: Hash + ?Sized>(&self, key: &T) -> bool {
+        let (quotient, remainder) = self.split(self.fingerprint(key));
+        if self.slots[quotient] & QF_OCCUPIED == 0 {
+            return false;
+        }
+        let mut pos = self.run_start(quotient);
+        loop {
+            let value = self.slots[pos] >> 3;
+            if value == remainder {
+                return true;
+            }
+            if value > remainder {
+                return false;
+            }
+            pos = self.next(pos);
+            if self.slots[pos] & QF_CONTINUATION == 0 {
+                return false;
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        // one slot is always kept empty, so the clusters can be found
+        self.slots.len() - 1
+    }
+
+    pub fn load_factor(&self) -> f64 {
+        self.count as f64 / self.slots.len() as f64
+    }
+
+    /*
+     * an absent item is reported present if its fingerprint coincides with one of the n stored ones:
+     * 1 - e^(-n / 2^(q + r))
+     */
+    pub fn false_positive_probability(&self) -> f64 {
+        let fingerprints = 2f64.powi((self.quotient_bits + self.remainder_bits) as i32);
+        1.0 - f64::exp(-(self.count as f64) / fingerprints)
+    }
+
+    /*
+     * doubles the number of slots: one bit of every remainder moves to the quotient,
+     * the false positive probability is not changed
+     */
+    pub fn resize(&mut self) -> bool {
+        if self.remainder_bits == 1 {
+            return false;
+        }
+        let mut resized =
+            QuotientFilter::new_seeded(self.quotient_bits + 1, self.remainder_bits - 1, self.seed);
+        for fingerprint in self.fingerprints() {
+            let (quotient, remainder) = resized.split(fingerprint);
+            resized.insert_split(quotient, remainder);
+        }
+        *self = resized;
+        true
+    }
+
+    /*
+     * returns the filter containing the items of both filters. Filters are compatible if they use the same seed
+     * and fingerprints of the same width; the result is large enough to keep the load factor below 3/4
+     */
+    pub fn merge(&self, other: &QuotientFilter) -> Option<QuotientFilter> {
+        let bits = self.quotient_bits + self.remainder_bits;
+        if bits != other.quotient_bits + other.remainder_bits || self.seed != other.seed {
+            return None;
+        }
+        let mut quotient_bits = std::cmp::max(self.quotient_bits, other.quotient_bits);
+        while ((self.count + other.count) as f64) > 0.75 * (1u64 << quotient_bits) as f64 {
+            quotient_bits += 1;
+        }
+        if quotient_bits >= bits {
+            return None;
+        }
+        let mut merged = QuotientFilter::new_seeded(quotient_bits, bits - quotient_bits, self.seed);
+        for fingerprint in self.fingerprints().into_iter().chain(other.fingerprints()) {
+            let (quotient, remainder) = merged.split(fingerprint);
+            merged.insert_split(quotient, remainder);
+        }
+        Some(merged)
+    }
+
+    fn fingerprint<T: Hash + ?Sized>(&self, key: &T) -> u64 {
+        let bits = self.quotient_bits + self.remainder_bits;
+        let hash = seeded_hash(key, self.seed, 0);
+        if bits == 64 {
+            hash
+        } else {
+            hash & ((1 << bits) - 1)
+        }
+    }
+
+    fn split(&self, fingerprint: u64) -> (usize, u64) {
+        (
+            (fingerprint >> self.remainder_bits) as usize,
+            fingerprint & ((1 << self.remainder_bits) - 1),
+        )
+    }
+
+    fn next(&self, pos: usize) -> usize {
+        (pos + 1) & (self.slots.len() - 1)
+    }
+
+    fn prev(&self, pos: usize) -> usize {
+        pos.wrapping_sub(1) & (self.slots.len() - 1)
+    }
+
+    fn is_empty_slot(&self, pos: usize) -> bool {
+        self.slots[pos] & QF_FLAGS == 0
+    }
+
+    /*
+     * returns the slot where the run of the quotient starts (or would start if the quotient has no run yet)
+     */
+    fn run_start(&self, quotient: usize) -> usize {
+        // go back to the beginning of the cluster
+        let mut bucket = quotient;
+        while self.slots[bucket] & QF_SHIFTED != 0 {
+            bucket = self.prev(bucket);
+        }
+        // walk forward: one run for every occupied quotient of the cluster
+        let mut pos = bucket;
+        while bucket != quotient {
+            loop {
+                pos = self.next(pos);
+                if self.slots[pos] & QF_CONTINUATION == 0 {
+                    break;
+                }
+            }
+            loop {
+                bucket = self.next(bucket);
+                if self.slots[bucket] & QF_OCCUPIED != 0 {
+                    break;
+                }
+            }
+        }
+        pos
+    }
+
+    fn insert_split(&mut self, quotient: usize, remainder: u64) -> bool {
+        if self.is_empty_slot(quotient) {
+            if self.count == self.capacity() {
+                return false;
+            }
+            self.slots[quotient] = remainder << 3 | QF_OCCUPIED;
+            self.count += 1;
+            return true;
+        }
+        let was_occupied = self.slots[quotient] & QF_OCCUPIED != 0;
+        self.slots[quotient] |= QF_OCCUPIED;
+        let start = self.run_start(quotient);
+        let mut pos = start;
+        if was_occupied {
+            // the run is sorted, find the position of the remainder in it
+            loop {
+                let value = self.slots[pos] >> 3;
+                if value == remainder {
+                    return true;
+                }
+                if value > remainder {
+                    break;
+                }
+                pos = self.next(pos);
+                if self.slots[pos] & QF_CONTINUATION == 0 {
+                    break;
+                }
+            }
+        }
+        if self.count == self.capacity() {
+            if !was_occupied {
+                self.slots[quotient] &= !QF_OCCUPIED;
+            }
+            return false;
+        }
+        let mut entry = remainder << 3;
+        if pos != start {
+            entry |= QF_CONTINUATION;
+        }
+        if pos != quotient {
+            entry |= QF_SHIFTED;
+        }
+        // the old head of the run becomes its continuation
+        let head_replaced = was_occupied && pos == start;
+        let mut first = true;
+        loop {
+            let was_empty = self.is_empty_slot(pos);
+            let occupied = self.slots[pos] & QF_OCCUPIED;
+            let displaced = self.slots[pos] & !QF_OCCUPIED;
+            self.slots[pos] = entry | occupied;
+            if was_empty {
+                break;
+            }
+            entry = displaced | QF_SHIFTED;
+            if first && head_replaced {
+                entry |= QF_CONTINUATION;
+            }
+            first = false;
+            pos = self.next(pos);
+        }
+        self.count += 1;
+        true
+    }
+
+    /*
+     * restores all stored fingerprints
+     */
+    fn fingerprints(&self) -> Vec<u64> {
+        let mut res = Vec::with_capacity(self.count);
+        let Some(empty) = (0..self.slots.len()).find(|pos| self.is_empty_slot(*pos)) else {
+            return res;
+        };
+        let mut quotients = std::collections::VecDeque::new();
+        let mut quotient = 0;
+        let mut pos = empty;
+        for _ in 0..self.slots.len() {
+            pos = self.next(pos);
+            if self.is_empty_slot(pos) {
+                quotients.clear();
+                continue;
+            }
+            if self.slots[pos] & QF_OCCUPIED != 0 {
+                quotients.push_back(pos);
+            }
+            if self.slots[pos] & QF_CONTINUATION == 0 {
+                quotient = quotients.pop_front().unwrap();
+            }
+            res.push((quotient as u64) << self.remainder_bits | self.slots[pos] >> 3);
+        }
+        res
+    }
+}
+
+impl<T: Hash + ?Sized> ApproximateMembership<T> for QuotientFilter {
+    fn insert(&mut self, key: &T) -> bool {
+        QuotientFilter::insert(self, key)
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        QuotientFilter::contains(self, key)
+    }
+
+    fn false_positive_probability(&self) -> f64 {
+        QuotientFilter::false_positive_probability(self)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn quotient_filter_test() {
+    // a small filter with long clusters and wrapping around the end
+    let mut filter = QuotientFilter::new(4, 12);
+    let mut fingerprints = vec![];
+    for idx in 0..15u32 {
+        assert!(filter.insert(&idx));
+        fingerprints.push(filter.fingerprint(&idx));
+    }
+    assert!(!filter.insert(&100u32));
+    assert_eq!(filter.len(), 15);
+    assert!((0..15u32).all(|idx| filter.contains(&idx)));
+    let mut stored = filter.fingerprints();
+    stored.sort();
+    fingerprints.sort();
+    fingerprints.dedup();
+    assert_eq!(stored, fingerprints);
+
+    let mut filter = QuotientFilter::with_rate(1000, 0.01);
+    for idx in 0..1000u32 {
+        assert!(filter.insert(&idx));
+    }
+    assert!((0..1000u32).all(|idx| filter.contains(&idx)));
+    let false_positives = (1000..101000u32).filter(|idx| filter.contains(idx)).count();
+    assert!(false_positives < 1000);
+
+    let len = filter.len();
+    assert!(len > 990);
+    assert!(filter.resize());
+    assert_eq!(filter.len(), len);
+    assert!(filter.load_factor() < 0.3);
+    assert!((0..1000u32).all(|idx| filter.contains(&idx)));
+
+    // filters with fingerprints of the same width are compatible whatever their sizes are
+    let mut other = QuotientFilter::new(11, 7);
+    for idx in 1000..3000u32 {
+        other.insert(&idx);
+    }
+    let merged = filter.merge(&other).unwrap();
+    assert!(filter.merge(&QuotientFilter::new(10, 4)).is_none());
+    assert!(merged.len() <= 3000 && merged.len() > 2950);
+    assert!(merged.load_factor() <= 0.75);
+    assert!((0..3000u32).all(|idx| merged.contains(&idx)));
+}
+
+#[cfg(test)]
+#[test]
+fn approximate_membership_test() {
+    let mut filters: Vec<Box<dyn ApproximateMembership<str>>> = vec![
+        Box::new(BloomFilter::with_rate(1000, 0.01)),
+        Box::new(CountingBloomFilter::build(10000, 1000, 7)),
+        Box::new(ScalableBloomFilter::new(100, 0.01)),
+        Box::new(CuckooFilter::with_capacity(1000)),
+        Box::new(QuotientFilter::with_rate(1000, 0.01)),
+    ];
+    for filter in filters.iter_mut() {
+        // the probability follows the current state of the filter
+        assert_eq!(filter.false_positive_probability(), 0.0);
+        for idx in 0..1000 {
+            assert!(filter.insert(&format!("https://site/{}", idx)));
+        }
+        assert!((0..1000).all(|idx| filter.contains(&format!("https://site/{}", idx))));
+        let false_positives = (1000..11000)
+            .filter(|idx| filter.contains(&format!("https://site/{}", idx)))
+            .count();
+        assert!(false_positives < 300);
+        assert!(filter.false_positive_probability() < 0.03);
+    }
+}
+
+// version of the binary format produced by to_bytes of the sketches
+const SKETCH_FORMAT_VERSION: u8 = 1;
+const HYPER_LOG_LOG_MAGIC: &[u8; 4] = b"HLLG";
+const COUNT_MIN_SKETCH_MAGIC: &[u8; 4] = b"CMSK";
+const COUNT_SKETCH_MAGIC: &[u8; 4] = b"CNTS";
+const MIN_HASH_MAGIC: &[u8; 4] = b"MNHS";
+
+enum HyperLogLogRegisters {
+    // only the non-zero registers, used while they take less memory than the dense array
+    Sparse(BTreeMap<u32, u8>),
+    Dense(Vec<u8>),
+}
+
+/*
+* HyperLogLog estimates the number of distinct items using 2^precision small registers.
+* Every register keeps the maximum rank (position of the first set bit) of the hashes that fall into it,
+* the relative error is about 1.04 / sqrt(2^precision).
+* While few registers are set they are kept in a sparse map. Sketches built with the same
+* precision and seed can be merged, the result is the sketch of the union of the streams.
+*/
+pub struct HyperLogLog {
+    precision: u8,
+    seed: u64,
+    registers: HyperLogLogRegisters,
+}
+
+impl HyperLogLog {
+    /*
+     * precision - from 4 to 16, the sketch has 2^precision registers
+     */
+    pub fn new(precision: u8) -> Self {
+        Self::new_seeded(precision, BLOOM_DEFAULT_SEED)
+    }
+
+    pub fn new_seeded(precision: u8, seed: u64) -> Self {
+        assert!((4..=16).contains(&precision));
+        HyperLogLog {
+            precision,
+            seed,
+            registers: HyperLogLogRegisters::Sparse(BTreeMap::new()),
+        }
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, key: &T) {
+        let hash = seeded_hash(key, self.seed, 0);
+        let idx = (hash >> (64 - self.precision)) as u32;
+        let rest = hash << self.precision;
+        let max_rank = 64 - self.precision + 1;
+        let rank = std::cmp::min(rest.leading_zeros() as u8 + 1, max_rank);
+        self.update(idx, rank);
+    }
+
+    /*
+     * returns the estimated number of distinct inserted items
+     */
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers_count() as f64;
+        let alpha = match self.registers_count() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let (sum, zeros) = match &self.registers {
+            HyperLogLogRegisters::Sparse(map) => (
+                map.values()
+                    .map(|rank| 2f64.powi(-(*rank as i32)))
+                    .sum::<f64>()
+                    + (self.registers_count() - map.len()) as f64,
+                self.registers_count() - map.len(),
+            ),
+            HyperLogLogRegisters::Dense(registers) => (
+                registers
+                    .iter()
+                    .map(|rank| 2f64.powi(-(*rank as i32)))
+                    .sum(),
+                registers.iter().filter(|rank| **rank == 0).count(),
+            ),
+        };
+        let raw = alpha * m * m / sum;
+        if raw <= 2.5 * m && zeros > 0 {
+            // linear counting is more precise for small cardinalities
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+
+    /*
+     * adds the items of the other sketch, returns false if the sketches are not compatible
+     */
+    pub fn merge(&mut self, other: &HyperLogLog) -> bool {
+        if self.precision != other.precision || self.seed != other.seed {
+            return false;
+        }
+        match &other.registers {
+            HyperLogLogRegisters::Sparse(map) => {
+                for (idx, rank) in map.iter() {
+                    self.update(*idx, *rank);
+                }
+            }
+            HyperLogLogRegisters::Dense(registers) => {
+                self.make_dense();
+                if let HyperLogLogRegisters::Dense(own) = &mut self.registers {
+                    for (own, rank) in own.iter_mut().zip(registers.iter()) {
+                        *own = std::cmp::max(*own, *rank);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.registers, HyperLogLogRegisters::Sparse(_))
+    }
+
+    /*
+     * serializes the sketch: header (magic, format version), precision, seed, representation
+     * (0 - sparse: number of registers and (index u32, rank u8) pairs, 1 - dense: all registers)
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(HYPER_LOG_LOG_MAGIC);
+        bytes.push(SKETCH_FORMAT_VERSION);
+        bytes.push(self.precision);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        match &self.registers {
+            HyperLogLogRegisters::Sparse(map) => {
+                bytes.push(0);
+                bytes.extend_from_slice(&(map.len() as u64).to_le_bytes());
+                for (idx, rank) in map.iter() {
+                    bytes.extend_from_slice(&idx.to_le_bytes());
+                    bytes.push(*rank);
+                }
+            }
+            HyperLogLogRegisters::Dense(registers) => {
+                bytes.push(1);
+                bytes.extend_from_slice(registers);
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<HyperLogLog> {
+        let mut reader = ByteReader::new(bytes);
+        reader.header(HYPER_LOG_LOG_MAGIC, SKETCH_FORMAT_VERSION)?;
+        let precision = reader.read_u8()?;
+        if !(4..=16).contains(&precision) {
+            return None;
+        }
+        let seed = reader.read_u64()?;
+        let registers_count = 1usize << precision;
+        let registers = match reader.read_u8()? {
+            0 => {
+                let len = reader.read_len()?;
+                let mut map = BTreeMap::new();
+                for _ in 0..len {
+                    let idx = u32::from_le_bytes(reader.take(4)?.try_into().ok()?);
+                    if idx as usize >= registers_count {
+                        return None;
+                    }
+                    map.insert(idx, reader.read_u8()?);
+                }
+                HyperLogLogRegisters::Sparse(map)
+            }
+            1 => HyperLogLogRegisters::Dense(reader.take(registers_count)?.to_vec()),
+            _ => return None,
+        };
+        if !reader.is_empty() {
+            return None;
+        }
+        Some(HyperLogLog {
+            precision,
+            seed,
+            registers,
+        })
+    }
+
+    fn registers_count(&self) -> usize {
+        1 << self.precision
+    }
+
+    fn update(&mut self, idx: u32, rank: u8) {
+        match &mut self.registers {
+            HyperLogLogRegisters::Sparse(map) => {
+                let value = map.entry(idx).or_insert(0);
+                *value = std::cmp::max(*value, rank);
+                // a sparse entry takes about 5 bytes, a dense register takes 1 byte
+                if map.len() > self.registers_count() / 5 {
+                    self.make_dense();
+                }
+            }
+            HyperLogLogRegisters::Dense(registers) => {
+                let value = &mut registers[idx as usize];
+                *value = std::cmp::max(*value, rank);
+            }
+        }
+    }
+
+    fn make_dense(&mut self) {
+        if let HyperLogLogRegisters::Sparse(map) = &self.registers {
+            let mut registers = vec![0; self.registers_count()];
+            for (idx, rank) in map.iter() {
+                registers[*idx as usize] = *rank;
+            }
+            self.registers = HyperLogLogRegisters::Dense(registers);
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn hyper_log_log_test() {
+    let mut sketch = HyperLogLog::new(14);
+    for idx in 0..100u32 {
+        sketch.insert(&idx);
+        sketch.insert(&idx);
+    }
+    assert!(sketch.is_sparse());
+    assert!((sketch.estimate() - 100.0).abs() < 2.0);
+    let restored = HyperLogLog::from_bytes(&sketch.to_bytes()).unwrap();
+    assert!(restored.is_sparse());
+    assert_eq!(restored.estimate(), sketch.estimate());
+
+    for idx in 100..100000u32 {
+        sketch.insert(&idx);
+    }
+    assert!(!sketch.is_sparse());
+    assert!((sketch.estimate() - 100000.0).abs() < 2000.0);
+    let restored = HyperLogLog::from_bytes(&sketch.to_bytes()).unwrap();
+    assert_eq!(restored.estimate(), sketch.estimate());
+    assert!(HyperLogLog::from_bytes(&sketch.to_bytes()[1..]).is_none());
+
+    let mut other = HyperLogLog::new(14);
+    for idx in 50000..150000u32 {
+        other.insert(&idx);
+    }
+    let mut small = HyperLogLog::new(14);
+    small.insert("one");
+    small.insert("two");
+    assert!(sketch.merge(&other));
+    assert!(sketch.merge(&small));
+    assert!((sketch.estimate() - 150002.0).abs() < 3000.0);
+    assert!(!sketch.merge(&HyperLogLog::new(12)));
+    assert!(small.merge(&other));
+    assert!((small.estimate() - 100002.0).abs() < 2000.0);
+}
+
+/*
+* Common interface of the frequency sketches: they count the occurrences of items in a stream
+* approximately, using memory that doesn't depend on the number of distinct items.
+*/
+pub trait FrequencySketch {
+    fn add<T: Hash + ?Sized>(&mut self, key: &T, count: u64);
+    fn estimate<T: Hash + ?Sized>(&self, key: &T) -> u64;
+}
+
+/*
+* Count-Min sketch. depth rows of width counters, every row has its own hash function.
+* The estimate is the minimum of the item's counters: it is never less than the true count and
+* exceeds it by more than epsilon * total with the probability delta,
+* where width = e / epsilon and depth = ln(1 / delta).
+*/
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<u64>,
+    total: u64,
+    seed: u64,
+}
+
+impl CountMinSketch {
+    pub fn new(width: usize, depth: usize) -> Self {
+        Self::new_seeded(width, depth, BLOOM_DEFAULT_SEED)
+    }
+
+    pub fn new_seeded(width: usize, depth: usize, seed: u64) -> Self {
+        assert!(width > 0 && depth > 0);
+        CountMinSketch {
+            width,
+            depth,
+            counters: vec![0; width * depth],
+            total: 0,
+            seed,
+        }
+    }
+
+    /*
+     * builds the sketch with the error at most epsilon * total with the probability 1 - delta
+     */
+    pub fn with_error(epsilon: f64, delta: f64) -> Self {
+        assert!(epsilon > 0.0 && delta > 0.0 && delta < 1.0);
+        let width = (std::f64::consts::E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil() as usize;
+        Self::new(width, std::cmp::max(1, depth))
+    }
+
+    /*
+     * the sum of all added counts
+     */
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /*
+     * adds the counts of the other sketch, returns false if the sketches are not compatible
+     */
+    pub fn merge(&mut self, other: &CountMinSketch) -> bool {
+        if (self.width, self.depth, self.seed) != (other.width, other.depth, other.seed) {
+            return false;
+        }
+        for (own, value) in self.counters.iter_mut().zip(other.counters.iter()) {
+            *own = own.saturating_add(*value);
+        }
+        self.total = self.total.saturating_add(other.total);
+        true
+    }
+
+    /*
+     * serializes the sketch: header (magic, format version), width, depth, seed, total and counters
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(37 + 8 * self.counters.len());
+        bytes.extend_from_slice(COUNT_MIN_SKETCH_MAGIC);
+        bytes.push(SKETCH_FORMAT_VERSION);
+        for value in [self.width as u64, self.depth as u64, self.seed, self.total] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for counter in self.counters.iter() {
+            bytes.extend_from_slice(&counter.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<CountMinSketch> {
+        let mut reader = ByteReader::new(bytes);
+        reader.header(COUNT_MIN_SKETCH_MAGIC, SKETCH_FORMAT_VERSION)?;
+        let width = reader.read_len()?;
+        let depth = reader.read_len()?;
+        let seed = reader.read_u64()?;
+        let total = reader.read_u64()?;
+        let len = width.checked_mul(depth)?;
+        if len == 0 {
+            return None;
+        }
+        let counters = (0..len)
+            .map(|_| reader.read_u64())
+            .collect::<Option<Vec<u64>>>()?;
+        if !reader.is_empty() {
+            return None;
+        }
+        Some(CountMinSketch {
+            width,
+            depth,
+            counters,
+            total,
+            seed,
+        })
+    }
+
+    fn cell<T: Hash + ?Sized>(&self, key: &T, row: usize) -> usize {
+        row * self.width + (seeded_hash(key, self.seed, row as u64) % self.width as u64) as usize
+    }
+}
+
+impl FrequencySketch for CountMinSketch {
+    fn add<T: Hash + ?Sized>(&mut self, key: &T, count: u64) {
+        for row in 0..self.depth {
+            let cell = self.cell(key, row);
+            self.counters[cell] = self.counters[cell].saturating_add(count);
+        }
+        self.total = self.total.saturating_add(count);
+    }
+
+    fn estimate<T: Hash + ?Sized>(&self, key: &T) -> u64 {
+        (0..self.depth)
+            .map(|row| self.counters[self.cell(key, row)])
+            .min()
+            .unwrap()
+    }
+}
+
+/*
+* Count-Sketch. Like Count-Min, but every row also has a random sign for each item and
+* the estimate is the median of the signed counters. The estimate is unbiased,
+* its error depends on the second moment of the stream instead of the total count.
+*/
+pub struct CountSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<i64>,
+    seed: u64,
+}
+
+impl CountSketch {
+    pub fn new(width: usize, depth: usize) -> Self {
+        Self::new_seeded(width, depth, BLOOM_DEFAULT_SEED)
+    }
+
+    pub fn new_seeded(width: usize, depth: usize, seed: u64) -> Self {
+        assert!(width > 0 && depth > 0);
+        CountSketch {
+            width,
+            depth,
+            counters: vec![0; width * depth],
+            seed,
+        }
+    }
+
+    /*
+     * returns the signed estimate, it may be negative for rare items
+     */
+    pub fn estimate_signed<T: Hash + ?Sized>(&self, key: &T) -> i64 {
+        let mut values: Vec<i64> = (0..self.depth)
+            .map(|row| {
+                let (cell, sign) = self.cell(key, row);
+                self.counters[cell].saturating_mul(sign)
+            })
+            .collect();
+        values.sort_unstable();
+        let mid = values.len() / 2;
+        if values.len() % 2 == 1 {
+            values[mid]
+        } else {
+            // widened, the counters may be saturated
+            ((values[mid - 1] as i128 + values[mid] as i128) / 2) as i64
+        }
+    }
+
+    pub fn merge(&mut self, other: &CountSketch) -> bool {
+        if (self.width, self.depth, self.seed) != (other.width, other.depth, other.seed) {
+            return false;
+        }
+        for (own, value) in self.counters.iter_mut().zip(other.counters.iter()) {
+            *own = own.saturating_add(*value);
+        }
+        true
+    }
+
+    /*
+     * serializes the sketch: header (magic, format version), width, depth, seed and counters
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(29 + 8 * self.counters.len());
+        bytes.extend_from_slice(COUNT_SKETCH_MAGIC);
+        bytes.push(SKETCH_FORMAT_VERSION);
+        for value in [self.width as u64, self.depth as u64, self.seed] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for counter in self.counters.iter() {
+            bytes.extend_from_slice(&counter.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<CountSketch> {
+        let mut reader = ByteReader::new(bytes);
+        reader.header(COUNT_SKETCH_MAGIC, SKETCH_FORMAT_VERSION)?;
+        let width = reader.read_len()?;
+        let depth = reader.read_len()?;
+        let seed = reader.read_u64()?;
+        let len = width.checked_mul(depth)?;
+        if len == 0 {
+            return None;
+        }
+        let counters = (0..len)
+            .map(|_| reader.read_u64().map(|value| value as i64))
+            .collect::<Option<Vec<i64>>>()?;
+        if !reader.is_empty() {
+            return None;
+        }
+        Some(CountSketch {
+            width,
+            depth,
+            counters,
+            seed,
+        })
+    }
+
+    fn cell<T: Hash + ?Sized>(&self, key: &T, row: usize) -> (usize, i64) {
+        let hash = seeded_hash(key, self.seed, row as u64);
+        // the highest bit selects the sign, the low 63 bits select the counter
+        let sign = if hash >> 63 == 1 { 1 } else { -1 };
+        (
+            row * self.width + ((hash & (u64::MAX >> 1)) % self.width as u64) as usize,
+            sign,
+        )
+    }
+}
+
+impl FrequencySketch for CountSketch {
+    fn add<T: Hash + ?Sized>(&mut self, key: &T, count: u64) {
+        let count = i64::try_from(count).unwrap_or(i64::MAX);
+        for row in 0..self.depth {
+            let (cell, sign) = self.cell(key, row);
+            self.counters[cell] = self.counters[cell].saturating_add(sign * count);
+        }
+    }
+
+    fn estimate<T: Hash + ?Sized>(&self, key: &T) -> u64 {
+        std::cmp::max(0, self.estimate_signed(key)) as u64
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn frequency_sketch_test() {
+    // item idx occurs 1000 / idx times
+    let counts: Vec<(u32, u64)> = (1..=1000u32).map(|idx| (idx, 1000 / idx as u64)).collect();
+    let total: u64 = counts.iter().map(|(_, count)| count).sum();
+    let mut count_min = CountMinSketch::with_error(0.001, 0.01);
+    let mut count_sketch = CountSketch::new(512, 5);
+    for (key, count) in counts.iter() {
+        for _ in 0..*count {
+            count_min.add(key, 1);
+        }
+        count_sketch.add(key, *count);
+    }
+    assert_eq!(count_min.total(), total);
+    for (key, count) in counts.iter() {
+        let estimate = count_min.estimate(key);
+        assert!(estimate >= *count && estimate <= count + total / 1000);
+    }
+    // every column is used, the odd ones included
+    let used = |parity: usize| {
+        count_sketch
+            .counters
+            .iter()
+            .enumerate()
+            .filter(|(idx, counter)| idx % 512 % 2 == parity && **counter != 0)
+            .count()
+    };
+    assert!(used(1) > 0 && used(1) * 2 > used(0));
+    let mut saturated = CountSketch::new(8, 2);
+    saturated.add(&1u32, u64::MAX);
+    saturated.add(&1u32, 1);
+    assert_eq!(saturated.estimate_signed(&1u32), i64::MAX);
+    for (key, count) in counts.iter().take(10) {
+        let estimate = count_sketch.estimate_signed(key);
+        assert!((estimate - *count as i64).abs() < 40);
+    }
+    assert_eq!(count_min.estimate("absent"), 0);
+
+    let restored = CountMinSketch::from_bytes(&count_min.to_bytes()).unwrap();
+    assert_eq!(restored.estimate(&1u32), count_min.estimate(&1u32));
+    let restored = CountSketch::from_bytes(&count_sketch.to_bytes()).unwrap();
+    assert_eq!(restored.estimate(&1u32), count_sketch.estimate(&1u32));
+    assert!(CountSketch::from_bytes(&count_min.to_bytes()).is_none());
+
+    let mut other = CountMinSketch::with_error(0.001, 0.01);
+    other.add(&1u32, 500);
+    assert!(count_min.merge(&other));
+    assert!(count_min.estimate(&1u32) >= 1500);
+    assert!(!count_min.merge(&CountMinSketch::new(10, 2)));
+    let mut other = CountSketch::new(512, 5);
+    other.add(&1u32, 500);
+    assert!(count_sketch.merge(&other));
+    assert!((count_sketch.estimate_signed(&1u32) - 1500).abs() < 40);
+}
+
+/*
+* Tracks the k most frequent items of a stream on top of a frequency sketch.
+* Only the current candidates are stored, every added item is compared with the least frequent of them.
+*/
+pub struct HeavyHitters<K, S> {
+    sketch: S,
+    k: usize,
+    top: HashMap<K, u64>,
+}
+
+impl<K, S> HeavyHitters<K, S>
+where
+    K: Hash + Eq + Clone,
+    S: FrequencySketch,
+{
+    pub fn new(sketch: S, k: usize) -> Self {
+        HeavyHitters {
+            sketch,
+            k,
+            top: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, key: K, count: u64) {
+        self.sketch.add(&key, count);
+        let estimate = self.sketch.estimate(&key);
+        if let Some(value) = self.top.get_mut(&key) {
+            *value = estimate;
+            return;
+        }
+        if self.top.len() < self.k {
+            self.top.insert(key, estimate);
+            return;
+        }
+        let min = self
+            .top
+            .iter()
+            .min_by_key(|(_, value)| **value)
+            .map(|(key, value)| (key.clone(), *value));
+        if let Some((min_key, min_value)) = min {
+            if estimate > min_value {
+                self.top.remove(&min_key);
+                self.top.insert(key, estimate);
+            }
+        }
+    }
+
+    /*
+     * returns the candidates with their estimated counts, the most frequent first
+     */
+    pub fn top(&self) -> Vec<(K, u64)> {
+        let mut res: Vec<(K, u64)> = self
+            .top
+            .iter()
+            .map(|(key, value)| (key.clone(), *value))
+            .collect();
+        res.sort_by_key(|item| std::cmp::Reverse(item.1));
+        res
+    }
+
+    pub fn sketch(&self) -> &S {
+        &self.sketch
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn heavy_hitters_test() {
+    let mut heavy_hitters = HeavyHitters::new(CountMinSketch::with_error(0.001, 0.01), 3);
+    let mut rng = crate::random::xorshift_32(11);
+    for step in 0..30000 {
+        let key = match step % 10 {
+            0..=2 => "/index".to_string(),
+            3 | 4 => "/login".to_string(),
+            5 => "/search".to_string(),
+            _ => format!("/item/{}", rng.next().unwrap() % 5000),
+        };
+        heavy_hitters.add(key, 1);
+    }
+    let top: Vec<String> = heavy_hitters
+        .top()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(top, vec!["/index", "/login", "/search"]);
+    assert!(heavy_hitters.top()[0].1 >= 9000);
+
+    let mut heavy_hitters = HeavyHitters::new(CountSketch::new(256, 5), 2);
+    for (key, count) in [("a", 5), ("b", 100), ("c", 3), ("d", 50), ("e", 1)] {
+        heavy_hitters.add(key, count);
+    }
+    let top: Vec<&str> = heavy_hitters
+        .top()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(top, vec!["b", "d"]);
+}
+
+const MERSENNE_61: u64 = (1 << 61) - 1;
+
+/*
+* MinHash signature of a set. For every one of num_perm random hash functions the minimum hash
+* over the items of the set is kept. The share of equal positions in two signatures estimates
+* the Jaccard similarity |A ∩ B| / |A ∪ B| of the sets with the error about 1 / sqrt(num_perm).
+* The hash functions are (a * h + b) mod (2^61 - 1) with the coefficients generated from the seed.
+*/
+#[derive(Clone)]
+pub struct MinHash {
+    seed: u64,
+    coefficients: Vec<(u64, u64)>,
+    signature: Vec<u64>,
+}
+
+impl MinHash {
+    pub fn new(num_perm: usize) -> Self {
+        Self::new_seeded(num_perm, BLOOM_DEFAULT_SEED)
+    }
+
+    pub fn new_seeded(num_perm: usize, seed: u64) -> Self {
+        assert!(num_perm > 0);
+        let mut rng = xorshift_32(seed as u32 ^ (seed >> 32) as u32);
+        let mut next = || {
+            let high = rng.next().unwrap() as u64;
+            let low = rng.next().unwrap() as u64;
+            (high << 32 | low) % MERSENNE_61
+        };
+        let coefficients = (0..num_perm)
+            .map(|_| (std::cmp::max(1, next()), next()))
+            .collect();
+        MinHash {
+            seed,
+            coefficients,
+            signature: vec![u64::MAX; num_perm],
+        }
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, key: &T) {
+        let hash = seeded_hash(key, self.seed, 0) % MERSENNE_61;
+        for (value, (a, b)) in self.signature.iter_mut().zip(self.coefficients.iter()) {
+            let permuted = ((*a as u128 * hash as u128 + *b as u128) % MERSENNE_61 as u128) as u64;
+            *value = std::cmp::min(*value, permuted);
+        }
+    }
+
+    pub fn signature(&self) -> &[u64] {
+        &self.signature
+    }
+
+    /*
+     * returns the estimated Jaccard similarity, or None if the signatures are not compatible
+     */
+    pub fn jaccard(&self, other: &MinHash) -> Option<f64> {
+        if self.seed != other.seed || self.signature.len() != other.signature.len() {
+            return None;
+        }
+        let equal = self
+            .signature
+            .iter()
+            .zip(other.signature.iter())
+            .filter(|(a, b)| a == b)
+            .count();
+        Some(equal as f64 / self.signature.len() as f64)
+    }
+
+    /*
+     * makes the signature of the union of the sets, returns false if the signatures are not compatible
+     */
+    pub fn merge(&mut self, other: &MinHash) -> bool {
+        if self.seed != other.seed || self.signature.len() != other.signature.len() {
+            return false;
+        }
+        for (own, value) in self.signature.iter_mut().zip(other.signature.iter()) {
+            *own = std::cmp::min(*own, *value);
+        }
+        true
+    }
+
+    /*
+     * serializes the signature: header (magic, format version), seed, number of hash functions and signature
+     * (the coefficients are generated from the seed again)
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(21 + 8 * self.signature.len());
+        bytes.extend_from_slice(MIN_HASH_MAGIC);
+        bytes.push(SKETCH_FORMAT_VERSION);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.signature.len() as u64).to_le_bytes());
+        for value in self.signature.iter() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<MinHash> {
+        let mut reader = ByteReader::new(bytes);
+        reader.header(MIN_HASH_MAGIC, SKETCH_FORMAT_VERSION)?;
+        let seed = reader.read_u64()?;
+        let len = reader.read_len()?;
+        if len == 0 || len > bytes.len() {
+            return None;
+        }
+        let signature = (0..len)
+            .map(|_| reader.read_u64())
+            .collect::<Option<Vec<u64>>>()?;
+        if !reader.is_empty() {
+            return None;
+        }
+        let mut res = MinHash::new_seeded(len, seed);
+        res.signature = signature;
+        Some(res)
+    }
+}
+
+/*
+* Locality-sensitive hashing index over MinHash signatures. The signature is split into bands of rows values,
+* two sets become candidates if at least one band is equal, that happens with the probability
+* 1 - (1 - s^rows)^bands for the sets with the Jaccard similarity s.
+*/
+pub struct MinHashLsh<K> {
+    bands: usize,
+    rows: usize,
+    tables: Vec<HashMap<u64, Vec<K>>>,
+}
+
+impl<K: Clone + Eq + Hash> MinHashLsh<K> {
+    pub fn new(bands: usize, rows: usize) -> Self {
+        assert!(bands > 0 && rows > 0);
+        MinHashLsh {
+            bands,
+            rows,
+            tables: (0..bands).map(|_| HashMap::new()).collect(),
+        }
+    }
+
+    /*
+     * returns false if the signature length is not bands * rows
+     */
+    pub fn insert(&mut self, key: K, min_hash: &MinHash) -> bool {
+        if !self.fits(min_hash) {
+            return false;
+        }
+        for band in 0..self.bands {
+            let hash = self.band_hash(min_hash, band);
+            self.tables[band].entry(hash).or_default().push(key.clone());
+        }
+        true
+    }
+
+    /*
+     * returns the keys of the sets that share at least one band with the signature
+     * (none if the signature length is not bands * rows)
+     */
+    pub fn query(&self, min_hash: &MinHash) -> Vec<K> {
+        let mut seen = HashSet::new();
+        let mut res = vec![];
+        if !self.fits(min_hash) {
+            return res;
+        }
+        for band in 0..self.bands {
+            if let Some(keys) = self.tables[band].get(&self.band_hash(min_hash, band)) {
+                for key in keys {
+                    if seen.insert(key.clone()) {
+                        res.push(key.clone());
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    fn fits(&self, min_hash: &MinHash) -> bool {
+        min_hash.signature.len() == self.bands * self.rows
+    }
+
+    fn band_hash(&self, min_hash: &MinHash, band: usize) -> u64 {
+        let values = &min_hash.signature[band * self.rows..(band + 1) * self.rows];
+        seeded_hash(values, 0, band as u64)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn min_hash_test() {
+    let words = |from: u32, to: u32| {
+        let mut min_hash = MinHash::new(256);
+        for idx in from..to {
+            min_hash.insert(&format!("word{}", idx));
+        }
+        min_hash
+    };
+    let a = words(0, 1000);
+    let b = words(333, 1333);
+    let c = words(5000, 6000);
+    // |a ∩ b| = 667, |a ∪ b| = 1333
+    assert!((a.jaccard(&b).unwrap() - 0.5).abs() < 0.1);
+    assert!(a.jaccard(&c).unwrap() < 0.05);
+    assert_eq!(a.jaccard(&a.clone()), Some(1.0));
+    assert_eq!(a.jaccard(&MinHash::new(128)), None);
+
+    let mut union = a.clone();
+    assert!(union.merge(&b));
+    assert!((union.jaccard(&words(0, 1333)).unwrap() - 1.0).abs() < f64::EPSILON);
+
+    let restored = MinHash::from_bytes(&a.to_bytes()).unwrap();
+    assert_eq!(restored.jaccard(&b), a.jaccard(&b));
+    assert!(MinHash::from_bytes(&a.to_bytes()[..20]).is_none());
+
+    let mut lsh = MinHashLsh::new(32, 8);
+    assert!(lsh.insert("a", &a));
+    assert!(lsh.insert("c", &c));
+    assert_eq!(lsh.query(&words(10, 1000)), vec!["a"]);
+    assert!(!lsh.insert("short", &MinHash::new(128)));
+    assert!(lsh.query(&MinHash::new(128)).is_empty());
+    assert!(lsh.query(&words(20000, 21000)).is_empty());
+}
+
+/*
+* A monoid is a set with an associative binary operation and an identity element.
+* It describes what the range structures (segment tree and others) aggregate.
+*/
+pub trait Monoid {
+    type S: Clone;
+    fn identity() -> Self::S;
+    fn op(a: &Self::S, b: &Self::S) -> Self::S;
+}
+
+/*
+* A monoid of lazy updates acting on another monoid.
+* mapping applies the update f to an aggregate x of len elements,
+* composition(f, g) returns the update equivalent to applying g first and f after it.
+*/
+pub trait MapMonoid {
+    type M: Monoid;
+    type F: Clone;
+    fn identity_map() -> Self::F;
+    fn mapping(f: &Self::F, x: &<Self::M as Monoid>::S, len: usize) -> <Self::M as Monoid>::S;
+    fn composition(f: &Self::F, g: &Self::F) -> Self::F;
+}
+
+pub struct SumMonoid;
+
+impl Monoid for SumMonoid {
+    type S = i64;
+    fn identity() -> i64 {
+        0
+    }
+    fn op(a: &i64, b: &i64) -> i64 {
+        a + b
+    }
+}
+
+pub struct MinMonoid;
+
+impl Monoid for MinMonoid {
+    type S = i64;
+    fn identity() -> i64 {
+        i64::MAX
+    }
+    fn op(a: &i64, b: &i64) -> i64 {
+        std::cmp::min(*a, *b)
+    }
+}
+
+pub struct MaxMonoid;
+
+impl Monoid for MaxMonoid {
+    type S = i64;
+    fn identity() -> i64 {
+        i64::MIN
+    }
+    fn op(a: &i64, b: &i64) -> i64 {
+        std::cmp::max(*a, *b)
+    }
+}
+
+/*
+* Range update used by the ready-made lazy monoids: assign a value to every element
+* of the range or add a value to every element of the range
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RangeUpdate {
+    Assign(i64),
+    Add(i64),
+}
+
+impl RangeUpdate {
+    fn compose(f: &RangeUpdate, g: &RangeUpdate) -> RangeUpdate {
+        match (*f, *g) {
+            (RangeUpdate::Assign(value), _) => RangeUpdate::Assign(value),
+            (RangeUpdate::Add(delta), RangeUpdate::Assign(value)) => {
+                RangeUpdate::Assign(value + delta)
+            }
+            (RangeUpdate::Add(first), RangeUpdate::Add(second)) => RangeUpdate::Add(first + second),
+        }
+    }
+}
+
+// range assignment / range addition with range sum queries
+pub struct RangeUpdateSum;
+
+impl MapMonoid for RangeUpdateSum {
+    type M = SumMonoid;
+    type F = RangeUpdate;
+    fn identity_map() -> RangeUpdate {
+        RangeUpdate::Add(0)
+    }
+    fn mapping(f: &RangeUpdate, x: &i64, len: usize) -> i64 {
+        match *f {
+            RangeUpdate::Assign(value) => value * len as i64,
+            RangeUpdate::Add(delta) => x + delta * len as i64,
+        }
+    }
+    fn composition(f: &RangeUpdate, g: &RangeUpdate) -> RangeUpdate {
+        RangeUpdate::compose(f, g)
+    }
+}
+
+// range assignment / range addition with range minimum queries
+pub struct RangeUpdateMin;
+
+impl MapMonoid for RangeUpdateMin {
+    type M = MinMonoid;
+    type F = RangeUpdate;
+    fn identity_map() -> RangeUpdate {
+        RangeUpdate::Add(0)
+    }
+    fn mapping(f: &RangeUpdate, x: &i64, _len: usize) -> i64 {
+        match *f {
+            RangeUpdate::Assign(value) => value,
+            RangeUpdate::Add(_) if *x == MinMonoid::identity() => *x,
+            RangeUpdate::Add(delta) => x + delta,
+        }
+    }
+    fn composition(f: &RangeUpdate, g: &RangeUpdate) -> RangeUpdate {
+        RangeUpdate::compose(f, g)
+    }
+}
+
+// range assignment / range addition with range maximum queries
+pub struct RangeUpdateMax;
+
+impl MapMonoid for RangeUpdateMax {
+    type M = MaxMonoid;
+    type F = RangeUpdate;
+    fn identity_map() -> RangeUpdate {
+        RangeUpdate::Add(0)
+    }
+    fn mapping(f: &RangeUpdate, x: &i64, _len: usize) -> i64 {
+        match *f {
+            RangeUpdate::Assign(value) => value,
+            RangeUpdate::Add(_) if *x == MaxMonoid::identity() => *x,
+            RangeUpdate::Add(delta) => x + delta,
+        }
+    }
+    fn composition(f: &RangeUpdate, g: &RangeUpdate) -> RangeUpdate {
+        RangeUpdate::compose(f, g)
+    }
+}
+
+/*
+* Segment tree over an arbitrary monoid.
+* Point update and range product over the half-open range [l, r) in O(log n),
+* max_right / min_left search the border of a range satisfying a monotone predicate in O(log n).
+*/
+pub struct SegmentTree<M: Monoid> {
+    n: usize,
+    size: usize,
+    data: Vec<M::S>,
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    pub fn new(n: usize) -> Self {
+        Self::from_slice(&vec![M::identity(); n])
+    }
+
+    pub fn from_slice(values: &[M::S]) -> Self {
+        let n = values.len();
+        let size = n.next_power_of_two();
+        let mut data = vec![M::identity(); 2 * size];
+        data[size..size + n].clone_from_slice(values);
+        for idx in (1..size).rev() {
+            data[idx] = M::op(&data[2 * idx], &data[2 * idx + 1]);
+        }
+        SegmentTree { n, size, data }
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn set(&mut self, pos: usize, value: M::S) {
+        assert!(pos < self.n);
+        let mut pos = pos + self.size;
+        self.data[pos] = value;
+        while pos > 1 {
+            pos >>= 1;
+            self.data[pos] = M::op(&self.data[2 * pos], &self.data[2 * pos + 1]);
+        }
+    }
+
+    pub fn get(&self, pos: usize) -> M::S {
+        assert!(pos < self.n);
+        self.data[pos + self.size].clone()
+    }
+
+    /*
+     * returns the product of the elements in [l, r)
+     */
+    pub fn prod(&self, l: usize, r: usize) -> M::S {
+        assert!(l <= r && r <= self.n);
+        let mut sml = M::identity();
+        let mut smr = M::identity();
+        let mut l = l + self.size;
+        let mut r = r + self.size;
+        while l < r {
+            if l & 1 == 1 {
+                sml = M::op(&sml, &self.data[l]);
+                l += 1;
+            }
+            if r & 1 == 1 {
+                r -= 1;
+                smr = M::op(&self.data[r], &smr);
+            }
+            l >>= 1;
+            r >>= 1;
+        }
+        M::op(&sml, &smr)
+    }
+
+    pub fn all_prod(&self) -> M::S {
+        self.data[1].clone()
+    }
+
+    /*
+     * returns the largest r such that f(prod(l, r)) is true.
+     * f must be monotone and f(identity) must be true
+     */
+    pub fn max_right<F>(&self, l: usize, f: F) -> usize
+    where
+        F: Fn(&M::S) -> bool,
+    {
+        assert!(l <= self.n);
+        assert!(f(&M::identity()));
+        if l == self.n {
+            return self.n;
+        }
+        let mut l = l + self.size;
+        let mut sm = M::identity();
+        loop {
+            while l & 1 == 0 {
+                l >>= 1;
+            }
+            if !f(&M::op(&sm, &self.data[l])) {
+                while l < self.size {
+                    l *= 2;
+                    let res = M::op(&sm, &self.data[l]);
+                    if f(&res) {
+                        sm = res;
+                        l += 1;
+                    }
+                }
+                return l - self.size;
+            }
+            sm = M::op(&sm, &self.data[l]);
+            l += 1;
+            if l.is_power_of_two() {
+                break;
+            }
+        }
+        self.n
+    }
+
+    /*
+     * returns the smallest l such that f(prod(l, r)) is true.
+     * f must be monotone and f(identity) must be true
+     */
+    pub fn min_left<F>(&self, r: usize, f: F) -> usize
+    where
+        F: Fn(&M::S) -> bool,
+    {
+        assert!(r <= self.n);
+        assert!(f(&M::identity()));
+        if r == 0 {
+            return 0;
+        }
+        let mut r = r + self.size;
+        let mut sm = M::identity();
+        loop {
+            r -= 1;
+            while r > 1 && r & 1 == 1 {
+                r >>= 1;
+            }
+            if !f(&M::op(&self.data[r], &sm)) {
+                while r < self.size {
+                    r = 2 * r + 1;
+                    let res = M::op(&self.data[r], &sm);
+                    if f(&res) {
+                        sm = res;
+                        r -= 1;
+                    }
+                }
+                return r + 1 - self.size;
+            }
+            sm = M::op(&self.data[r], &sm);
+            if r.is_power_of_two() {
+                break;
+            }
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn segment_tree_test() {
+    let values = [5, 3, 8, 1, 9, 2, 7];
+    let mut tree = SegmentTree::<SumMonoid>::from_slice(&values);
+    assert_eq!(tree.prod(0, 7), 35);
+    assert_eq!(tree.prod(2, 5), 18);
+    assert_eq!(tree.prod(3, 3), 0);
+    tree.set(3, 10);
+    assert_eq!(tree.get(3), 10);
+    assert_eq!(tree.all_prod(), 44);
+    // prefix sums from 1: 3, 11, 21, 30, 32, 39
+    assert_eq!(tree.max_right(1, |sum| *sum <= 21), 4);
+    assert_eq!(tree.max_right(1, |sum| *sum <= 100), 7);
+    assert_eq!(tree.max_right(7, |sum| *sum <= 0), 7);
+    // suffix sums ending at 6: 2, 11, 21, 29, 32, 37
+    assert_eq!(tree.min_left(6, |sum| *sum <= 21), 3);
+    assert_eq!(tree.min_left(6, |sum| *sum < 2), 6);
+    assert_eq!(tree.min_left(0, |sum| *sum < 2), 0);
+
+    let tree = SegmentTree::<MinMonoid>::from_slice(&values);
+    for l in 0..values.len() {
+        for r in l..=values.len() {
+            let expected = values[l..r].iter().copied().min().unwrap_or(i64::MAX);
+            assert_eq!(tree.prod(l, r), expected);
+        }
+    }
+    let tree = SegmentTree::<MaxMonoid>::new(0);
+    assert!(tree.is_empty());
+    assert_eq!(tree.all_prod(), i64::MIN);
+}
+
+/*
+* Segment tree with lazy propagation.
+* Range update (any MapMonoid, e.g. range assignment or range addition) and range product
+* over the half-open range [l, r) in O(log n).
+*/
+pub struct LazySegmentTree<F: MapMonoid> {
+    n: usize,
+    size: usize,
+    log: u32,
+    data: Vec<<F::M as Monoid>::S>,
+    lazy: Vec<F::F>,
+}
+
+impl<F: MapMonoid> LazySegmentTree<F> {
+    pub fn new(n: usize) -> Self {
+        Self::from_slice(&vec![F::M::identity(); n])
+    }
+
+    pub fn from_slice(values: &[<F::M as Monoid>::S]) -> Self {
+        let n = values.len();
+        let size = n.next_power_of_two();
+        let log = size.trailing_zeros();
+        let mut data = vec![F::M::identity(); 2 * size];
+        data[size..size + n].clone_from_slice(values);
+        let mut tree = LazySegmentTree {
+            n,
+            size,
+            log,
+            data,
+            lazy: vec![F::identity_map(); size],
+        };
+        for idx in (1..size).rev() {
+            tree.update(idx);
+        }
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn set(&mut self, pos: usize, value: <F::M as Monoid>::S) {
+        assert!(pos < self.n);
+        let pos = pos + self.size;
+        for i in (1..=self.log).rev() {
+            self.push(pos >> i);
+        }
+        self.data[pos] = value;
+        for i in 1..=self.log {
+            self.update(pos >> i);
+        }
+    }
+
+    pub fn get(&mut self, pos: usize) -> <F::M as Monoid>::S {
+        assert!(pos < self.n);
+        let pos = pos + self.size;
+        for i in (1..=self.log).rev() {
+            self.push(pos >> i);
+        }
+        self.data[pos].clone()
+    }
+
+    /*
+     * returns the product of the elements in [l, r)
+     */
+    pub fn prod(&mut self, l: usize, r: usize) -> <F::M as Monoid>::S {
+        assert!(l <= r && r <= self.n);
+        if l == r {
+            return F::M::identity();
+        }
+        let mut l = l + self.size;
+        let mut r = r + self.size;
+        self.push_borders(l, r);
+        let mut sml = F::M::identity();
+        let mut smr = F::M::identity();
+        while l < r {
+            if l & 1 == 1 {
+                sml = F::M::op(&sml, &self.data[l]);
+                l += 1;
+            }
+            if r & 1 == 1 {
+                r -= 1;
+                smr = F::M::op(&self.data[r], &smr);
+            }
+            l >>= 1;
+            r >>= 1;
+        }
+        F::M::op(&sml, &smr)
+    }
+
+    pub fn all_prod(&self) -> <F::M as Monoid>::S {
+        self.data[1].clone()
+    }
+
+    /*
+     * applies the update f to every element in [l, r)
+     */
+    pub fn apply(&mut self, l: usize, r: usize, f: F::F) {
+        assert!(l <= r && r <= self.n);
+        if l == r {
+            return;
+        }
+        let l = l + self.size;
+        let r = r + self.size;
+        self.push_borders(l, r);
+        {
+            let mut l = l;
+            let mut r = r;
+            while l < r {
+                if l & 1 == 1 {
+                    self.all_apply(l, &f);
+                    l += 1;
+                }
+                if r & 1 == 1 {
+                    r -= 1;
+                    self.all_apply(r, &f);
+                }
+                l >>= 1;
+                r >>= 1;
+            }
+        }
+        for i in 1..=self.log {
+            if ((l >> i) << i) != l {
+                self.update(l >> i);
+            }
+            if ((r >> i) << i) != r {
+                self.update((r - 1) >> i);
+            }
+        }
+    }
+
+    /*
+     * returns the largest r such that g(prod(l, r)) is true.
+     * g must be monotone and g(identity) must be true
+     */
+    pub fn max_right<G>(&mut self, l: usize, g: G) -> usize
+    where
+        G: Fn(&<F::M as Monoid>::S) -> bool,
+    {
+        assert!(l <= self.n);
+        assert!(g(&F::M::identity()));
+        if l == self.n {
+            return self.n;
+        }
+        let mut l = l + self.size;
+        for i in (1..=self.log).rev() {
+            self.push(l >> i);
+        }
+        let mut sm = F::M::identity();
+        loop {
+            while l & 1 == 0 {
+                l >>= 1;
+            }
+            if !g(&F::M::op(&sm, &self.data[l])) {
+                while l < self.size {
+                    self.push(l);
+                    l *= 2;
+                    let res = F::M::op(&sm, &self.data[l]);
+                    if g(&res) {
+                        sm = res;
+                        l += 1;
+                    }
+                }
+                return l - self.size;
+            }
+            sm = F::M::op(&sm, &self.data[l]);
+            l += 1;
+            if l.is_power_of_two() {
+                break;
+            }
+        }
+        self.n
+    }
+
+    /*
+     * returns the smallest l such that g(prod(l, r)) is true.
+     * g must be monotone and g(identity) must be true
+     */
+    pub fn min_left<G>(&mut self, r: usize, g: G) -> usize
+    where
+        G: Fn(&<F::M as Monoid>::S) -> bool,
+    {
+        assert!(r <= self.n);
+        assert!(g(&F::M::identity()));
+        if r == 0 {
+            return 0;
+        }
+        let mut r = r + self.size;
+        for i in (1..=self.log).rev() {
+            self.push((r - 1) >> i);
+        }
+        let mut sm = F::M::identity();
+        loop {
+            r -= 1;
+            while r > 1 && r & 1 == 1 {
+                r >>= 1;
+            }
+            if !g(&F::M::op(&self.data[r], &sm)) {
+                while r < self.size {
+                    self.push(r);
+                    r = 2 * r + 1;
+                    let res = F::M::op(&self.data[r], &sm);
+                    if g(&res) {
+                        sm = res;
+                        r -= 1;
+                    }
+                }
+                return r + 1 - self.size;
+            }
+            sm = F::M::op(&self.data[r], &sm);
+            if r.is_power_of_two() {
+                break;
+            }
+        }
+        0
+    }
+
+    fn update(&mut self, k: usize) {
+        self.data[k] = F::M::op(&self.data[2 * k], &self.data[2 * k + 1]);
+    }
+
+    // number of leaves under the node k
+    fn node_len(&self, k: usize) -> usize {
+        self.size >> (usize::BITS - 1 - k.leading_zeros())
+    }
+
+    fn all_apply(&mut self, k: usize, f: &F::F) {
+        self.data[k] = F::mapping(f, &self.data[k], self.node_len(k));
+        if k < self.size {
+            self.lazy[k] = F::composition(f, &self.lazy[k]);
+        }
+    }
+
+    fn push(&mut self, k: usize) {
+        let f = std::mem::replace(&mut self.lazy[k], F::identity_map());
+        self.all_apply(2 * k, &f);
+        self.all_apply(2 * k + 1, &f);
+    }
+
+    fn push_borders(&mut self, l: usize, r: usize) {
+        for i in (1..=self.log).rev() {
+            if ((l >> i) << i) != l {
+                self.push(l >> i);
+            }
+            if ((r >> i) << i) != r {
+                self.push((r - 1) >> i);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn lazy_segment_tree_test() {
+    let mut values = vec![5, 3, 8, 1, 9, 2, 7, 4, 6, 0, 11];
+    let mut sum = LazySegmentTree::<RangeUpdateSum>::from_slice(&values);
+    let mut min = LazySegmentTree::<RangeUpdateMin>::from_slice(&values);
+    let mut max = LazySegmentTree::<RangeUpdateMax>::from_slice(&values);
+    let updates = [
+        (2, 7, RangeUpdate::Add(3)),
+        (0, 4, RangeUpdate::Assign(-2)),
+        (5, 11, RangeUpdate::Add(-4)),
+        (3, 9, RangeUpdate::Assign(6)),
+        (1, 10, RangeUpdate::Add(1)),
+        (4, 5, RangeUpdate::Add(10)),
+    ];
+    for (l, r, f) in updates {
+        sum.apply(l, r, f);
+        min.apply(l, r, f);
+        max.apply(l, r, f);
+        for value in values[l..r].iter_mut() {
+            *value = match f {
+                RangeUpdate::Assign(x) => x,
+                RangeUpdate::Add(x) => *value + x,
+            };
+        }
+        for l in 0..values.len() {
+            for r in l..=values.len() {
+                let slice = &values[l..r];
+                assert_eq!(sum.prod(l, r), slice.iter().sum::<i64>());
+                assert_eq!(
+                    min.prod(l, r),
+                    slice.iter().copied().min().unwrap_or(i64::MAX)
+                );
+                assert_eq!(
+                    max.prod(l, r),
+                    slice.iter().copied().max().unwrap_or(i64::MIN)
+                );
+            }
+        }
+    }
+    // values: [-2, -1, -1, 7, 17, 7, 7, 7, 7, -3, 7]
+    assert_eq!(values, vec![-2, -1, -1, 7, 17, 7, 7, 7, 7, -3, 7]);
+    sum.set(0, 2);
+    assert_eq!(sum.get(0), 2);
+    assert_eq!(sum.get(4), 17);
+    // prefix sums from 0: 2, 1, 0, 7, 24
+    assert_eq!(sum.max_right(0, |s| *s <= 7), 4);
+    assert_eq!(max.max_right(0, |m| *m < 10), 4);
+    assert_eq!(max.min_left(11, |m| *m < 10), 5);
+    assert_eq!(min.min_left(11, |m| *m >= 0), 10);
+}
+
+#[derive(Clone)]
+struct PersistentSegmentTreeNode<S> {
+    value: S,
+    left: usize,
+    right: usize,
+}
+
+/*
+* Persistent segment tree over an arbitrary monoid.
+* Every point update creates a new version sharing all untouched nodes with the old one,
+* so any version can still be queried. Update and query take O(log n),
+* an update allocates O(log n) new nodes.
+*/
+pub struct PersistentSegmentTree<M: Monoid> {
+    n: usize,
+    nodes: Vec<PersistentSegmentTreeNode<M::S>>,
+    roots: Vec<usize>,
+}
+
+impl<M: Monoid> PersistentSegmentTree<M> {
+    /*
+     * builds the version 0 from the specified values
+     */
+    pub fn from_slice(values: &[M::S]) -> Self {
+        let mut tree = PersistentSegmentTree {
+            n: values.len(),
+            nodes: vec![],
+            roots: vec![],
+        };
+        let root = if values.is_empty() {
+            tree.make_node(M::identity(), 0, 0)
+        } else {
+            tree.build(values, 0, values.len())
+        };
+        tree.roots.push(root);
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /*
+     * returns the number of versions, the latest version is versions() - 1
+     */
+    pub fn versions(&self) -> usize {
+        self.roots.len()
+    }
+
+    /*
+     * sets the element pos of the specified version and returns the number of the new version
+     */
+    pub fn set(&mut self, version: usize, pos: usize, value: M::S) -> usize {
+        assert!(pos < self.n);
+        let root = self.set_node(self.roots[version], 0, self.n, pos, value);
+        self.roots.push(root);
+        self.roots.len() - 1
+    }
+
+    pub fn get(&self, version: usize, pos: usize) -> M::S {
+        assert!(pos < self.n);
+        let mut node = self.roots[version];
+        let (mut l, mut r) = (0, self.n);
+        while r - l > 1 {
+            let mid = l + (r - l) / 2;
+            if pos < mid {
+                node = self.nodes[node].left;
+                r = mid;
+            } else {
+                node = self.nodes[node].right;
+                l = mid;
+            }
+        }
+        self.nodes[node].value.clone()
+    }
+
+    /*
+     * returns the product of the elements in [l, r) of the specified version
+     */
+    pub fn prod(&self, version: usize, l: usize, r: usize) -> M::S {
+        assert!(l <= r && r <= self.n);
+        if l == r {
+            return M::identity();
+        }
+        self.prod_node(self.roots[version], 0, self.n, l, r)
+    }
+
+    fn make_node(&mut self, value: M::S, left: usize, right: usize) -> usize {
+        self.nodes
+            .push(PersistentSegmentTreeNode { value, left, right });
+        self.nodes.len() - 1
+    }
+
+    fn build(&mut self, values: &[M::S], l: usize, r: usize) -> usize {
+        if r - l == 1 {
+            return self.make_node(values[l].clone(), 0, 0);
+        }
+        let mid = l + (r - l) / 2;
+        let left = self.build(values, l, mid);
+        let right = self.build(values, mid, r);
+        let value = M::op(&self.nodes[left].value, &self.nodes[right].value);
+        self.make_node(value, left, right)
+    }
+
+    fn set_node(&mut self, node: usize, l: usize, r: usize, pos: usize, value: M::S) -> usize {
+        if r - l == 1 {
+            return self.make_node(value, 0, 0);
+        }
+        let mid = l + (r - l) / 2;
+        let (mut left, mut right) = (self.nodes[node].left, self.nodes[node].right);
+        if pos < mid {
+            left = self.set_node(left, l, mid, pos, value);
+        } else {
+            right = self.set_node(right, mid, r, pos, value);
+        }
+        let value = M::op(&self.nodes[left].value, &self.nodes[right].value);
+        self.make_node(value, left, right)
+    }
+
+    fn prod_node(&self, node: usize, l: usize, r: usize, ql: usize, qr: usize) -> M::S {
+        if ql <= l && r <= qr {
+            return self.nodes[node].value.clone();
+        }
+        let mid = l + (r - l) / 2;
+        let mut res = M::identity();
+        if ql < mid {
+            res = self.prod_node(self.nodes[node].left, l, mid, ql, qr);
+        }
+        if qr > mid {
+            res = M::op(
+                &res,
+                &self.prod_node(self.nodes[node].right, mid, r, ql, qr),
+            );
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn persistent_segment_tree_test() {
+    let mut tree = PersistentSegmentTree::<SumMonoid>::from_slice(&[1, 2, 3, 4, 5]);
+    let v1 = tree.set(0, 2, 10);
+    let v2 = tree.set(v1, 0, -1);
+    let v3 = tree.set(0, 4, 0);
+    assert_eq!(tree.versions(), 4);
+    assert_eq!(tree.prod(0, 0, 5), 15);
+    assert_eq!(tree.prod(v1, 0, 5), 22);
+    assert_eq!(tree.prod(v2, 0, 5), 20);
+    assert_eq!(tree.prod(v3, 0, 5), 10);
+    assert_eq!(tree.prod(v2, 1, 3), 12);
+    assert_eq!(tree.prod(0, 1, 3), 5);
+    assert_eq!(tree.get(v2, 0), -1);
+    assert_eq!(tree.get(v3, 0), 1);
+    assert_eq!(tree.get(v3, 2), 3);
+
+    let tree = PersistentSegmentTree::<MinMonoid>::from_slice(&[]);
+    assert!(tree.is_empty());
+    assert_eq!(tree.prod(0, 0, 0), i64::MAX);
+}
+
+/*
+* Fenwick tree (binary indexed tree).
+* Point update and prefix sum in O(log n), uses n + 1 elements of memory.
+* For non-negative values find_kth works as an order statistics search over counts in O(log n).
+*/
+pub struct Fenwick<T> {
+    tree: Vec<T>,
+}
+
+impl<T> Fenwick<T>
+where
+    T: Copy + Default + std::ops::AddAssign + std::ops::Sub<Output = T>,
+{
+    pub fn new(n: usize) -> Self {
+        Fenwick {
+            tree: vec![T::default(); n + 1],
+        }
+    }
+
+    /*
+     * builds the tree in O(n)
+     */
+    pub fn from_slice(values: &[T]) -> Self {
+        let mut tree = vec![T::default(); values.len() + 1];
+        tree[1..].copy_from_slice(values);
+        for idx in 1..tree.len() {
+            let parent = idx + (idx & idx.wrapping_neg());
+            if parent < tree.len() {
+                let value = tree[idx];
+                tree[parent] += value;
+            }
+        }
+        Fenwick { tree }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.len() == 1
+    }
+
+    /*
+     * adds delta to the element idx
+     */
+    pub fn add(&mut self, idx: usize, delta: T) {
+        assert!(idx < self.len());
+        let mut idx = idx + 1;
+        while idx < self.tree.len() {
+            self.tree[idx] += delta;
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+
+    /*
+     * returns the sum of the elements in [0, r)
+     */
+    pub fn prefix_sum(&self, r: usize) -> T {
+        assert!(r <= self.len());
+        let mut res = T::default();
+        let mut idx = r;
+        while idx > 0 {
+            res += self.tree[idx];
+            idx &= idx - 1;
+        }
+        res
+    }
+
+    /*
+     * returns the sum of the elements in [l, r)
+     */
+    pub fn sum(&self, l: usize, r: usize) -> T {
+        assert!(l <= r);
+        self.prefix_sum(r) - self.prefix_sum(l)
+    }
+}
+
+impl<T> Fenwick<T>
+where
+    T: Copy + Default + PartialOrd + std::ops::AddAssign + std::ops::Sub<Output = T>,
+{
+    /*
+     * treats the elements as non-negative counts and returns the index of the k-th (0-based) item,
+     * that is the smallest idx such that prefix_sum(idx + 1) > k
+     */
+    pub fn find_kth(&self, k: T) -> Option<usize> {
+        let mut pos = 0;
+        let mut rest = k;
+        let mut step = self.len().checked_next_power_of_two()?;
+        while step > 0 {
+            if pos + step < self.tree.len() && self.tree[pos + step] <= rest {
+                pos += step;
+                rest = rest - self.tree[pos];
+            }
+            step >>= 1;
+        }
+        if pos < self.len() {
+            Some(pos)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn fenwick_test() {
+    let values = [5, 3, 8, 1, 9, 2, 7];
+    let mut fenwick = Fenwick::from_slice(&values);
+    assert_eq!(fenwick.len(), 7);
+    for l in 0..values.len() {
+        for r in l..=values.len() {
+            assert_eq!(fenwick.sum(l, r), values[l..r].iter().sum::<i32>());
+        }
+    }
+    fenwick.add(3, 4);
+    assert_eq!(fenwick.prefix_sum(4), 21);
+    assert_eq!(fenwick.sum(3, 4), 5);
+
+    // multiset {1, 1, 4, 6, 6, 6}
+    let mut counts = Fenwick::<u32>::new(8);
+    for value in [1, 4, 6, 1, 6, 6] {
+        counts.add(value, 1);
+    }
+    let kth: Vec<_> = (0..7).map(|k| counts.find_kth(k)).collect();
+    assert_eq!(
+        kth,
+        vec![Some(1), Some(1), Some(4), Some(6), Some(6), Some(6), None]
+    );
+    assert_eq!(Fenwick::<u32>::new(0).find_kth(0), None);
+}
+
+/*
+* Fenwick tree with range update and range sum, built on two ordinary Fenwick trees.
+* Both operations take O(log n). The updates are stored with the negated deltas, so T must be signed.
+*/
+pub struct RangeFenwick<T> {
+    linear: Fenwick<T>,
+    constant: Fenwick<T>,
+}
+
+impl<T> RangeFenwick<T>
+where
+    T: Copy
+        + Default
+        + From<u32>
+        + std::ops::AddAssign
+        + std::ops::Sub<Output = T>
+        + std::ops::Mul<Output = T>
+        + std::ops::Neg<Output = T>,
+{
+    pub fn new(n: usize) -> Self {
+        RangeFenwick {
+            linear: Fenwick::new(n + 1),
+            constant: Fenwick::new(n + 1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.linear.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /*
+     * adds delta to every element in [l, r)
+     */
+    pub fn add(&mut self, l: usize, r: usize, delta: T) {
+        assert!(l <= r && r <= self.len());
+        self.linear.add(l, delta);
+        self.linear.add(r, -delta);
+        self.constant.add(l, delta * Self::index(l));
+        self.constant.add(r, -(delta * Self::index(r)));
+    }
+
+    /*
+     * returns the sum of the elements in [0, r)
+     */
+    pub fn prefix_sum(&self, r: usize) -> T {
+        assert!(r <= self.len());
+        self.linear.prefix_sum(r) * Self::index(r) - self.constant.prefix_sum(r)
+    }
+
+    /*
+     * returns the sum of the elements in [l, r)
+     */
+    pub fn sum(&self, l: usize, r: usize) -> T {
+        assert!(l <= r);
+        self.prefix_sum(r) - self.prefix_sum(l)
+    }
+
+    fn index(idx: usize) -> T {
+        T::from(u32::try_from(idx).expect("index doesn't fit into u32"))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn range_fenwick_test() {
+    let mut values = [0i64; 9];
+    let mut fenwick = RangeFenwick::<i64>::new(values.len());
+    for (l, r, delta) in [(0, 4, 3), (2, 9, -2), (5, 6, 10), (3, 3, 7), (1, 8, 1)] {
+        fenwick.add(l, r, delta);
+        for value in values[l..r].iter_mut() {
+            *value += delta;
+        }
+        for l in 0..values.len() {
+            for r in l..=values.len() {
+                assert_eq!(fenwick.sum(l, r), values[l..r].iter().sum::<i64>());
+            }
+        }
+    }
+    let mut fenwick = RangeFenwick::<f64>::new(4);
+    fenwick.add(1, 3, 0.5);
+    assert_eq!(fenwick.prefix_sum(4), 1.0);
+}
+
+/*
+* Two-dimensional Fenwick tree.
+* Point update and sum over a rectangle in O(log n * log m).
+*/
+pub struct Fenwick2D<T> {
+    tree: Vec<Vec<T>>,
+}
+
+impl<T> Fenwick2D<T>
+where
+    T: Copy + Default + std::ops::AddAssign + std::ops::Sub<Output = T> + std::ops::Add<Output = T>,
+{
+    pub fn new(n: usize, m: usize) -> Self {
+        Fenwick2D {
+            tree: vec![vec![T::default(); m + 1]; n + 1],
+        }
+    }
+
+    /*
+     * adds delta to the element (x, y)
+     */
+    pub fn add(&mut self, x: usize, y: usize, delta: T) {
+        assert!(x + 1 < self.tree.len() && y + 1 < self.tree[0].len());
+        let mut i = x + 1;
+        while i < self.tree.len() {
+            let mut j = y + 1;
+            while j < self.tree[i].len() {
+                self.tree[i][j] += delta;
+                j += j & j.wrapping_neg();
+            }
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /*
+     * returns the sum over the rectangle [0, x) x [0, y)
+     */
+    pub fn prefix_sum(&self, x: usize, y: usize) -> T {
+        assert!(x < self.tree.len() && y < self.tree[0].len());
+        let mut res = T::default();
+        let mut i = x;
+        while i > 0 {
+            let mut j = y;
+            while j > 0 {
+                res += self.tree[i][j];
+                j &= j - 1;
+            }
+            i &= i - 1;
+        }
+        res
+    }
+
+    /*
+     * returns the sum over the rectangle [x1, x2) x [y1, y2)
+     */
+    pub fn sum(&self, x1: usize, y1: usize, x2: usize, y2: usize) -> T {
+        assert!(x1 <= x2 && y1 <= y2);
+        // the two added rectangles cover the subtracted ones, so it works for unsigned types too
+        self.prefix_sum(x2, y2) + self.prefix_sum(x1, y1)
+            - self.prefix_sum(x1, y2)
+            - self.prefix_sum(x2, y1)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn fenwick_2d_test() {
+    let mut grid = vec![vec![0; 5]; 4];
+    let mut fenwick = Fenwick2D::new(4, 5);
+    for (x, y, delta) in [(0, 0, 1), (1, 3, 5), (3, 4, -2), (2, 2, 7), (1, 3, 1)] {
+        fenwick.add(x, y, delta);
+        grid[x][y] += delta;
+    }
+    for x1 in 0..=4 {
+        for x2 in x1..=4 {
+            for y1 in 0..=5 {
+                for y2 in y1..=5 {
+                    let expected: i32 = grid[x1..x2]
+                        .iter()
+                        .map(|row| row[y1..y2].iter().sum::<i32>())
+                        .sum();
+                    assert_eq!(fenwick.sum(x1, y1, x2, y2), expected);
+                }
+            }
+        }
+    }
+
+    let mut fenwick = Fenwick2D::<u64>::new(3, 3);
+    fenwick.add(0, 0, 1);
+    fenwick.add(2, 1, 4);
+    assert_eq!(fenwick.sum(1, 1, 2, 2), 0);
+    assert_eq!(fenwick.sum(1, 0, 3, 3), 4);
+    assert_eq!(fenwick.sum(0, 0, 3, 3), 5);
+}
+
+/*
+* Sparse table for static range queries with an idempotent operation (min, max, gcd, bitwise and/or).
+* Preprocessing in O(n log n), query in O(1): the range is covered by two overlapping power-of-two blocks,
+* so the operation must satisfy op(x, x) = x.
+*/
+pub struct SparseTable<M: Monoid> {
+    table: Vec<Vec<M::S>>,
+}
+
+impl<M: Monoid> SparseTable<M> {
+    pub fn from_slice(values: &[M::S]) -> Self {
+        let mut table = vec![values.to_vec()];
+        let mut k = 1;
+        while (1 << k) <= values.len() {
+            let prev = &table[k - 1];
+            let half = 1 << (k - 1);
+            let level = (0..=values.len() - (1 << k))
+                .map(|idx| M::op(&prev[idx], &prev[idx + half]))
+                .collect();
+            table.push(level);
+            k += 1;
+        }
+        SparseTable { table }
+    }
+
+    pub fn len(&self) -> usize {
+        self.table[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table[0].is_empty()
+    }
+
+    /*
+     * returns the product of the elements in [l, r)
+     */
+    pub fn prod(&self, l: usize, r: usize) -> M::S {
+        assert!(l <= r && r <= self.len());
+        if l == r {
+            return M::identity();
+        }
+        let k = (usize::BITS - 1 - (r - l).leading_zeros()) as usize;
+        M::op(&self.table[k][l], &self.table[k][r - (1 << k)])
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn sparse_table_test() {
+    let values = [5, 3, 8, 1, 9, 2, 7, 4, 6, 0, 11, -3, 12];
+    let min = SparseTable::<MinMonoid>::from_slice(&values);
+    let max = SparseTable::<MaxMonoid>::from_slice(&values);
+    for l in 0..values.len() {
+        for r in l..=values.len() {
+            let slice = &values[l..r];
+            assert_eq!(
+                min.prod(l, r),
+                slice.iter().copied().min().unwrap_or(i64::MAX)
+            );
+            assert_eq!(
+                max.prod(l, r),
+                slice.iter().copied().max().unwrap_or(i64::MIN)
+            );
+        }
+    }
+    let empty = SparseTable::<MinMonoid>::from_slice(&[]);
+    assert!(empty.is_empty());
+    assert_eq!(empty.prod(0, 0), i64::MAX);
+}
+
+/*
+* Disjoint sparse table for static range queries with any associative operation (sum, product, matrix product).
+* Preprocessing in O(n log n), query in O(1) with a single application of the operation.
+*/
+pub struct DisjointSparseTable<M: Monoid> {
+    values: Vec<M::S>,
+    table: Vec<Vec<M::S>>,
+}
+
+impl<M: Monoid> DisjointSparseTable<M> {
+    pub fn from_slice(values: &[M::S]) -> Self {
+        let n = values.len();
+        let mut table = vec![];
+        let mut half = 1;
+        while half < n {
+            // every block of size 2 * half is split at mid: suffix products to the left, prefix products to the right
+            let mut level = vec![M::identity(); n];
+            for mid in (half..n).step_by(2 * half) {
+                level[mid - 1] = values[mid - 1].clone();
+                for idx in (mid - half..mid - 1).rev() {
+                    level[idx] = M::op(&values[idx], &level[idx + 1]);
+                }
+                level[mid] = values[mid].clone();
+                for idx in mid + 1..std::cmp::min(mid + half, n) {
+                    level[idx] = M::op(&level[idx - 1], &values[idx]);
+                }
+            }
+            table.push(level);
+            half *= 2;
+        }
+        DisjointSparseTable {
+            values: values.to_vec(),
+            table,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /*
+     * returns the product of the elements in [l, r)
+     */
+    pub fn prod(&self, l: usize, r: usize) -> M::S {
+        assert!(l <= r && r <= self.len());
+        if l == r {
+            return M::identity();
+        }
+        let r = r - 1;
+        if l == r {
+            return self.values[l].clone();
+        }
+        let level = (usize::BITS - 1 - (l ^ r).leading_zeros()) as usize;
+        M::op(&self.table[level][l], &self.table[level][r])
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn disjoint_sparse_table_test() {
+    struct Concat;
+    impl Monoid for Concat {
+        type S = String;
+        fn identity() -> String {
+            String::new()
+        }
+        fn op(a: &String, b: &String) -> String {
+            format!("{}{}", a, b)
+        }
+    }
+
+    let values = [5, 3, 8, 1, 9, 2, 7, 4, 6, 0, 11, -3, 12];
+    let sum = DisjointSparseTable::<SumMonoid>::from_slice(&values);
+    for l in 0..values.len() {
+        for r in l..=values.len() {
+            assert_eq!(sum.prod(l, r), values[l..r].iter().sum::<i64>());
+        }
+    }
+    let letters: Vec<String> = "abcdefghij".chars().map(String::from).collect();
+    let concat = DisjointSparseTable::<Concat>::from_slice(&letters);
+    assert_eq!(concat.prod(2, 7), "cdefg");
+    assert_eq!(concat.prod(0, 10), "abcdefghij");
+    assert_eq!(concat.prod(9, 10), "j");
+    assert_eq!(concat.prod(4, 4), "");
+}
+
+/*
+* Range minimum query with linear preprocessing (block decomposition in the spirit of Fischer–Heun).
+* The array is split into blocks of 64 elements. Inside a block the answer is read from a bitmask of the
+* monotonic stack, between blocks a sparse table over the block minimums is used.
+* Preprocessing in O(n), query in O(1). The query returns the position of the leftmost minimum.
+*/
+pub struct Rmq<T> {
+    values: Vec<T>,
+    masks: Vec<u64>,
+    blocks: Vec<Vec<usize>>,
+}
+
+const RMQ_BLOCK: usize = 64;
+
+impl<T: Ord + Clone> Rmq<T> {
+    pub fn from_slice(values: &[T]) -> Self {
+        let mut masks = vec![0u64; values.len()];
+        for start in (0..values.len()).step_by(RMQ_BLOCK) {
+            let end = std::cmp::min(start + RMQ_BLOCK, values.len());
+            let mut stack: Vec<usize> = Vec::with_capacity(RMQ_BLOCK);
+            let mut mask = 0u64;
+            for idx in start..end {
+                while let Some(&top) = stack.last() {
+                    if values[top] <= values[idx] {
+                        break;
+                    }
+                    mask &= !(1 << (top - start));
+                    stack.pop();
+                }
+                stack.push(idx);
+                mask |= 1 << (idx - start);
+                masks[idx] = mask;
+            }
+        }
+        let mut rmq = Rmq {
+            values: values.to_vec(),
+            masks,
+            blocks: vec![],
+        };
+        let block_count = values.len().div_ceil(RMQ_BLOCK);
+        let mut level: Vec<usize> = (0..block_count)
+            .map(|block| {
+                let end = std::cmp::min((block + 1) * RMQ_BLOCK, values.len());
+                rmq.in_block(block * RMQ_BLOCK, end - 1)
+            })
+            .collect();
+        let mut width = 1;
+        while !level.is_empty() {
+            let next: Vec<usize> = (0..level.len().saturating_sub(width))
+                .map(|idx| rmq.better(level[idx], level[idx + width]))
+                .collect();
+            rmq.blocks.push(level);
+            level = next;
+            width *= 2;
+        }
+        rmq
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /*
+     * returns the position of the minimum in [l, r), or None for an empty range
+     */
+    pub fn query(&self, l: usize, r: usize) -> Option<usize> {
+        assert!(l <= r && r <= self.len());
+        if l == r {
+            return None;
+        }
+        let r = r - 1;
+        let (lb, rb) = (l / RMQ_BLOCK, r / RMQ_BLOCK);
+        if lb == rb {
+            return Some(self.in_block(l, r));
+        }
+        let mut res = self.in_block(l, (lb + 1) * RMQ_BLOCK - 1);
+        if lb + 1 < rb {
+            let (from, to) = (lb + 1, rb);
+            let k = (usize::BITS - 1 - (to - from).leading_zeros()) as usize;
+            res = self.better(res, self.blocks[k][from]);
+            res = self.better(res, self.blocks[k][to - (1 << k)]);
+        }
+        Some(self.better(res, self.in_block(rb * RMQ_BLOCK, r)))
+    }
+
+    /*
+     * returns the minimum value in [l, r), or None for an empty range
+     */
+    pub fn min(&self, l: usize, r: usize) -> Option<&T> {
+        self.query(l, r).map(|idx| &self.values[idx])
+    }
+
+    // l and r (inclusive) lie in the same block
+    fn in_block(&self, l: usize, r: usize) -> usize {
+        let start = l - l % RMQ_BLOCK;
+        let mask = self.masks[r] & (!0u64 << (l - start));
+        start + mask.trailing_zeros() as usize
+    }
+
+    fn better(&self, first: usize, second: usize) -> usize {
+        if self.values[second] < self.values[first]
+            || (self.values[second] == self.values[first] && second < first)
+        {
+            second
+        } else {
+            first
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn rmq_test() {
+    let values: Vec<u32> = crate::random::xorshift_32(7)
+        .take(1000)
+        .map(|value| value % 100)
+        .collect();
+    let rmq = Rmq::from_slice(&values);
+    for l in (0..values.len()).step_by(7) {
+        for r in (l..=values.len()).step_by(5) {
+            let expected = (l..r).min_by_key(|&idx| (values[idx], idx));
+            assert_eq!(rmq.query(l, r), expected);
+        }
+    }
+    let rmq = Rmq::from_slice(&[3, 1, 2, 1]);
+    assert_eq!(rmq.query(0, 4), Some(1));
+    assert_eq!(rmq.query(2, 4), Some(3));
+    assert_eq!(rmq.min(0, 1), Some(&3));
+    assert_eq!(rmq.query(2, 2), None);
+    assert!(Rmq::<u32>::from_slice(&[]).is_empty());
+}
+
+const TREAP_DEFAULT_SEED: u32 = 0x2545_f491;
+
+struct TreapNode<K, V> {
+    key: K,
+    value: V,
+    priority: u32,
+    size: usize,
+    left: Option<Box<TreapNode<K, V>>>,
+    right: Option<Box<TreapNode<K, V>>>,
+}
+
+type TreapLink<K, V> = Option<Box<TreapNode<K, V>>>;
+
+impl<K, V> TreapNode<K, V> {
+    fn update(&mut self) {
+        self.size = 1 + treap_size(&self.left) + treap_size(&self.right);
+    }
+}
+
+fn treap_size<K, V>(node: &TreapLink<K, V>) -> usize {
+    node.as_ref().map_or(0, |node| node.size)
+}
+
+// splits the tree into the keys less than key and the rest
+fn treap_split<K: Ord, V>(node: TreapLink<K, V>, key: &K) -> (TreapLink<K, V>, TreapLink<K, V>) {
+    match node {
+        None => (None, None),
+        Some(mut node) => {
+            if node.key < *key {
+                let (left, right) = treap_split(node.right.take(), key);
+                node.right = left;
+                node.update();
+                (Some(node), right)
+            } else {
+                let (left, right) = treap_split(node.left.take(), key);
+                node.left = right;
+                node.update();
+                (left, Some(node))
+            }
+        }
+    }
+}
+
+// all keys of the first tree are less than the keys of the second one
+fn treap_merge<K, V>(first: TreapLink<K, V>, second: TreapLink<K, V>) -> TreapLink<K, V> {
+    match (first, second) {
+        (None, second) => second,
+        (first, None) => first,
+        (Some(mut first), Some(mut second)) => {
+            if first.priority > second.priority {
+                first.right = treap_merge(first.right.take(), Some(second));
+                first.update();
+                Some(first)
+            } else {
+                second.left = treap_merge(Some(first), second.left.take());
+                second.update();
+                Some(second)
+            }
+        }
+    }
+}
+
+/*
+* Treap (cartesian tree by key and random priority) used as an ordered map.
+* Besides the usual map operations it answers order statistics: the k-th smallest key and the rank of a key.
+* Priorities come from a seeded xorshift generator, so the shape of the tree is reproducible.
+* All operations take O(log n) on average.
+*/
+pub struct Treap<K, V> {
+    root: TreapLink<K, V>,
+    rng: XorShift32,
+}
+
+impl<K: Ord, V> Treap<K, V> {
+    pub fn new() -> Self {
+        Self::with_seed(TREAP_DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u32) -> Self {
+        Treap {
+            root: None,
+            rng: xorshift_32(seed),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        treap_size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /*
+     * inserts the key, returns the previous value if the key was already present
+     */
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(std::mem::replace(old, value));
+        }
+        let node = Box::new(TreapNode {
+            key,
+            value,
+            priority: self.rng.next().unwrap(),
+            size: 1,
+            left: None,
+            right: None,
+        });
+        let (left, right) = treap_split(self.root.take(), &node.key);
+        self.root = treap_merge(treap_merge(left, Some(node)), right);
+        None
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (left, right) = treap_split(self.root.take(), key);
+        let (mut middle, right) = match right {
+            Some(node) if treap_min_key(&node) == key => {
+                let mut middle = None;
+                let rest = treap_remove_min(Some(node), &mut middle);
+                (middle, rest)
+            }
+            right => (None, right),
+        };
+        self.root = treap_merge(left, right);
+        middle.take().map(|node| node.value)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let mut node = &self.root;
+        while let Some(current) = node {
+            match key.cmp(&current.key) {
+                std::cmp::Ordering::Less => node = &current.left,
+                std::cmp::Ordering::Greater => node = &current.right,
+                std::cmp::Ordering::Equal => return Some(&current.value),
+            }
+        }
+        None
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let mut node = &mut self.root;
+        while let Some(current) = node {
+            match key.cmp(&current.key) {
+                std::cmp::Ordering::Less => node = &mut current.left,
+                std::cmp::Ordering::Greater => node = &mut current.right,
+                std::cmp::Ordering::Equal => return Some(&mut current.value),
+            }
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /*
+     * returns the k-th smallest entry (numbering from zero)
+     */
+    pub fn kth(&self, mut k: usize) -> Option<(&K, &V)> {
+        let mut node = &self.root;
+        while let Some(current) = node {
+            let left = treap_size(&current.left);
+            if k < left {
+                node = &current.left;
+            } else if k == left {
+                return Some((&current.key, &current.value));
+            } else {
+                k -= left + 1;
+                node = &current.right;
+            }
+        }
+        None
+    }
+
+    /*
+     * returns the number of keys less than the key
+     */
+    pub fn rank(&self, key: &K) -> usize {
+        let mut res = 0;
+        let mut node = &self.root;
+        while let Some(current) = node {
+            if current.key < *key {
+                res += treap_size(&current.left) + 1;
+                node = &current.right;
+            } else {
+                node = &current.left;
+            }
+        }
+        res
+    }
+
+    /*
+     * moves the entries with keys greater than or equal to the key into the returned treap
+     */
+    pub fn split_off(&mut self, key: &K) -> Treap<K, V> {
+        let (left, right) = treap_split(self.root.take(), key);
+        self.root = left;
+        let seed = self.rng.next().unwrap();
+        Treap {
+            root: right,
+            rng: xorshift_32(seed),
+        }
+    }
+
+    /*
+     * moves all entries of the other treap into this one. All keys of the other treap must be greater
+     * than the keys of this one, otherwise nothing is moved and false is returned
+     */
+    pub fn append(&mut self, other: &mut Treap<K, V>) -> bool {
+        if let (Some(last), Some(first)) = (self.last_key(), other.kth(0)) {
+            if *last >= *first.0 {
+                return false;
+            }
+        }
+        self.root = treap_merge(self.root.take(), other.root.take());
+        true
+    }
+
+    /*
+     * iterates over the entries in the key order
+     */
+    pub fn iter(&self) -> TreapRange<'_, K, V> {
+        self.range_from(Bound::Unbounded, Bound::Unbounded)
+    }
+
+    /*
+     * iterates over the entries with keys in the range, in the key order
+     */
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> TreapRange<'_, K, V>
+    where
+        K: Clone,
+    {
+        self.range_from(range.start_bound(), range.end_bound().cloned())
+    }
+
+    fn range_from(&self, start: Bound<&K>, end: Bound<K>) -> TreapRange<'_, K, V> {
+        let mut stack = vec![];
+        let mut node = &self.root;
+        while let Some(current) = node {
+            let after_start = match start {
+                Bound::Included(key) => current.key >= *key,
+                Bound::Excluded(key) => current.key > *key,
+                Bound::Unbounded => true,
+            };
+            if after_start {
+                stack.push(current.as_ref());
+                node = &current.left;
+            } else {
+                node = &current.right;
+            }
+        }
+        TreapRange { stack, end }
+    }
+
+    fn last_key(&self) -> Option<&K> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = &node.right {
+            node = right;
+        }
+        Some(&node.key)
+    }
+}
+
+impl<K: Ord, V> Default for Treap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn treap_min_key<K, V>(mut node: &TreapNode<K, V>) -> &K {
+    while let Some(left) = &node.left {
+        node = left;
+    }
+    &node.key
+}
+
+// detaches the leftmost node of the tree into removed
+fn treap_remove_min<K, V>(node: TreapLink<K, V>, removed: &mut TreapLink<K, V>) -> TreapLink<K, V> {
+    let mut node = node?;
+    if node.left.is_none() {
+        let right = node.right.take();
+        *removed = Some(node);
+        return right;
+    }
+    node.left = treap_remove_min(node.left.take(), removed);
+    node.update();
+    Some(node)
+}
+
+pub struct TreapRange<'a, K, V> {
+    stack: Vec<&'a TreapNode<K, V>>,
+    end: Bound<K>,
+}
+
+impl<'a, K: Ord, V> Iterator for TreapRange<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        let before_end = match &self.end {
+            Bound::Included(key) => node.key <= *key,
+            Bound::Excluded(key) => node.key < *key,
+            Bound::Unbounded => true,
+        };
+        if !before_end {
+            self.stack.clear();
+            return None;
+        }
+        let mut child = &node.right;
+        while let Some(current) = child {
+            self.stack.push(current);
+            child = &current.left;
+        }
+        Some((&node.key, &node.value))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn treap_test() {
+    let mut treap = Treap::new();
+    let mut expected = BTreeMap::new();
+    let mut rng = crate::random::xorshift_32(5);
+    for _ in 0..2000 {
+        let key = rng.next().unwrap() % 500;
+        let value = rng.next().unwrap();
+        if rng.next().unwrap().is_multiple_of(3) {
+            assert_eq!(treap.remove(&key), expected.remove(&key));
+        } else {
+            assert_eq!(treap.insert(key, value), expected.insert(key, value));
+        }
+    }
+    assert_eq!(treap.len(), expected.len());
+    let keys: Vec<u32> = expected.keys().copied().collect();
+    for (idx, key) in keys.iter().enumerate() {
+        assert_eq!(treap.kth(idx).map(|entry| *entry.0), Some(*key));
+        assert_eq!(treap.rank(key), idx);
+        assert_eq!(treap.get(key), expected.get(key));
+    }
+    assert_eq!(treap.kth(keys.len()), None);
+    let range: Vec<u32> = treap.range(100..=200).map(|entry| *entry.0).collect();
+    let expected_range: Vec<u32> = expected.range(100..=200).map(|entry| *entry.0).collect();
+    assert_eq!(range, expected_range);
+    assert!(treap.iter().map(|entry| entry.0).eq(expected.keys()));
+
+    // leaderboard: score -> player, rank counted from the top
+    let mut leaderboard = Treap::with_seed(1);
+    for (score, player) in [(120, "ann"), (340, "bob"), (95, "eve"), (200, "joe")] {
+        leaderboard.insert(score, player);
+    }
+    assert_eq!(leaderboard.len() - leaderboard.rank(&200) - 1, 1);
+    assert_eq!(leaderboard.kth(leaderboard.len() - 1), Some((&340, &"bob")));
+    *leaderboard.get_mut(&95).unwrap() = "max";
+    assert_eq!(leaderboard.get(&95), Some(&"max"));
+
+    let mut high = leaderboard.split_off(&150);
+    assert_eq!(
+        leaderboard.iter().map(|entry| *entry.0).collect::<Vec<_>>(),
+        vec![95, 120]
+    );
+    assert_eq!(
+        high.iter().map(|entry| *entry.0).collect::<Vec<_>>(),
+        vec![200, 340]
+    );
+    assert!(!high.append(&mut leaderboard));
+    assert!(leaderboard.append(&mut high));
+    assert!(high.is_empty());
+    assert_eq!(leaderboard.len(), 4);
+    assert!(leaderboard.contains_key(&340));
+}
+
+struct ImplicitTreapNode<T> {
+    value: T,
+    priority: u32,
+    size: usize,
+    reversed: bool,
+    left: Option<Box<ImplicitTreapNode<T>>>,
+    right: Option<Box<ImplicitTreapNode<T>>>,
+}
+
+type ImplicitTreapLink<T> = Option<Box<ImplicitTreapNode<T>>>;
+
+impl<T> ImplicitTreapNode<T> {
+    fn update(&mut self) {
+        self.size = 1 + implicit_treap_size(&self.left) + implicit_treap_size(&self.right);
+    }
+
+    // applies the pending reversal to the children
+    fn push(&mut self) {
+        if self.reversed {
+            std::mem::swap(&mut self.left, &mut self.right);
+            for child in [&mut self.left, &mut self.right].into_iter().flatten() {
+                child.reversed ^= true;
+            }
+            self.reversed = false;
+        }
+    }
+}
+
+fn implicit_treap_size<T>(node: &ImplicitTreapLink<T>) -> usize {
+    node.as_ref().map_or(0, |node| node.size)
+}
+
+// splits the sequence into the first count elements and the rest
+fn implicit_treap_split<T>(
+    node: ImplicitTreapLink<T>,
+    count: usize,
+) -> (ImplicitTreapLink<T>, ImplicitTreapLink<T>) {
+    match node {
+        None => (None, None),
+        Some(mut node) => {
+            node.push();
+            let left_size = implicit_treap_size(&node.left);
+            if left_size < count {
+                let (left, right) = implicit_treap_split(node.right.take(), count - left_size - 1);
+                node.right = left;
+                node.update();
+                (Some(node), right)
+            } else {
+                let (left, right) = implicit_treap_split(node.left.take(), count);
+                node.left = right;
+                node.update();
+                (left, Some(node))
+            }
+        }
+    }
+}
+
+fn implicit_treap_merge<T>(
+    first: ImplicitTreapLink<T>,
+    second: ImplicitTreapLink<T>,
+) -> ImplicitTreapLink<T> {
+    match (first, second) {
+        (None, second) => second,
+        (first, None) => first,
+        (Some(mut first), Some(mut second)) => {
+            if first.priority > second.priority {
+                first.push();
+                first.right = implicit_treap_merge(first.right.take(), Some(second));
+                first.update();
+                Some(first)
+            } else {
+                second.push();
+                second.left = implicit_treap_merge(Some(first), second.left.take());
+                second.update();
+                Some(second)
+            }
+        }
+    }
+}
+
+/*
+* Treap with implicit keys: the key of an element is its position in the sequence.
+* Supports inserting and removing at any position, cutting and concatenating sequences
+* and reversing a subarray, all in O(log n) on average.
+*/
+pub struct ImplicitTreap<T> {
+    root: ImplicitTreapLink<T>,
+    rng: XorShift32,
+}
+
+impl<T> ImplicitTreap<T> {
+    pub fn new() -> Self {
+        Self::with_seed(TREAP_DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u32) -> Self {
+        ImplicitTreap {
+            root: None,
+            rng: xorshift_32(seed),
+        }
+    }
+
+    pub fn from_slice(values: &[T]) -> Self
+    where
+        T: Clone,
+    {
+        let mut res = Self::new();
+        for value in values {
+            res.push(value.clone());
+        }
+        res
+    }
+
+    pub fn len(&self) -> usize {
+        implicit_treap_size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.insert(self.len(), value);
+    }
+
+    /*
+     * inserts the value so that it gets the position pos
+     */
+    pub fn insert(&mut self, pos: usize, value: T) {
+        assert!(pos <= self.len());
+        let node = Box::new(ImplicitTreapNode {
+            value,
+            priority: self.rng.next().unwrap(),
+            size: 1,
+            reversed: false,
+            left: None,
+            right: None,
+        });
+        let (left, right) = implicit_treap_split(self.root.take(), pos);
+        self.root = implicit_treap_merge(implicit_treap_merge(left, Some(node)), right);
+    }
+
+    pub fn remove(&mut self, pos: usize) -> Option<T> {
+        if pos >= self.len() {
+            return None;
+        }
+        let (left, right) = implicit_treap_split(self.root.take(), pos);
+        let (middle, right) = implicit_treap_split(right, 1);
+        self.root = implicit_treap_merge(left, right);
+        middle.map(|node| node.value)
+    }
+
+    pub fn get(&self, mut pos: usize) -> Option<&T> {
+        let mut node = &self.root;
+        // the pending reversals of the ancestors decide which child is the left one
+        let mut reversed = false;
+        while let Some(current) = node {
+            reversed ^= current.reversed;
+            let (left, right) = if reversed {
+                (&current.right, &current.left)
+            } else {
+                (&current.left, &current.right)
+            };
+            let left_size = implicit_treap_size(left);
+            if pos < left_size {
+                node = left;
+            } else if pos == left_size {
+                return Some(&current.value);
+            } else {
+                pos -= left_size + 1;
+                node = right;
+            }
+        }
+        None
+    }
+
+    /*
+     * reverses the elements in [l, r)
+     */
+    pub fn reverse(&mut self, l: usize, r: usize) {
+        assert!(l <= r && r <= self.len());
+        let (left, right) = implicit_treap_split(self.root.take(), l);
+        let (mut middle, right) = implicit_treap_split(right, r - l);
+        if let Some(node) = middle.as_mut() {
+            node.reversed ^= true;
+        }
+        self.root = implicit_treap_merge(implicit_treap_merge(left, middle), right);
+    }
+
+    /*
+     * moves the elements starting from the position pos into the returned sequence
+     */
+    pub fn split_off(&mut self, pos: usize) -> ImplicitTreap<T> {
+        assert!(pos <= self.len());
+        let (left, right) = implicit_treap_split(self.root.take(), pos);
+        self.root = left;
+        let seed = self.rng.next().unwrap();
+        ImplicitTreap {
+            root: right,
+            rng: xorshift_32(seed),
+        }
+    }
+
+    /*
+     * moves all elements of the other sequence to the end of this one
+     */
+    pub fn append(&mut self, other: &mut ImplicitTreap<T>) {
+        self.root = implicit_treap_merge(self.root.take(), other.root.take());
+    }
+
+    pub fn iter(&self) -> ImplicitTreapIter<'_, T> {
+        let mut iter = ImplicitTreapIter { stack: vec![] };
+        iter.push_left(&self.root, false);
+        iter
+    }
+}
+
+impl<T> Default for ImplicitTreap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct ImplicitTreapIter<'a, T> {
+    // nodes with the parity of the pending reversals on the path to them
+    stack: Vec<(&'a ImplicitTreapNode<T>, bool)>,
+}
+
+impl<'a, T> ImplicitTreapIter<'a, T> {
+    fn push_left(&mut self, mut node: &'a ImplicitTreapLink<T>, mut reversed: bool) {
+        while let Some(current) = node {
+            reversed ^= current.reversed;
+            self.stack.push((current, reversed));
+            node = if reversed {
+                &current.right
+            } else {
+                &current.left
+            };
+        }
+    }
+}
+
+impl<'a, T> Iterator for ImplicitTreapIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, reversed) = self.stack.pop()?;
+        self.push_left(if reversed { &node.left } else { &node.right }, reversed);
+        Some(&node.value)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn implicit_treap_test() {
+    let mut treap = ImplicitTreap::from_slice(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    let mut expected: Vec<i32> = (0..10).collect();
+    treap.reverse(2, 7);
+    expected[2..7].reverse();
+    treap.reverse(0, 5);
+    expected[0..5].reverse();
+    assert!(treap.iter().eq(expected.iter()));
+    for (pos, value) in expected.iter().enumerate() {
+        assert_eq!(treap.get(pos), Some(value));
+    }
+    assert_eq!(treap.get(10), None);
+
+    let mut rng = crate::random::xorshift_32(9);
+    for _ in 0..500 {
+        let len = expected.len() as u32;
+        match rng.next().unwrap() % 3 {
+            0 => {
+                let pos = (rng.next().unwrap() % (len + 1)) as usize;
+                treap.insert(pos, pos as i32 * 10);
+                expected.insert(pos, pos as i32 * 10);
+            }
+            1 if len > 0 => {
+                let pos = (rng.next().unwrap() % len) as usize;
+                assert_eq!(treap.remove(pos), Some(expected.remove(pos)));
+            }
+            _ => {
+                let l = (rng.next().unwrap() % (len + 1)) as usize;
+                let r = (rng.next().unwrap() % (len + 1)) as usize;
+                let (l, r) = (std::cmp::min(l, r), std::cmp::max(l, r));
+                treap.reverse(l, r);
+                expected[l..r].reverse();
+            }
+        }
+    }
+    assert!(treap.iter().eq(expected.iter()));
+
+    let mut tail = treap.split_off(3);
+    assert_eq!(treap.len(), 3);
+    assert_eq!(tail.len(), expected.len() - 3);
+    tail.append(&mut treap);
+    expected.rotate_left(3);
+    assert!(tail.iter().eq(expected.iter()));
+    assert!(treap.is_empty());
+    assert_eq!(treap.remove(0), None);
+}
+
+/*
+* Min-priority queue over items 0, 1, 2, ... (for example the vertices of a graph).
+* Every item is present at most once, its priority can be changed while it is in the queue,
+* so algorithms like Dijkstra don't have to push duplicates.
+*/
+pub trait IndexedPriorityQueue<P: Ord> {
+    /*
+     * creates an empty queue for the items less than capacity (it grows if a greater item is pushed)
+     */
+    fn with_capacity(capacity: usize) -> Self
+    where
+        Self: Sized;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, item: usize) -> bool {
+        self.priority(item).is_some()
+    }
+
+    fn priority(&self, item: usize) -> Option<&P>;
+
+    /*
+     * returns the item with the least priority
+     */
+    fn peek(&self) -> Option<(usize, &P)>;
+
+    /*
+     * adds the item, returns false if it is already in the queue
+     */
+    fn push(&mut self, item: usize, priority: P) -> bool;
+
+    fn pop(&mut self) -> Option<(usize, P)>;
+
+    fn remove(&mut self, item: usize) -> Option<P>;
+
+    /*
+     * sets the new priority of the item, returns false if the item is not in the queue
+     */
+    fn change_priority(&mut self, item: usize, priority: P) -> bool;
+
+    /*
+     * lowers the priority of the item, returns false if the item is not in the queue
+     * or its priority is already less than the new one
+     */
+    fn decrease_key(&mut self, item: usize, priority: P) -> bool {
+        match self.priority(item) {
+            Some(current) if priority <= *current => self.change_priority(item, priority),
+            _ => false,
+        }
+    }
+}
+
+/*
+* Indexed d-ary heap: every node has D children. A greater D makes the tree lower,
+* so decreasing a key is cheaper (O(log_D n)), while pop costs O(D log_D n).
+*/
+pub struct DaryHeap<P, const D: usize> {
+    heap: Vec<(usize, P)>,
+    positions: Vec<Option<usize>>,
+}
+
+/*
+* Indexed binary heap
+*/
+pub type IndexedBinaryHeap<P> = DaryHeap<P, 2>;
+
+impl<P: Ord, const D: usize> DaryHeap<P, D> {
+    fn swap_nodes(&mut self, first: usize, second: usize) {
+        self.heap.swap(first, second);
+        self.positions[self.heap[first].0] = Some(first);
+        self.positions[self.heap[second].0] = Some(second);
+    }
+
+    fn sift_up(&mut self, mut idx: usize) {
+        while idx > 0 {
+            let parent = (idx - 1) / D;
+            if self.heap[parent].1 <= self.heap[idx].1 {
+                break;
+            }
+            self.swap_nodes(parent, idx);
+            idx = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut idx: usize) {
+        loop {
+            let first = idx * D + 1;
+            let last = std::cmp::min(first + D, self.heap.len());
+            let best = (first..last).min_by(|a, b| self.heap[*a].1.cmp(&self.heap[*b].1));
+            match best {
+                Some(child) if self.heap[child].1 < self.heap[idx].1 => {
+                    self.swap_nodes(idx, child);
+                    idx = child;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn remove_at(&mut self, idx: usize) -> (usize, P) {
+        let last = self.heap.len() - 1;
+        self.swap_nodes(idx, last);
+        let (item, priority) = self.heap.pop().unwrap();
+        self.positions[item] = None;
+        if idx < self.heap.len() {
+            self.sift_down(idx);
+            self.sift_up(idx);
+        }
+        (item, priority)
+    }
+}
+
+impl<P: Ord, const D: usize> IndexedPriorityQueue<P> for DaryHeap<P, D> {
+    fn with_capacity(capacity: usize) -> Self {
+        assert!(D >= 2);
+        DaryHeap {
+            heap: Vec::with_capacity(capacity),
+            positions: vec![None; capacity],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    fn priority(&self, item: usize) -> Option<&P> {
+        let idx = (*self.positions.get(item)?)?;
+        Some(&self.heap[idx].1)
+    }
+
+    fn peek(&self) -> Option<(usize, &P)> {
+        self.heap.first().map(|(item, priority)| (*item, priority))
+    }
+
+    fn push(&mut self, item: usize, priority: P) -> bool {
+        if self.contains(item) {
+            return false;
+        }
+        if item >= self.positions.len() {
+            self.positions.resize(item + 1, None);
+        }
+        self.heap.push((item, priority));
+        self.positions[item] = Some(self.heap.len() - 1);
+        self.sift_up(self.heap.len() - 1);
+        true
+    }
+
+    fn pop(&mut self) -> Option<(usize, P)> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    fn remove(&mut self, item: usize) -> Option<P> {
+        let idx = (*self.positions.get(item)?)?;
+        Some(self.remove_at(idx).1)
+    }
+
+    fn change_priority(&mut self, item: usize, priority: P) -> bool {
+        let idx = match self.positions.get(item) {
+            Some(Some(idx)) => *idx,
+            _ => return false,
+        };
+        self.heap[idx].1 = priority;
+        self.sift_up(idx);
+        self.sift_down(self.positions[item].unwrap());
+        true
+    }
+}
+
+#[derive(Clone)]
+struct PairingNode<P> {
+    priority: Option<P>,
+    child: Option<usize>,
+    sibling: Option<usize>,
+    // the parent for the first child, the left sibling for the others
+    prev: Option<usize>,
+}
+
+/*
+* Indexed pairing heap. Push takes O(1), pop and decrease_key take O(log n) amortized.
+* The nodes live in an array indexed by the item, so meld moves the nodes of the heap with the smaller
+* capacity into the other one and takes O(min(n, m)) for the capacities n and m.
+*/
+pub struct PairingHeap<P> {
+    nodes: Vec<PairingNode<P>>,
+    root: Option<usize>,
+    len: usize,
+}
+
+impl<P: Ord> PairingHeap<P> {
+    /*
+     * moves all items of the other heap into this one. The heaps must not share items,
+     * otherwise nothing is moved and false is returned
+     */
+    pub fn meld(&mut self, mut other: PairingHeap<P>) -> bool {
+        let (smaller, larger) = if other.nodes.len() <= self.nodes.len() {
+            (&other, &*self)
+        } else {
+            (&*self, &other)
+        };
+        let shared = smaller
+            .nodes
+            .iter()
+            .enumerate()
+            .any(|(item, node)| node.priority.is_some() && larger.contains(item));
+        if shared {
+            return false;
+        }
+        if other.nodes.len() > self.nodes.len() {
+            std::mem::swap(self, &mut other);
+        }
+        for (item, node) in other.nodes.iter_mut().enumerate() {
+            if node.priority.is_some() {
+                self.nodes[item] = std::mem::replace(node, PairingNode::empty());
+            }
+        }
+        self.root = self.link(self.root, other.root);
+        self.len += other.len;
+        true
+    }
+
+    // makes the root with the greater priority the first child of the other one
+    fn link(&mut self, first: Option<usize>, second: Option<usize>) -> Option<usize> {
+        let (mut parent, mut child) = match (first, second) {
+            (None, second) => return second,
+            (first, None) => return first,
+            (Some(first), Some(second)) => (first, second),
+        };
+        if self.nodes[child].priority < self.nodes[parent].priority {
+            std::mem::swap(&mut parent, &mut child);
+        }
+        if let Some(old) = self.nodes[parent].child {
+            self.nodes[old].prev = Some(child);
+        }
+        self.nodes[child].sibling = self.nodes[parent].child;
+        self.nodes[child].prev = Some(parent);
+        self.nodes[parent].child = Some(child);
+        Some(parent)
+    }
+
+    // detaches the subtree of the item from its parent
+    fn cut(&mut self, item: usize) {
+        if let Some(prev) = self.nodes[item].prev.take() {
+            let sibling = self.nodes[item].sibling.take();
+            if self.nodes[prev].child == Some(item) {
+                self.nodes[prev].child = sibling;
+            } else {
+                self.nodes[prev].sibling = sibling;
+            }
+            if let Some(sibling) = sibling {
+                self.nodes[sibling].prev = Some(prev);
+            }
+        }
+    }
+
+    // two pass merging of the children of the item, returns the new root
+    fn merge_children(&mut self, item: usize) -> Option<usize> {
+        let mut children = vec![];
+        let mut child = self.nodes[item].child.take();
+        while let Some(current) = child {
+            child = self.nodes[current].sibling.take();
+            self.nodes[current].prev = None;
+            children.push(current);
+        }
+        let mut pairs = vec![];
+        for pair in children.chunks(2) {
+            pairs.push(self.link(Some(pair[0]), pair.get(1).copied()));
+        }
+        pairs
+            .into_iter()
+            .rev()
+            .fold(None, |acc, root| self.link(root, acc))
+    }
+}
+
+impl<P> PairingNode<P> {
+    fn empty() -> Self {
+        PairingNode {
+            priority: None,
+            child: None,
+            sibling: None,
+            prev: None,
+        }
+    }
+}
+
+impl<P: Ord> IndexedPriorityQueue<P> for PairingHeap<P> {
+    fn with_capacity(capacity: usize) -> Self {
+        PairingHeap {
+            nodes: (0..capacity).map(|_| PairingNode::empty()).collect(),
+            root: None,
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn priority(&self, item: usize) -> Option<&P> {
+        self.nodes.get(item)?.priority.as_ref()
+    }
+
+    fn peek(&self) -> Option<(usize, &P)> {
+        let root = self.root?;
+        self.nodes[root]
+            .priority
+            .as_ref()
+            .map(|priority| (root, priority))
+    }
+
+    fn push(&mut self, item: usize, priority: P) -> bool {
+        if self.contains(item) {
+            return false;
+        }
+        if item >= self.nodes.len() {
+            self.nodes.resize_with(item + 1, PairingNode::empty);
+        }
+        self.nodes[item].priority = Some(priority);
+        self.root = self.link(self.root, Some(item));
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<(usize, P)> {
+        let root = self.root?;
+        self.remove(root).map(|priority| (root, priority))
+    }
+
+    fn remove(&mut self, item: usize) -> Option<P> {
+        if !self.contains(item) {
+            return None;
+        }
+        let rest = self.merge_children(item);
+        if self.root == Some(item) {
+            self.root = rest;
+        } else {
+            self.cut(item);
+            self.root = self.link(self.root, rest);
+        }
+        self.len -= 1;
+        self.nodes[item].priority.take()
+    }
+
+    fn change_priority(&mut self, item: usize, priority: P) -> bool {
+        match self.priority(item) {
+            None => return false,
+            Some(current) if priority <= *current => {
+                self.nodes[item].priority = Some(priority);
+                if self.root != Some(item) {
+                    self.cut(item);
+                    self.root = self.link(self.root, Some(item));
+                }
+            }
+            Some(_) => {
+                self.remove(item);
+                self.push(item, priority);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+fn check_indexed_priority_queue<H: IndexedPriorityQueue<u32>>() {
+    let mut heap = H::with_capacity(4);
+    let mut expected: Vec<Option<u32>> = vec![None; 300];
+    let mut rng = crate::random::xorshift_32(3);
+    for _ in 0..3000 {
+        let item = (rng.next().unwrap() % 300) as usize;
+        let priority = rng.next().unwrap() % 1000;
+        match rng.next().unwrap() % 5 {
+            0 => {
+                assert_eq!(heap.push(item, priority), expected[item].is_none());
+                expected[item] = expected[item].or(Some(priority));
+            }
+            1 => {
+                let decreased = expected[item].is_some_and(|old| priority <= old);
+                assert_eq!(heap.decrease_key(item, priority), decreased);
+                if decreased {
+                    expected[item] = Some(priority);
+                }
+            }
+            2 => {
+                assert_eq!(
+                    heap.change_priority(item, priority),
+                    expected[item].is_some()
+                );
+                expected[item] = expected[item].map(|_| priority);
+            }
+            3 => assert_eq!(heap.remove(item), expected[item].take()),
+            _ => {
+                // equal priorities may be popped in any order
+                let min = expected.iter().flatten().min().copied();
+                let popped = heap.pop();
+                assert_eq!(popped.map(|entry| entry.1), min);
+                if let Some((item, priority)) = popped {
+                    assert_eq!(expected[item].take(), Some(priority));
+                }
+            }
+        }
+        assert_eq!(heap.priority(item), expected[item].as_ref());
+    }
+    assert_eq!(heap.len(), expected.iter().flatten().count());
+    let mut sorted: Vec<u32> = expected.iter().flatten().copied().collect();
+    sorted.sort_unstable();
+    let mut popped = vec![];
+    while let Some((item, priority)) = heap.pop() {
+        assert_eq!(expected[item], Some(priority));
+        popped.push(priority);
+    }
+    assert_eq!(popped, sorted);
+    assert!(heap.is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn indexed_priority_queue_test() {
+    check_indexed_priority_queue::<IndexedBinaryHeap<u32>>();
+    check_indexed_priority_queue::<DaryHeap<u32, 4>>();
+    check_indexed_priority_queue::<PairingHeap<u32>>();
+
+    let mut first = PairingHeap::with_capacity(3);
+    first.push(0, 5);
+    first.push(2, 1);
+    let mut second = PairingHeap::with_capacity(10);
+    second.push(7, 3);
+    second.push(9, 0);
+    assert!(first.meld(second));
+    let mut other = PairingHeap::with_capacity(1);
+    other.push(0, 4);
+    assert!(!first.meld(other));
+    let mut larger = PairingHeap::with_capacity(20);
+    larger.push(15, 6);
+    larger.push(2, 7);
+    assert!(!first.meld(larger));
+    assert_eq!(first.len(), 4);
+    assert!(first.decrease_key(0, 2));
+    assert!(!first.decrease_key(0, 8));
+    let order: Vec<(usize, u32)> = std::iter::from_fn(|| first.pop()).collect();
+    assert_eq!(order, vec![(9, 0), (2, 1), (0, 2), (7, 3)]);
+
+    // melding many heaps one by one
+    let mut heap = PairingHeap::with_capacity(0);
+    for item in 0..1000 {
+        let mut single = PairingHeap::with_capacity(item + 1);
+        single.push(item, (item * 7919) % 1000);
+        assert!(heap.meld(single));
+    }
+    assert_eq!(heap.len(), 1000);
+    let order: Vec<usize> = std::iter::from_fn(|| heap.pop().map(|entry| entry.1)).collect();
+    assert_eq!(order, (0..1000).collect::<Vec<_>>());
 }
@@ -0,0 +1,535 @@
+use std::rc::Rc;
+
+pub use super::PersistentSegmentTree;
+
+enum ArrayNode<T> {
+    Leaf(T),
+    Branch(Rc<ArrayNode<T>>, Rc<ArrayNode<T>>),
+}
+
+/*
+* Persistent array on a balanced binary tree with path copying.
+* set returns a new version and leaves the old one untouched, the versions share all unchanged nodes.
+* get and set take O(log n), set allocates O(log n) nodes. Cloning a version is O(1).
+*/
+pub struct PersistentArray<T> {
+    root: Option<Rc<ArrayNode<T>>>,
+    len: usize,
+}
+
+impl<T> Clone for PersistentArray<T> {
+    fn clone(&self) -> Self {
+        PersistentArray {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T: Clone> PersistentArray<T> {
+    pub fn from_slice(values: &[T]) -> Self {
+        PersistentArray {
+            root: if values.is_empty() {
+                None
+            } else {
+                Some(Self::build(values))
+            },
+            len: values.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, pos: usize) -> Option<&T> {
+        if pos >= self.len {
+            return None;
+        }
+        let mut node = self.root.as_ref()?;
+        let (mut l, mut r) = (0, self.len);
+        loop {
+            match node.as_ref() {
+                ArrayNode::Leaf(value) => return Some(value),
+                ArrayNode::Branch(left, right) => {
+                    let mid = l + (r - l) / 2;
+                    if pos < mid {
+                        node = left;
+                        r = mid;
+                    } else {
+                        node = right;
+                        l = mid;
+                    }
+                }
+            }
+        }
+    }
+
+    /*
+     * returns the new version with the element pos replaced
+     */
+    pub fn set(&self, pos: usize, value: T) -> Self {
+        assert!(pos < self.len);
+        PersistentArray {
+            root: Some(Self::set_node(
+                self.root.as_ref().unwrap(),
+                0,
+                self.len,
+                pos,
+                value,
+            )),
+            len: self.len,
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        (0..self.len)
+            .map(|pos| self.get(pos).unwrap().clone())
+            .collect()
+    }
+
+    fn build(values: &[T]) -> Rc<ArrayNode<T>> {
+        if values.len() == 1 {
+            return Rc::new(ArrayNode::Leaf(values[0].clone()));
+        }
+        let mid = values.len() / 2;
+        Rc::new(ArrayNode::Branch(
+            Self::build(&values[..mid]),
+            Self::build(&values[mid..]),
+        ))
+    }
+
+    fn set_node(
+        node: &Rc<ArrayNode<T>>,
+        l: usize,
+        r: usize,
+        pos: usize,
+        value: T,
+    ) -> Rc<ArrayNode<T>> {
+        match node.as_ref() {
+            ArrayNode::Leaf(_) => Rc::new(ArrayNode::Leaf(value)),
+            ArrayNode::Branch(left, right) => {
+                let mid = l + (r - l) / 2;
+                if pos < mid {
+                    Rc::new(ArrayNode::Branch(
+                        Self::set_node(left, l, mid, pos, value),
+                        right.clone(),
+                    ))
+                } else {
+                    Rc::new(ArrayNode::Branch(
+                        left.clone(),
+                        Self::set_node(right, mid, r, pos, value),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn persistent_array_test() {
+    let first = PersistentArray::from_slice(&[1, 2, 3, 4, 5]);
+    let second = first.set(1, 20);
+    let third = second.set(4, 50).set(0, 10);
+    assert_eq!(first.to_vec(), vec![1, 2, 3, 4, 5]);
+    assert_eq!(second.to_vec(), vec![1, 20, 3, 4, 5]);
+    assert_eq!(third.to_vec(), vec![10, 20, 3, 4, 50]);
+    assert_eq!(third.clone().get(4), Some(&50));
+    assert_eq!(third.get(5), None);
+    assert!(PersistentArray::<u8>::from_slice(&[]).is_empty());
+}
+
+#[derive(Clone, Copy)]
+struct KthNode {
+    left: usize,
+    right: usize,
+    count: usize,
+}
+
+/*
+* Persistent segment tree over the sorted distinct values of an array.
+* The version i counts the values of the first i elements, so the difference of the versions r and l
+* describes the subarray [l, r). It answers the k-th smallest value and the number of values less than x
+* in any subarray in O(log n), the construction takes O(n log n) time and memory.
+*/
+pub struct PersistentKthTree<T> {
+    values: Vec<T>,
+    nodes: Vec<KthNode>,
+    roots: Vec<usize>,
+}
+
+impl<T: Ord + Clone> PersistentKthTree<T> {
+    pub fn from_slice(arr: &[T]) -> Self {
+        let mut values = arr.to_vec();
+        values.sort();
+        values.dedup();
+        // the node 0 is the empty tree, its children point to itself
+        let mut tree = PersistentKthTree {
+            values,
+            nodes: vec![KthNode {
+                left: 0,
+                right: 0,
+                count: 0,
+            }],
+            roots: vec![0],
+        };
+        for value in arr {
+            let pos = tree.values.binary_search(value).unwrap();
+            let root = tree.insert(*tree.roots.last().unwrap(), 0, tree.values.len(), pos);
+            tree.roots.push(root);
+        }
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.roots.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /*
+     * returns the k-th smallest value (numbering from zero) in [l, r)
+     */
+    pub fn kth_smallest(&self, l: usize, r: usize, mut k: usize) -> Option<&T> {
+        assert!(l <= r && r <= self.len());
+        if k >= r - l {
+            return None;
+        }
+        let (mut left_node, mut right_node) = (self.roots[l], self.roots[r]);
+        let (mut lo, mut hi) = (0, self.values.len());
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            let count = self.nodes[self.nodes[right_node].left].count
+                - self.nodes[self.nodes[left_node].left].count;
+            if k < count {
+                left_node = self.nodes[left_node].left;
+                right_node = self.nodes[right_node].left;
+                hi = mid;
+            } else {
+                k -= count;
+                left_node = self.nodes[left_node].right;
+                right_node = self.nodes[right_node].right;
+                lo = mid;
+            }
+        }
+        Some(&self.values[lo])
+    }
+
+    /*
+     * returns the number of values less than x in [l, r)
+     */
+    pub fn count_less(&self, l: usize, r: usize, x: &T) -> usize {
+        assert!(l <= r && r <= self.len());
+        let bound = self.values.partition_point(|value| value < x);
+        self.prefix_count(self.roots[r], bound) - self.prefix_count(self.roots[l], bound)
+    }
+
+    // the number of values with positions less than bound in the version
+    fn prefix_count(&self, mut node: usize, bound: usize) -> usize {
+        let (mut lo, mut hi) = (0, self.values.len());
+        let mut res = 0;
+        while node != 0 && lo < bound {
+            if hi <= bound {
+                return res + self.nodes[node].count;
+            }
+            let mid = lo + (hi - lo) / 2;
+            if bound <= mid {
+                node = self.nodes[node].left;
+                hi = mid;
+            } else {
+                res += self.nodes[self.nodes[node].left].count;
+                node = self.nodes[node].right;
+                lo = mid;
+            }
+        }
+        res
+    }
+
+    fn insert(&mut self, node: usize, lo: usize, hi: usize, pos: usize) -> usize {
+        let mut new_node = self.nodes[node];
+        new_node.count += 1;
+        if hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if pos < mid {
+                new_node.left = self.insert(new_node.left, lo, mid, pos);
+            } else {
+                new_node.right = self.insert(new_node.right, mid, hi, pos);
+            }
+        }
+        self.nodes.push(new_node);
+        self.nodes.len() - 1
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn persistent_kth_tree_test() {
+    let arr: Vec<u32> = crate::random::xorshift_32(13)
+        .take(300)
+        .map(|value| value % 50)
+        .collect();
+    let tree = PersistentKthTree::from_slice(&arr);
+    for l in (0..arr.len()).step_by(11) {
+        for r in (l..=arr.len()).step_by(17) {
+            let mut sorted = arr[l..r].to_vec();
+            sorted.sort_unstable();
+            for (k, value) in sorted.iter().enumerate() {
+                assert_eq!(tree.kth_smallest(l, r, k), Some(value));
+            }
+            assert_eq!(tree.kth_smallest(l, r, sorted.len()), None);
+            for x in [0, 10, 25, 49, 50] {
+                assert_eq!(
+                    tree.count_less(l, r, &x),
+                    sorted.partition_point(|value| *value < x)
+                );
+            }
+        }
+    }
+    let tree = PersistentKthTree::from_slice(&["pear", "apple", "plum", "fig"]);
+    assert_eq!(tree.kth_smallest(1, 4, 1), Some(&"fig"));
+    assert_eq!(tree.count_less(0, 4, &"grape"), 2);
+    assert!(PersistentKthTree::<u8>::from_slice(&[]).is_empty());
+}
+
+struct StackNode<T> {
+    value: T,
+    next: Option<Rc<StackNode<T>>>,
+}
+
+/*
+* Persistent stack (immutable singly linked list). push and pop return new versions sharing
+* the tail with the old one, all operations and cloning take O(1).
+*/
+pub struct PersistentStack<T> {
+    head: Option<Rc<StackNode<T>>>,
+    len: usize,
+}
+
+impl<T> PersistentStack<T> {
+    pub fn new() -> Self {
+        PersistentStack { head: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.value)
+    }
+
+    pub fn push(&self, value: T) -> Self {
+        PersistentStack {
+            head: Some(Rc::new(StackNode {
+                value,
+                next: self.head.clone(),
+            })),
+            len: self.len + 1,
+        }
+    }
+
+    /*
+     * returns the top element and the version without it
+     */
+    pub fn pop(&self) -> Option<(&T, Self)> {
+        self.head.as_ref().map(|node| {
+            (
+                &node.value,
+                PersistentStack {
+                    head: node.next.clone(),
+                    len: self.len - 1,
+                },
+            )
+        })
+    }
+
+    /*
+     * iterates from the top to the bottom
+     */
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut node = self.head.as_deref();
+        std::iter::from_fn(move || {
+            let current = node?;
+            node = current.next.as_deref();
+            Some(&current.value)
+        })
+    }
+
+    fn reversed(&self) -> Self
+    where
+        T: Clone,
+    {
+        self.iter()
+            .fold(PersistentStack::new(), |acc, value| acc.push(value.clone()))
+    }
+}
+
+impl<T> Clone for PersistentStack<T> {
+    fn clone(&self) -> Self {
+        PersistentStack {
+            head: self.head.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Default for PersistentStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for PersistentStack<T> {
+    // the nodes are released iteratively, so a long list doesn't overflow the call stack
+    fn drop(&mut self) {
+        let mut head = self.head.take();
+        while let Some(node) = head {
+            match Rc::try_unwrap(node) {
+                Ok(mut node) => head = node.next.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn persistent_stack_test() {
+    let empty = PersistentStack::new();
+    let first = empty.push(1);
+    let second = first.push(2).push(3);
+    let (top, popped) = second.pop().unwrap();
+    assert_eq!(*top, 3);
+    let branch = popped.push(4);
+    assert_eq!(second.iter().copied().collect::<Vec<_>>(), vec![3, 2, 1]);
+    assert_eq!(branch.iter().copied().collect::<Vec<_>>(), vec![4, 2, 1]);
+    assert_eq!(first.peek(), Some(&1));
+    assert_eq!(branch.len(), 3);
+    assert!(empty.pop().is_none());
+
+    let mut long = PersistentStack::new();
+    for idx in 0..200000 {
+        long = long.push(idx);
+    }
+    assert_eq!(long.len(), 200000);
+}
+
+/*
+* Persistent queue on two persistent stacks: the elements are pushed to the rear stack and popped
+* from the front one, the rear is reversed when the front becomes empty.
+* The front is empty only for the empty queue, so peek is O(1). Operations take amortized O(1)
+* when every version is popped at most once, cloning a version is O(1).
+*/
+pub struct PersistentQueue<T> {
+    front: PersistentStack<T>,
+    rear: PersistentStack<T>,
+}
+
+impl<T: Clone> PersistentQueue<T> {
+    pub fn new() -> Self {
+        PersistentQueue {
+            front: PersistentStack::new(),
+            rear: PersistentStack::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.front.len() + self.rear.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.front.is_empty()
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.front.peek()
+    }
+
+    pub fn push_back(&self, value: T) -> Self {
+        Self::make(self.front.clone(), self.rear.push(value))
+    }
+
+    /*
+     * returns the first element and the version without it
+     */
+    pub fn pop_front(&self) -> Option<(&T, Self)> {
+        let (value, front) = self.front.pop()?;
+        Some((value, Self::make(front, self.rear.clone())))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let rear: Vec<&T> = self.rear.iter().collect();
+        self.front.iter().chain(rear.into_iter().rev())
+    }
+
+    fn make(front: PersistentStack<T>, rear: PersistentStack<T>) -> Self {
+        if front.is_empty() {
+            PersistentQueue {
+                front: rear.reversed(),
+                rear: PersistentStack::new(),
+            }
+        } else {
+            PersistentQueue { front, rear }
+        }
+    }
+}
+
+impl<T: Clone> Clone for PersistentQueue<T> {
+    fn clone(&self) -> Self {
+        PersistentQueue {
+            front: self.front.clone(),
+            rear: self.rear.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Default for PersistentQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn persistent_queue_test() {
+    let empty = PersistentQueue::new();
+    let first = empty.push_back(1).push_back(2).push_back(3);
+    let (value, second) = first.pop_front().unwrap();
+    assert_eq!(*value, 1);
+    let third = second.push_back(4);
+    let (value, fourth) = third.pop_front().unwrap();
+    assert_eq!(*value, 2);
+    assert_eq!(first.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(third.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+    assert_eq!(fourth.iter().copied().collect::<Vec<_>>(), vec![3, 4]);
+    assert_eq!(fourth.peek(), Some(&3));
+    assert_eq!(fourth.len(), 2);
+    assert!(empty.is_empty());
+    assert!(empty.pop_front().is_none());
+
+    let mut queue = PersistentQueue::new();
+    let mut expected = std::collections::VecDeque::new();
+    for idx in 0..1000 {
+        if idx % 3 == 2 {
+            let (value, next) = queue.pop_front().unwrap();
+            assert_eq!(Some(value), expected.front());
+            queue = next;
+            expected.pop_front();
+        } else {
+            queue = queue.push_back(idx);
+            expected.push_back(idx);
+        }
+    }
+    assert!(queue.iter().eq(expected.iter()));
+}